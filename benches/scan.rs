@@ -0,0 +1,77 @@
+//! Benchmark for scanning search paths.
+//!
+//! Generates a fixture tree of .desktop files spread over several search paths and times
+//! `get_apps_in_search_paths` over it. Compare the sequential and parallel scanners with
+//!
+//! ```sh
+//! cargo bench --bench scan
+//! cargo bench --bench scan --features parallel
+//! ```
+use applications::common::SearchPath;
+use applications::get_apps_in_search_paths;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const SEARCH_PATHS: usize = 8;
+const FILES_PER_PATH: usize = 500;
+const ITERATIONS: u32 = 10;
+
+fn generate_fixture(root: &Path) -> Vec<SearchPath> {
+    (0..SEARCH_PATHS)
+        .map(|i| {
+            let dir = root.join(format!("data-{i}/applications"));
+            let nested = dir.join("vendor");
+            std::fs::create_dir_all(&nested).unwrap();
+            for j in 0..FILES_PER_PATH {
+                // every other search path shadows half of the previous one's desktop file IDs
+                let id = if i % 2 == 1 && j % 2 == 0 {
+                    format!("app-{}-{j}", i - 1)
+                } else {
+                    format!("app-{i}-{j}")
+                };
+                let parent = if j % 10 == 0 { &nested } else { &dir };
+                std::fs::write(
+                    parent.join(format!("{id}.desktop")),
+                    format!(
+                        "[Desktop Entry]\nType=Application\nName=App {i} {j}\nExec=/usr/bin/app-{i}-{j} %U\nIcon=app-{i}-{j}\nCategories=Utility;\n"
+                    ),
+                )
+                .unwrap();
+            }
+            SearchPath::new(dir, 2)
+        })
+        .collect()
+}
+
+fn main() {
+    let fixture = tempfile::tempdir().unwrap();
+    let search_paths = generate_fixture(fixture.path());
+
+    // warm up the page cache
+    let expected = get_apps_in_search_paths(&search_paths).unwrap();
+
+    let mut total = Duration::ZERO;
+    let mut fastest = Duration::MAX;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        let apps = get_apps_in_search_paths(&search_paths).unwrap();
+        let elapsed = start.elapsed();
+        assert_eq!(apps, expected, "scan output must be deterministic");
+        total += elapsed;
+        fastest = fastest.min(elapsed);
+    }
+
+    println!(
+        "scan ({}): {} search paths, {} files, {} apps: mean {:?}, fastest {:?}",
+        if cfg!(feature = "parallel") {
+            "parallel"
+        } else {
+            "sequential"
+        },
+        SEARCH_PATHS,
+        SEARCH_PATHS * FILES_PER_PATH,
+        expected.len(),
+        total / ITERATIONS,
+        fastest
+    );
+}
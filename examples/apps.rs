@@ -1,7 +1,11 @@
+#[allow(unused_imports)]
 use applications::{common::SearchPath, AppInfo, AppInfoContext, AppTrait, get_default_search_paths};
+#[allow(clippy::single_component_path_imports)]
 use env_logger;
+#[allow(clippy::single_component_path_imports)]
 use log;
 
+#[allow(unused_variables)]
 fn main() {
     // Initialize the logger
     env_logger::init();
@@ -1,3 +1,4 @@
+#[allow(unused_imports)]
 use applications::{load_icon, utils::image::RustImage};
 
 fn main() {
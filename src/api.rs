@@ -1,7 +1,19 @@
-use crate::common::{App, AppInfo, AppInfoContext, AppTrait, SearchPath};
-use crate::platforms::{get_all_apps, get_frontmost_application, get_running_apps, open_file_with};
-use anyhow::Result;
-use std::path::PathBuf;
+use crate::common::{
+    Activation, App, AppInfo, AppInfoContext, AppOrdering, AppSourceKind, AppTrait, Launch,
+    NotSupported, RunningApp, SearchPath, Window,
+};
+use crate::platforms::{
+    activate_window, apps_for_file, apps_for_mime_type, apps_for_scheme, default_app_for_mime_type,
+    frontmost_application, get_default_search_paths, launch_app, open, open_file_with,
+    running_app_processes, set_default_app_for_mime_type, windows,
+};
+use crate::provider::{
+    AppProvider, MergeRule, ProviderEntry, SearchPathProvider, BUILTIN_PRIORITY,
+};
+use crate::utils::executable::path_executables;
+use crate::utils::parallel;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicBool;
 use std::sync::{self, Arc, Mutex};
 use std::thread;
@@ -12,6 +24,116 @@ impl AppInfoContext {
             cached_apps: Arc::new(Mutex::new(vec![])),
             refreshing: Arc::new(AtomicBool::new(false)),
             extra_search_paths,
+            replace_default_search_paths: false,
+            ordering: AppOrdering::default(),
+            path_executables: false,
+            sources: vec![],
+            providers: vec![],
+            merge_rule: MergeRule::default(),
+            package_owners: false,
+        }
+    }
+
+    /// Create a context that scans only `search_paths`, instead of adding them to the platform's defaults.
+    /// Use `get_default_search_paths()` as a starting point to customize the defaults.
+    pub fn with_search_paths(search_paths: Vec<SearchPath>) -> Self {
+        AppInfoContext {
+            replace_default_search_paths: true,
+            ..Self::new(search_paths)
+        }
+    }
+
+    /// The search paths the built-in provider scans: the platform's defaults, unless they're replaced,
+    /// then the extra search paths
+    pub fn search_paths(&self) -> Vec<SearchPath> {
+        let mut search_paths = if self.replace_default_search_paths {
+            vec![]
+        } else {
+            get_default_search_paths()
+        };
+        search_paths.extend(self.extra_search_paths.iter().cloned());
+        search_paths
+    }
+
+    /// Set the order of the apps returned by `get_all_apps`, apps are sorted by name by default
+    pub fn with_ordering(mut self, ordering: AppOrdering) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
+    /// List the executables on `$PATH` along with the apps, each linked to the app that runs it, if any
+    pub fn with_path_executables(mut self, path_executables: bool) -> Self {
+        self.path_executables = path_executables;
+        self
+    }
+
+    /// Only keep the apps from `sources`, e.g. `vec![AppSourceKind::Flatpak, AppSourceKind::Snap]`
+    pub fn with_sources(mut self, sources: Vec<AppSourceKind>) -> Self {
+        self.sources = sources;
+        self
+    }
+
+    /// Look up the distro package (dpkg, rpm or pacman) that installed each app, see [`AppSource::Package`](crate::AppSource::Package).
+    /// Only has an effect on Linux.
+    pub fn with_package_owners(mut self, package_owners: bool) -> Self {
+        self.package_owners = package_owners;
+        self
+    }
+
+    /// Register a provider along with the built-in one. Providers with a higher priority win conflicts,
+    /// the built-in provider has priority [`BUILTIN_PRIORITY`].
+    pub fn with_provider(mut self, provider: impl AppProvider + 'static, priority: i32) -> Self {
+        self.register_provider(Arc::new(provider), priority);
+        self
+    }
+
+    pub fn register_provider(&mut self, provider: Arc<dyn AppProvider>, priority: i32) {
+        self.providers.push(ProviderEntry { provider, priority });
+    }
+
+    /// Set how apps from different providers are merged, apps with the same id are merged by default
+    pub fn with_merge_rule(mut self, merge_rule: MergeRule) -> Self {
+        self.merge_rule = merge_rule;
+        self
+    }
+
+    /// All providers including the built-in one, from the highest priority to the lowest.
+    /// Providers with the same priority keep their registration order, after the built-in one.
+    pub fn providers(&self) -> Vec<ProviderEntry> {
+        let mut providers = vec![ProviderEntry {
+            provider: Arc::new(SearchPathProvider),
+            priority: BUILTIN_PRIORITY,
+        }];
+        providers.extend(self.providers.iter().cloned());
+        providers.sort_by_key(|entry| std::cmp::Reverse(entry.priority));
+        providers
+    }
+
+    /// Directories to watch to know when the apps should be refreshed, as reported by the providers
+    pub fn watch_paths(&self) -> Vec<PathBuf> {
+        let mut watch_paths: Vec<PathBuf> = vec![];
+        for entry in self.providers() {
+            for path in entry.provider.watch_paths(self) {
+                if !watch_paths.contains(&path) {
+                    watch_paths.push(path);
+                }
+            }
+        }
+        watch_paths
+    }
+
+    /// The cached app with this path, else the app loaded from the path, e.g. for hidden .desktop files
+    fn app_at_path(&self, path: PathBuf) -> Result<App> {
+        let cached = self
+            .cached_apps
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|app| app.app_desktop_path == path)
+            .cloned();
+        match cached {
+            Some(app) => Ok(app),
+            None => App::from_path(&path),
         }
     }
 
@@ -21,22 +143,55 @@ impl AppInfoContext {
             return;
         }
         self.refreshing.store(true, sync::atomic::Ordering::Relaxed);
-        let refreshing = Arc::clone(&self.refreshing);
+        let refreshing = RefreshingGuard(Arc::clone(&self.refreshing));
         thread::spawn(move || {
-            ctx.refresh_apps().unwrap();
-            refreshing.store(false, sync::atomic::Ordering::Relaxed);
+            let _refreshing = refreshing;
+            if let Err(e) = ctx.refresh_apps() {
+                log::warn!("Failed to refresh the apps: {:#}", e);
+            }
         });
     }
 }
 
+/// Clears the refreshing flag when a refresh ends, however it ends
+struct RefreshingGuard(Arc<AtomicBool>);
+
+impl Drop for RefreshingGuard {
+    fn drop(&mut self) {
+        self.0.store(false, sync::atomic::Ordering::Relaxed);
+    }
+}
+
 impl AppInfo for AppInfoContext {
-    /// Refresh cache of all apps, this is synchronous and could take a few seconds, especially on Mac
+    /// Refresh cache of all apps, this is synchronous and could take a few seconds, especially on Mac.
+    /// A provider that fails is logged and skipped, the apps of the others are kept.
     fn refresh_apps(&mut self) -> Result<()> {
         self.refreshing.store(true, sync::atomic::Ordering::Relaxed);
-        let apps = get_all_apps(&self.extra_search_paths)?;
+        let _refreshing = RefreshingGuard(Arc::clone(&self.refreshing));
+        let results = parallel::map_ordered(self.providers(), |entry| {
+            entry
+                .provider
+                .scan(self)
+                .with_context(|| format!("Provider {} failed", entry.provider.id()))
+        });
+        let results = results
+            .into_iter()
+            .filter_map(|result| result.map_err(|e| log::warn!("{:#}", e)).ok())
+            .collect();
+        let mut apps = self.merge_rule.merge(results);
+        #[cfg(target_os = "linux")]
+        if self.package_owners {
+            crate::platforms::resolve_package_owners(&mut apps);
+        }
+        if self.path_executables {
+            let tools = path_executables(&apps);
+            apps.extend(tools);
+        }
+        if !self.sources.is_empty() {
+            apps.retain(|app| self.sources.contains(&app.source.kind()));
+        }
+        self.ordering.sort(&mut apps);
         *self.cached_apps.lock().unwrap() = apps;
-        self.refreshing
-            .store(false, sync::atomic::Ordering::Relaxed);
         Ok(())
     }
 
@@ -48,12 +203,138 @@ impl AppInfo for AppInfoContext {
         open_file_with(file_path, app)
     }
 
+    fn get_default_app_for_mime_type(&self, mime_type: &str) -> Result<Option<App>> {
+        default_app_for_mime_type(mime_type)?
+            .map(|path| self.app_at_path(path))
+            .transpose()
+    }
+
+    fn get_apps_for_mime_type(&self, mime_type: &str) -> Result<Vec<App>> {
+        apps_for_mime_type(mime_type)?
+            .into_iter()
+            .map(|path| self.app_at_path(path))
+            .collect()
+    }
+
+    fn get_apps_for_file(&self, path: &Path) -> Result<Vec<App>> {
+        apps_for_file(path)?
+            .into_iter()
+            .map(|path| self.app_at_path(path))
+            .collect()
+    }
+
+    /// The handlers the platform knows, then the cached apps that declare the scheme in [`App::url_schemes`]
+    fn get_apps_for_scheme(&self, scheme: &str) -> Result<Vec<App>> {
+        let scheme = scheme.to_ascii_lowercase();
+        let mut apps = apps_for_scheme(&scheme)?
+            .into_iter()
+            .map(|path| self.app_at_path(path))
+            .collect::<Result<Vec<App>>>()?;
+        for app in self.get_all_apps() {
+            let known = apps
+                .iter()
+                .any(|other| other.app_desktop_path == app.app_desktop_path);
+            if !known && app.url_schemes.contains(&scheme) {
+                apps.push(app);
+            }
+        }
+        Ok(apps)
+    }
+
+    fn set_default_app_for_mime_type(&self, mime_type: &str, app: &App) -> Result<()> {
+        set_default_app_for_mime_type(mime_type, &app.app_desktop_path)
+    }
+
+    fn set_default_app_for_url_scheme(&self, scheme: &str, app: &App) -> Result<()> {
+        let mime_type = format!("x-scheme-handler/{}", scheme.to_ascii_lowercase());
+        set_default_app_for_mime_type(&mime_type, &app.app_desktop_path)
+    }
+
+    fn open(&self, target: &str) -> Result<Launch> {
+        let (path, argv) = open(target, false)?;
+        Ok(Launch {
+            app: self.app_at_path(path)?,
+            argv,
+        })
+    }
+
+    fn open_dry_run(&self, target: &str) -> Result<Launch> {
+        let (path, argv) = open(target, true)?;
+        Ok(Launch {
+            app: self.app_at_path(path)?,
+            argv,
+        })
+    }
+
     fn get_running_apps(&self) -> Vec<App> {
-        get_running_apps()
+        match self.get_running_app_processes() {
+            Ok(running) => running.into_iter().map(|running| running.app).collect(),
+            Err(e) => {
+                log::warn!("Failed to list running apps: {}", e);
+                vec![]
+            }
+        }
+    }
+
+    fn get_running_app_processes(&self) -> Result<Vec<RunningApp>> {
+        let mut running = running_app_processes(&self.get_all_apps())?;
+        if let Ok(frontmost) = self.get_frontmost_application() {
+            for running in running.iter_mut() {
+                running.is_frontmost = running.app.app_desktop_path == frontmost.app_desktop_path;
+            }
+        }
+        Ok(running)
     }
 
     fn get_frontmost_application(&self) -> Result<App> {
-        get_frontmost_application()
+        frontmost_application(&self.get_all_apps())
+    }
+
+    fn get_windows(&self) -> Result<Vec<Window>> {
+        windows(&self.get_all_apps())
+    }
+
+    fn get_app_windows(&self, app: &App) -> Result<Vec<Window>> {
+        Ok(self
+            .get_windows()?
+            .into_iter()
+            .filter(|window| {
+                window
+                    .app
+                    .as_ref()
+                    .is_some_and(|window_app| window_app.app_desktop_path == app.app_desktop_path)
+            })
+            .collect())
+    }
+
+    fn activate(&self, app: &App) -> Result<Activation> {
+        match self.get_app_windows(app) {
+            Ok(windows) if !windows.is_empty() => {
+                return Ok(Activation::Focused(activate_window(&windows)?));
+            }
+            Ok(_) => {}
+            // `open -a` brings a running app to the front, elsewhere launching it again could start
+            // a second instance
+            Err(e) if !cfg!(target_os = "macos") => {
+                let is_running = self.get_running_app_processes().map_or(true, |running| {
+                    running
+                        .iter()
+                        .any(|running| running.app.app_desktop_path == app.app_desktop_path)
+                });
+                if is_running {
+                    return Err(NotSupported(format!(
+                        "{} may be running, but its windows can't be listed to focus one: {}",
+                        app.name, e
+                    ))
+                    .into());
+                }
+            }
+            Err(e) => log::debug!("Failed to list the windows of {}: {}", app.name, e),
+        }
+        Ok(Activation::Launched(Launch {
+            app: app.clone(),
+            argv: launch_app(app)?,
+        }))
     }
 
     fn is_refreshing(&self) -> bool {
@@ -65,13 +346,23 @@ impl AppInfo for AppInfoContext {
     }
 }
 
+/// All apps in the default search paths and `extra_search_paths`, sorted like [`AppInfoContext`] does by default
+pub fn get_all_apps(extra_search_paths: &[SearchPath]) -> Result<Vec<App>> {
+    let mut apps = crate::platforms::get_all_apps(extra_search_paths)?;
+    AppOrdering::default().sort(&mut apps);
+    Ok(apps)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::common::{AppInfo, AppInfoContext, AppTrait};
+    use crate::common::{
+        AppInfo, AppInfoContext, AppKind, AppOrdering, AppTrait, NotSupported, SearchPath,
+    };
     use crate::utils::image::RustImage;
     use std::{thread, time::Duration};
 
     #[test]
+    #[allow(clippy::bool_assert_comparison, clippy::len_zero)]
     fn test_app_info() {
         let mut ctx = AppInfoContext::new(vec![]);
         assert_eq!(ctx.get_all_apps().len(), 0);
@@ -90,6 +381,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::len_zero)]
     fn get_all_apps() {
         let mut ctx = AppInfoContext::new(vec![]);
         ctx.refresh_apps().unwrap();
@@ -98,6 +390,218 @@ mod tests {
         assert!(apps.len() > 0);
     }
 
+    #[test]
+    fn get_running_apps() {
+        let mut ctx = AppInfoContext::new(vec![]);
+        ctx.refresh_apps().unwrap();
+        // the frontmost app may not be found, e.g. without a display server, it must not fail the listing
+        let running = ctx.get_running_apps();
+        if let Ok(processes) = ctx.get_running_app_processes() {
+            assert!(processes.iter().filter(|app| app.is_frontmost).count() <= 1);
+        }
+        println!("Running Apps: {:#?}", running.len());
+    }
+
+    #[test]
+    fn get_all_apps_is_stable() {
+        for ordering in [AppOrdering::Name, AppOrdering::Id, AppOrdering::Discovery] {
+            let mut ctx = AppInfoContext::new(vec![]).with_ordering(ordering);
+            ctx.refresh_apps().unwrap();
+            let first = ctx.get_all_apps();
+            ctx.refresh_apps().unwrap();
+            assert_eq!(first, ctx.get_all_apps());
+
+            let mut sorted = first.clone();
+            ordering.sort(&mut sorted);
+            assert_eq!(first, sorted);
+        }
+
+        let apps = super::get_all_apps(&[]).unwrap();
+        let mut sorted = apps.clone();
+        AppOrdering::default().sort(&mut sorted);
+        assert_eq!(apps, sorted);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn replace_default_search_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("applications");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("fixture.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Fixture\nExec=fixture\n",
+        )
+        .unwrap();
+
+        let mut ctx = AppInfoContext::with_search_paths(vec![SearchPath::new(dir.clone(), 1)]);
+        ctx.refresh_apps().unwrap();
+        let apps = ctx.get_all_apps();
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].name, "Fixture");
+
+        let mut ctx = AppInfoContext::new(vec![SearchPath::new(dir, 1)]);
+        ctx.refresh_apps().unwrap();
+        assert!(ctx.get_all_apps().len() > 1);
+    }
+
+    #[test]
+    fn search_paths() {
+        let extra = SearchPath::new(std::path::PathBuf::from("/opt/fixture"), 1);
+        let ctx = AppInfoContext::with_search_paths(vec![extra.clone()]);
+        assert_eq!(ctx.search_paths(), vec![extra.clone()]);
+
+        let ctx = AppInfoContext::new(vec![extra.clone()]);
+        let mut expected = crate::get_default_search_paths();
+        expected.push(extra);
+        assert_eq!(ctx.search_paths(), expected);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn activate_running_app() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("applications");
+        std::fs::create_dir_all(&dir).unwrap();
+        // this test is the running app
+        std::fs::write(
+            dir.join("fixture.desktop"),
+            format!(
+                "[Desktop Entry]\nType=Application\nName=Fixture\nExec={}\n",
+                std::env::current_exe().unwrap().display()
+            ),
+        )
+        .unwrap();
+
+        let mut ctx = AppInfoContext::with_search_paths(vec![SearchPath::new(dir, 1)]);
+        ctx.refresh_apps().unwrap();
+        let app = ctx.get_all_apps().remove(0);
+        // with a display, its windows are listed and it would be launched
+        if ctx.get_app_windows(&app).is_ok() {
+            return;
+        }
+        let err = ctx.activate(&app).unwrap_err();
+        assert!(err.downcast_ref::<NotSupported>().is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn path_executables() {
+        let mut ctx = AppInfoContext::with_search_paths(vec![]).with_path_executables(true);
+        ctx.refresh_apps().unwrap();
+        let apps = ctx.get_all_apps();
+        assert!(apps
+            .iter()
+            .any(|app| app.name == "sh" && matches!(app.kind, AppKind::CommandLineTool { .. })));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn filter_by_source() {
+        use crate::common::{AppSource, AppSourceKind, SearchPathKind};
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("bin");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("fixture.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Fixture\nExec=fixture\n",
+        )
+        .unwrap();
+        let tool = dir.join("tool");
+        std::fs::write(&tool, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let search_paths = vec![
+            SearchPath::new(dir.clone(), 1),
+            SearchPath::new(dir, 1).with_kind(SearchPathKind::Executables),
+        ];
+
+        let mut ctx = AppInfoContext::with_search_paths(search_paths.clone());
+        ctx.refresh_apps().unwrap();
+        assert_eq!(ctx.get_all_apps().len(), 2);
+
+        let mut ctx = AppInfoContext::with_search_paths(search_paths)
+            .with_sources(vec![AppSourceKind::Executable]);
+        ctx.refresh_apps().unwrap();
+        let apps = ctx.get_all_apps();
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].name, "tool");
+        assert_eq!(apps[0].source, AppSource::Executable);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn custom_providers() {
+        use crate::common::App;
+        use crate::provider::{AppProvider, MergeRule};
+        use std::path::PathBuf;
+
+        struct FixtureProvider(PathBuf);
+        impl AppProvider for FixtureProvider {
+            fn id(&self) -> &str {
+                "fixture"
+            }
+            fn scan(&self, _ctx: &AppInfoContext) -> anyhow::Result<Vec<App>> {
+                Ok(vec![App {
+                    name: "From Provider".to_string(),
+                    app_desktop_path: self.0.join("fixture.desktop"),
+                    ..Default::default()
+                }])
+            }
+            fn watch_paths(&self, _ctx: &AppInfoContext) -> Vec<PathBuf> {
+                vec![PathBuf::from("/opt/registry")]
+            }
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("applications");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("fixture.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Fixture\nExec=fixture\n",
+        )
+        .unwrap();
+        let names = |ctx: &mut AppInfoContext| -> Vec<String> {
+            ctx.refresh_apps().unwrap();
+            ctx.get_all_apps().into_iter().map(|app| app.name).collect()
+        };
+        let search_paths = vec![SearchPath::new(dir.clone(), 1)];
+
+        // the same app from a higher priority provider wins
+        let mut ctx = AppInfoContext::with_search_paths(search_paths.clone())
+            .with_provider(FixtureProvider(dir.clone()), 10);
+        assert_eq!(names(&mut ctx), vec!["From Provider"]);
+        assert_eq!(
+            ctx.watch_paths(),
+            vec![PathBuf::from("/opt/registry"), dir.clone()]
+        );
+
+        let mut ctx = AppInfoContext::with_search_paths(search_paths.clone())
+            .with_provider(FixtureProvider(dir.clone()), -10);
+        assert_eq!(names(&mut ctx), vec!["Fixture"]);
+
+        let mut ctx = AppInfoContext::with_search_paths(search_paths.clone())
+            .with_provider(FixtureProvider(dir), -10)
+            .with_merge_rule(MergeRule::KeepAll);
+        assert_eq!(names(&mut ctx), vec!["Fixture", "From Provider"]);
+
+        struct FailingProvider;
+        impl AppProvider for FailingProvider {
+            fn id(&self) -> &str {
+                "failing"
+            }
+            fn scan(&self, _ctx: &AppInfoContext) -> anyhow::Result<Vec<App>> {
+                Err(anyhow::anyhow!("registry unreachable"))
+            }
+        }
+
+        // a failing provider doesn't take the others' apps down with it
+        let mut ctx =
+            AppInfoContext::with_search_paths(search_paths).with_provider(FailingProvider, 10);
+        assert_eq!(names(&mut ctx), vec!["Fixture"]);
+        assert!(!ctx.is_refreshing());
+    }
+
     #[test]
     fn load_icons() {
         std::fs::create_dir_all("./icons").unwrap();
@@ -1,4 +1,6 @@
+#[allow(unused_imports)]
 use applications::{api, AppInfo, AppInfoContext};
+#[allow(unused_imports)]
 use std::path::PathBuf;
 
 fn main() {
@@ -1,9 +1,14 @@
 use applications::{App, AppTrait};
+#[allow(clippy::single_component_path_imports)]
 use env_logger;
+#[allow(unused_imports)]
 use lnk::ShellLink;
+#[allow(unused_imports)]
 use parselnk::Lnk;
+#[allow(unused_imports)]
 use std::{convert::TryFrom, path::PathBuf};
 
+#[allow(unused_variables)]
 fn main() {
     env_logger::init();
     log::set_max_level(log::LevelFilter::Debug);
@@ -2,8 +2,10 @@ use std::path::PathBuf;
 // use mslink::ShellLink;
 use applications::App;
 use lnk::ShellLink;
+#[allow(unused_imports)]
 use walkdir::WalkDir;
 
+#[allow(clippy::manual_map)]
 fn parse_lnk(path: PathBuf) -> Option<App> {
     let shortcut = ShellLink::open(&path).unwrap();
     let icon = shortcut.icon_location();
@@ -63,10 +65,12 @@ fn parse_lnk(path: PathBuf) -> Option<App> {
         icon_path,
         app_path_exe: exe,
         app_desktop_path: work_dir,
+        ..Default::default()
     })
     // println!("{:#?}", shortcut);
 }
 
+#[allow(unused_variables)]
 fn main() {
     let start_menu = "C:\\ProgramData\\Microsoft\\Windows\\Start Menu\\Programs";
     let devclean_ui_lnk = PathBuf::from(
@@ -1,11 +1,15 @@
 //! Common Data Structures
+use crate::provider::{MergeRule, ProviderEntry};
 use crate::utils::image::RustImageData;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     path::{Path, PathBuf},
     sync::{atomic::AtomicBool, Arc, Mutex},
+    time::Duration,
 };
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default, Eq, Hash)]
 pub struct App {
@@ -13,6 +17,184 @@ pub struct App {
     pub icon_path: Option<PathBuf>,
     pub app_path_exe: Option<PathBuf>, // Path to the .app file for mac, or Exec for Linux, or .exe for Windows
     pub app_desktop_path: PathBuf,     // Path to the .desktop file for Linux, .app for Mac
+    #[serde(default)]
+    pub source: AppSource,
+    #[serde(default)]
+    pub kind: AppKind,
+    /// URL schemes the app handles, lowercased, e.g. `mailto` or `zoommtg`
+    #[serde(default)]
+    pub url_schemes: Vec<String>,
+    /// Class of the app's windows when it differs from its name, `StartupWMClass` on Linux
+    #[serde(default)]
+    pub window_class: Option<String>,
+    /// macOS bundle identifier, e.g. `com.apple.Safari`
+    #[serde(default)]
+    pub bundle_id: Option<String>,
+}
+
+/// What an app entry stands for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default, Eq, Hash)]
+pub enum AppKind {
+    /// An app with its own entry: a .desktop file, an .app bundle or a shortcut
+    #[default]
+    Application,
+    /// A bare executable, e.g. found on `$PATH`
+    CommandLineTool {
+        /// Id (`app_desktop_path`) of the application whose executable this is, if any
+        desktop_app: Option<PathBuf>,
+    },
+}
+
+/// Where an app comes from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default, Eq, Hash)]
+pub enum AppSource {
+    /// Found in a search path that isn't tied to a known source
+    #[default]
+    Unknown,
+    /// A .desktop file in a system data directory like `/usr/share/applications`, usually installed by the distro's package manager.
+    /// Holds the owning package once resolved, see [`AppInfoContext::package_owners`].
+    Package(Option<PackageInfo>),
+    /// A .desktop file in the user's `$XDG_DATA_HOME/applications`, usually `~/.local/share/applications`
+    UserLocal,
+    /// A Flatpak app, launched with `flatpak run`
+    Flatpak(FlatpakInfo),
+    /// A Snap app, its .desktop file is written by snapd
+    Snap(SnapInfo),
+    /// An AppImage, its executable is the AppImage file itself
+    AppImage(AppImageInfo),
+    /// A package from the Nix store, installed in a Nix profile
+    Nix(NixInfo),
+    /// A Windows Start Menu shortcut
+    StartMenu,
+    /// A macOS app shipped with the system, in `/System`, e.g. `/System/Applications/Calculator.app`
+    MacSystem,
+    /// A macOS .app bundle outside of `/System`, e.g. in `/Applications` or `~/Applications`
+    MacApplication,
+    /// A bare executable, from a search path of kind [`SearchPathKind::Executables`] or `$PATH`
+    Executable,
+}
+
+/// [`AppSource`] without its metadata, to filter apps by source
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Eq, Hash)]
+pub enum AppSourceKind {
+    Unknown,
+    Package,
+    UserLocal,
+    Flatpak,
+    Snap,
+    AppImage,
+    Nix,
+    StartMenu,
+    MacSystem,
+    MacApplication,
+    Executable,
+}
+
+impl AppSource {
+    pub fn kind(&self) -> AppSourceKind {
+        match self {
+            AppSource::Unknown => AppSourceKind::Unknown,
+            AppSource::Package(_) => AppSourceKind::Package,
+            AppSource::UserLocal => AppSourceKind::UserLocal,
+            AppSource::Flatpak(_) => AppSourceKind::Flatpak,
+            AppSource::Snap(_) => AppSourceKind::Snap,
+            AppSource::AppImage(_) => AppSourceKind::AppImage,
+            AppSource::Nix(_) => AppSourceKind::Nix,
+            AppSource::StartMenu => AppSourceKind::StartMenu,
+            AppSource::MacSystem => AppSourceKind::MacSystem,
+            AppSource::MacApplication => AppSourceKind::MacApplication,
+            AppSource::Executable => AppSourceKind::Executable,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default, Eq, Hash)]
+pub enum FlatpakInstallationKind {
+    /// `/var/lib/flatpak`
+    #[default]
+    System,
+    /// `$XDG_DATA_HOME/flatpak`, usually `~/.local/share/flatpak`
+    User,
+}
+
+/// Flatpak metadata of an app, read from its installation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default, Eq, Hash)]
+pub struct FlatpakInfo {
+    /// Application ID, e.g. `org.gnome.Calculator`
+    pub app_id: String,
+    /// Branch of the current deployment, usually `stable`
+    pub branch: String,
+    /// Architecture of the current deployment, e.g. `x86_64`
+    pub arch: String,
+    /// Runtime ref from the app's `metadata`, e.g. `org.gnome.Platform/x86_64/46`
+    pub runtime: Option<String>,
+    /// Path to the installation, e.g. `/var/lib/flatpak`
+    pub installation: PathBuf,
+    pub installation_kind: FlatpakInstallationKind,
+}
+
+/// Snap metadata of an app, read from the snap's current revision
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default, Eq, Hash)]
+pub struct SnapInfo {
+    /// Snap name from `meta/snap.yaml`, e.g. `firefox`
+    pub name: String,
+    /// Name the snap is installed as, differs from `name` for parallel installs, e.g. `firefox_work`
+    pub instance_name: String,
+    /// Name of the app inside the snap
+    pub app: String,
+    /// Revision the `current` symlink points to, e.g. `4173`
+    pub revision: String,
+    pub version: Option<String>,
+    /// `strict`, `classic` or `devmode`
+    pub confinement: Option<String>,
+    /// Command that runs the app, e.g. `/snap/bin/firefox`
+    pub command_path: PathBuf,
+}
+
+/// AppImage metadata, read from the image without running it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default, Eq, Hash)]
+pub struct AppImageInfo {
+    /// 1 for the legacy ISO 9660 images, 2 for squashfs images
+    pub appimage_type: u8,
+    /// `X-AppImage-Version` from the embedded .desktop file
+    pub version: Option<String>,
+    /// Path of the embedded .desktop file inside the image, e.g. `/firefox.desktop`
+    pub desktop_file: Option<PathBuf>,
+    /// Path of the icon inside the image, with `.DirIcon` symlinks resolved
+    pub icon_file: Option<PathBuf>,
+}
+
+/// Package manager whose database a package was found in
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default, Eq, Hash)]
+pub enum PackageManager {
+    /// Debian, Ubuntu and derivatives
+    #[default]
+    Dpkg,
+    /// Fedora, RHEL, openSUSE and derivatives
+    Rpm,
+    /// Arch Linux and derivatives
+    Pacman,
+}
+
+/// The distro package that installed an app's .desktop file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default, Eq, Hash)]
+pub struct PackageInfo {
+    /// Package name, e.g. `firefox-esr`
+    pub name: String,
+    /// Installed version, e.g. `115.12.0esr-1~deb12u1`
+    pub version: Option<String>,
+    pub manager: PackageManager,
+}
+
+/// Nix package an app's .desktop file resolves to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default, Eq, Hash)]
+pub struct NixInfo {
+    /// Derivation name, e.g. `firefox-128.0.3`
+    pub name: String,
+    /// Store path of the package, e.g. `/nix/store/<hash>-firefox-128.0.3`
+    pub store_path: PathBuf,
+    /// Profile the app was found in, e.g. `~/.nix-profile`, None if it was found outside of a known profile
+    pub profile: Option<PathBuf>,
 }
 
 /// This trait specifies the methods that an app should implement, such as loading its logo
@@ -24,27 +206,337 @@ where
     fn from_path(path: &Path) -> Result<Self>;
 }
 
+/// Methods added after the first release have default bodies returning [`NotSupported`],
+/// so implementations outside this crate keep compiling
 pub trait AppInfo {
     /// It could take a few seconds to retrieve all apps, so a cache needs to be maintained
     /// This method is used to refresh the cache
     fn refresh_apps(&mut self) -> Result<()>;
     fn get_all_apps(&self) -> Vec<App>;
     fn open_file_with(&self, file_path: PathBuf, app: App);
+    /// The app that opens files of a MIME type by default, e.g. `application/pdf`. None if there's no default.
+    fn get_default_app_for_mime_type(&self, _mime_type: &str) -> Result<Option<App>> {
+        Err(unsupported("get_default_app_for_mime_type"))
+    }
+    /// All apps that can open files of a MIME type, the most preferred first
+    fn get_apps_for_mime_type(&self, _mime_type: &str) -> Result<Vec<App>> {
+        Err(unsupported("get_apps_for_mime_type"))
+    }
+    /// All apps that can open a file, from its detected MIME type, the default app first
+    fn get_apps_for_file(&self, _path: &Path) -> Result<Vec<App>> {
+        Err(unsupported("get_apps_for_file"))
+    }
+    /// All apps that handle links of a URL scheme, e.g. `mailto`, the default handler first
+    fn get_apps_for_scheme(&self, _scheme: &str) -> Result<Vec<App>> {
+        Err(unsupported("get_apps_for_scheme"))
+    }
+    /// Make an app the default for a MIME type, for the current user. Fails if the change doesn't take effect.
+    fn set_default_app_for_mime_type(&self, _mime_type: &str, _app: &App) -> Result<()> {
+        Err(unsupported("set_default_app_for_mime_type"))
+    }
+    /// Make an app the default handler of a URL scheme, e.g. `https` or `mailto`
+    fn set_default_app_for_url_scheme(&self, _scheme: &str, _app: &App) -> Result<()> {
+        Err(unsupported("set_default_app_for_url_scheme"))
+    }
+    /// Open a file or URL with its default app, e.g. `~/report.pdf` or `https://example.com`
+    fn open(&self, _target: &str) -> Result<Launch> {
+        Err(unsupported("open"))
+    }
+    /// Resolve the app and command line [`AppInfo::open`] would run, without running it
+    fn open_dry_run(&self, _target: &str) -> Result<Launch> {
+        Err(unsupported("open_dry_run"))
+    }
     fn get_running_apps(&self) -> Vec<App>;
+    /// The running apps with the processes they run in, matched to the cached apps where possible
+    fn get_running_app_processes(&self) -> Result<Vec<RunningApp>> {
+        Err(unsupported("get_running_app_processes"))
+    }
     fn get_frontmost_application(&self) -> Result<App>;
+    /// The open windows, in the order the window manager lists them, each linked to its app where possible
+    fn get_windows(&self) -> Result<Vec<Window>> {
+        Err(unsupported("get_windows"))
+    }
+    /// The open windows of an app
+    fn get_app_windows(&self, _app: &App) -> Result<Vec<Window>> {
+        Err(unsupported("get_app_windows"))
+    }
+    /// Focus the most recently active window of an app if it's running, else launch it.
+    /// [`NotSupported`] if the app is running but its windows can't be listed
+    fn activate(&self, _app: &App) -> Result<Activation> {
+        Err(unsupported("activate"))
+    }
     fn is_refreshing(&self) -> bool;
     fn empty_cache(&mut self);
 }
 
+/// The app a file or URL was opened with, see [`AppInfo::open`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Launch {
+    pub app: App,
+    /// The command line that was run, or that would run for [`AppInfo::open_dry_run`]
+    pub argv: Vec<String>,
+}
+
+/// A running app, see [`AppInfo::get_running_app_processes`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunningApp {
+    pub app: App,
+    /// IDs of the processes attributed to the app, in ascending order. Empty where the platform doesn't report them yet.
+    pub pids: Vec<u32>,
+    /// The process the app's other processes descend from
+    pub main_pid: Option<u32>,
+    /// Start time of each of `pids`, in clock ticks since boot where known, to tell them from processes that
+    /// reuse their PIDs later
+    #[serde(default)]
+    pub start_ticks: HashMap<u32, u64>,
+    /// When the main process started, in seconds since the Unix epoch
+    pub started_at: Option<u64>,
+    /// Name of the user running the main process
+    pub user: Option<String>,
+    /// Resident memory of all the app's processes, in bytes
+    pub memory_bytes: Option<u64>,
+    /// Whether the app is the frontmost application
+    pub is_frontmost: bool,
+}
+
+impl RunningApp {
+    /// How long [`RunningApp::terminate`] waits for a graceful exit
+    pub const TERMINATE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Quit the app: ask all its processes to exit and kill the ones still running after
+    /// [`RunningApp::TERMINATE_TIMEOUT`], or kill them right away unless `graceful`.
+    /// A PID that now belongs to another process is reported as [`TerminationOutcome::NotRunning`] and left alone.
+    /// On Linux, children the app started since it was listed are only terminated if they run in its systemd app scope.
+    pub fn terminate(&self, graceful: bool) -> Result<Vec<ProcessTermination>> {
+        self.terminate_with_timeout(graceful, Self::TERMINATE_TIMEOUT)
+    }
+
+    /// [`RunningApp::terminate`] with a custom timeout for the graceful exit
+    pub fn terminate_with_timeout(
+        &self,
+        graceful: bool,
+        timeout: Duration,
+    ) -> Result<Vec<ProcessTermination>> {
+        if self.pids.is_empty() {
+            return Err(anyhow::anyhow!("No processes known for {}", self.app.name));
+        }
+        crate::platforms::terminate(
+            &self.pids,
+            &self.start_ticks,
+            self.main_pid,
+            graceful,
+            timeout,
+        )
+    }
+}
+
+fn unsupported(method: &str) -> anyhow::Error {
+    NotSupported(format!("{method} is not supported by this AppInfo")).into()
+}
+
+/// The platform or the session can't do what was asked, e.g. find the frontmost app without a display server.
+/// It's returned inside an [`anyhow::Error`], check for it with `error.downcast_ref::<NotSupported>()`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{0}")]
+pub struct NotSupported(pub String);
+
+/// What happened to a process of an app being terminated
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TerminationOutcome {
+    /// Exited after being asked to
+    Exited,
+    /// Killed, because it didn't exit in time or termination wasn't graceful
+    Killed,
+    /// Was gone already
+    NotRunning,
+    /// Couldn't be signaled, e.g. it belongs to another user
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessTermination {
+    pub pid: u32,
+    pub outcome: TerminationOutcome,
+}
+
+/// Identifies a window, and the window system it belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WindowId {
+    /// The X11 window ID
+    X11(u32),
+    /// A Wayland toplevel, which has no ID of its own. Toplevels are numbered in the order the compositor
+    /// announced them, and keep their number for as long as the connection to the compositor.
+    Wayland(u64),
+}
+
+/// An open window, see [`AppInfo::get_windows`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Window {
+    pub id: WindowId,
+    pub title: Option<String>,
+    /// The app the window belongs to, if it could be matched
+    pub app: Option<App>,
+    /// The process that owns the window, if its client tells
+    pub pid: Option<u32>,
+    /// The X11 `WM_CLASS` class name, or the Wayland app ID
+    pub class: Option<String>,
+    /// Index of the workspace the window is on, `None` if it's on all of them or the window manager doesn't tell
+    pub workspace: Option<u32>,
+    /// Position on the screen and size, without the window manager's decorations
+    pub geometry: Option<WindowGeometry>,
+    /// Whether the window has the focus
+    pub is_focused: bool,
+}
+
+/// What [`AppInfo::activate`] did
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Activation {
+    /// The app was running, this window of it was raised and focused
+    Focused(Window),
+    /// The app had no window, it was launched
+    Launched(Launch),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// What a search path contains, and therefore how its entries are turned into apps
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SearchPathKind {
+    /// The native app entries of the platform: .desktop files on Linux, .app bundles on Mac and .lnk shortcuts on Windows
+    #[default]
+    Auto,
+    /// .desktop files (Linux)
+    DesktopEntries,
+    /// .app bundles (Mac)
+    AppBundles,
+    /// .lnk shortcuts (Windows)
+    Shortcuts,
+    /// Bare executable files, each one becomes an app named after the file
+    Executables,
+    /// AppImage files, detected by their magic bytes (Linux)
+    AppImages,
+}
+
 #[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize, Hash)]
 pub struct SearchPath {
     pub path: PathBuf,
     pub depth: u8,
+    /// Glob patterns an entry must match to be included, all entries are included if empty.
+    /// Patterns are matched against the file name and against the path relative to `path`.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Glob patterns of entries to skip, e.g. `*-url-handler.desktop`. Exclusions win over inclusions.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Follow symbolic links to directories while walking `path`
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    #[serde(default)]
+    pub kind: SearchPathKind,
 }
 
 impl SearchPath {
     pub fn new(path: PathBuf, depth: u8) -> Self {
-        Self { path, depth }
+        Self {
+            path,
+            depth,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_kind(mut self, kind: SearchPathKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_include(mut self, pattern: impl Into<String>) -> Self {
+        self.include.push(pattern.into());
+        self
+    }
+
+    pub fn with_exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Walk the search path up to `depth`, sorted by file name so the order doesn't depend on the file system.
+    /// Unreadable entries are skipped, include/exclude patterns are *not* applied, see [`SearchPath::is_match`].
+    pub fn walk(&self) -> impl Iterator<Item = walkdir::DirEntry> {
+        walkdir::WalkDir::new(&self.path)
+            .max_depth(self.depth as usize)
+            .follow_links(self.follow_symlinks)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+    }
+
+    /// Check an entry found under this search path against the include and exclude patterns
+    pub fn is_match(&self, path: &Path) -> bool {
+        let file_name = path.file_name().map(Path::new).unwrap_or(path);
+        let relative = path.strip_prefix(&self.path).unwrap_or(path);
+        let matches_any = |patterns: &[String]| {
+            patterns
+                .iter()
+                .any(|pattern| match glob::Pattern::new(pattern) {
+                    Ok(pattern) => {
+                        pattern.matches_path(file_name) || pattern.matches_path(relative)
+                    }
+                    Err(e) => {
+                        log::warn!("Invalid glob pattern {:?} in search path: {}", pattern, e);
+                        false
+                    }
+                })
+        };
+        (self.include.is_empty() || matches_any(&self.include)) && !matches_any(&self.exclude)
+    }
+}
+
+/// Order of the apps returned by [`AppInfo::get_all_apps`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AppOrdering {
+    /// Sort by name, ignoring case and accents, then by id
+    #[default]
+    Name,
+    /// Sort by id, i.e. the path to the .desktop file, .app bundle or shortcut
+    Id,
+    /// Keep the order in which apps were discovered, earlier search paths first
+    Discovery,
+}
+
+/// Key used to compare app names: NFD-decomposed, without combining marks, lowercased.
+/// So "Éclair", "eclair" and "ECLAIR" all sort together.
+fn collation_key(name: &str) -> String {
+    name.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl AppOrdering {
+    /// Sort apps in place. Apps with the same sort key keep their discovery order.
+    pub fn sort(&self, apps: &mut [App]) {
+        match self {
+            AppOrdering::Name => apps.sort_by_cached_key(|app| {
+                (
+                    collation_key(&app.name),
+                    app.name.clone(),
+                    app.app_desktop_path.clone(),
+                )
+            }),
+            AppOrdering::Id => apps.sort_by(|a, b| a.app_desktop_path.cmp(&b.app_desktop_path)),
+            AppOrdering::Discovery => {}
+        }
     }
 }
 
@@ -53,4 +545,174 @@ pub struct AppInfoContext {
     pub cached_apps: Arc<Mutex<Vec<App>>>,
     pub refreshing: Arc<AtomicBool>,
     pub extra_search_paths: Vec<SearchPath>,
+    /// Scan only `extra_search_paths`, ignoring the platform's default search paths
+    pub replace_default_search_paths: bool,
+    pub ordering: AppOrdering,
+    /// Also list the executables found on `$PATH`, as [`AppKind::CommandLineTool`] apps
+    pub path_executables: bool,
+    /// Only keep the apps from these sources, all apps are kept if empty
+    pub sources: Vec<AppSourceKind>,
+    /// Providers registered along with the built-in [`SearchPathProvider`](crate::provider::SearchPathProvider)
+    pub providers: Vec<ProviderEntry>,
+    pub merge_rule: MergeRule,
+    /// Look up the distro package that owns each [`AppSource::Package`] app (Linux)
+    pub package_owners: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(name: &str, path: &str) -> App {
+        App {
+            name: name.to_string(),
+            app_desktop_path: PathBuf::from(path),
+            ..Default::default()
+        }
+    }
+
+    fn names(apps: &[App]) -> Vec<&str> {
+        apps.iter().map(|app| app.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_search_path_patterns() {
+        let root = PathBuf::from("/usr/share/applications");
+        let search_path = SearchPath::new(root.clone(), 2);
+        assert!(search_path.is_match(&root.join("firefox.desktop")));
+
+        let search_path = search_path
+            .with_include("*.desktop")
+            .with_exclude("*-url-handler.desktop")
+            .with_exclude("wine/*");
+        assert!(search_path.is_match(&root.join("firefox.desktop")));
+        assert!(search_path.is_match(&root.join("kde4/kate.desktop")));
+        assert!(!search_path.is_match(&root.join("mimeinfo.cache")));
+        assert!(!search_path.is_match(&root.join("zoom-url-handler.desktop")));
+        assert!(!search_path.is_match(&root.join("wine/notepad.desktop")));
+    }
+
+    #[test]
+    fn test_search_path_walk() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("apps");
+        std::fs::create_dir_all(root.join("nested/deeper")).unwrap();
+        for file in ["b", "a", "nested/c", "nested/deeper/d"] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+        let relative = |search_path: &SearchPath| -> Vec<PathBuf> {
+            search_path
+                .walk()
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| entry.path().strip_prefix(&root).unwrap().to_path_buf())
+                .collect()
+        };
+        assert_eq!(
+            relative(&SearchPath::new(root.clone(), 2)),
+            vec![
+                PathBuf::from("a"),
+                PathBuf::from("b"),
+                PathBuf::from("nested/c")
+            ]
+        );
+
+        #[cfg(unix)]
+        {
+            let linked = tmp.path().join("linked");
+            std::fs::create_dir_all(&linked).unwrap();
+            std::fs::write(linked.join("e"), "").unwrap();
+            std::os::unix::fs::symlink(&linked, root.join("z")).unwrap();
+            assert!(!relative(&SearchPath::new(root.clone(), 2)).contains(&PathBuf::from("z/e")));
+            assert!(
+                relative(&SearchPath::new(root.clone(), 2).with_follow_symlinks(true))
+                    .contains(&PathBuf::from("z/e"))
+            );
+        }
+    }
+
+    #[test]
+    fn test_collation_key() {
+        assert_eq!(collation_key("Éclair"), "eclair");
+        assert_eq!(collation_key("ÅngströM"), "angstrom");
+        assert_eq!(collation_key("Firefox"), "firefox");
+    }
+
+    #[test]
+    fn test_sort_by_name() {
+        let apps = vec![
+            app("zed", "/usr/share/applications/zed.desktop"),
+            app("Éclair", "/usr/share/applications/eclair.desktop"),
+            app("Firefox", "/usr/share/applications/firefox.desktop"),
+            app("eclair", "/usr/share/applications/eclair2.desktop"),
+            app(
+                "Firefox",
+                "/home/me/.local/share/applications/firefox.desktop",
+            ),
+            app("firefox", "/usr/share/applications/firefox-esr.desktop"),
+        ];
+        let mut sorted = apps.clone();
+        AppOrdering::Name.sort(&mut sorted);
+        assert_eq!(
+            names(&sorted),
+            vec!["eclair", "Éclair", "Firefox", "Firefox", "firefox", "zed"]
+        );
+        // same name, tie broken by id
+        assert_eq!(
+            sorted[2].app_desktop_path,
+            PathBuf::from("/home/me/.local/share/applications/firefox.desktop")
+        );
+
+        // the result doesn't depend on the input order
+        let mut reversed: Vec<App> = apps.into_iter().rev().collect();
+        AppOrdering::Name.sort(&mut reversed);
+        assert_eq!(sorted, reversed);
+    }
+
+    #[test]
+    fn test_sort_by_id_and_discovery() {
+        let apps = vec![
+            app("B", "/b.desktop"),
+            app("C", "/a.desktop"),
+            app("A", "/c.desktop"),
+        ];
+        let mut sorted = apps.clone();
+        AppOrdering::Id.sort(&mut sorted);
+        assert_eq!(names(&sorted), vec!["C", "B", "A"]);
+
+        let mut sorted = apps.clone();
+        AppOrdering::Discovery.sort(&mut sorted);
+        assert_eq!(sorted, apps);
+    }
+
+    /// An implementation from before the methods with default bodies were added
+    struct MinimalAppInfo;
+
+    impl AppInfo for MinimalAppInfo {
+        fn refresh_apps(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn get_all_apps(&self) -> Vec<App> {
+            vec![]
+        }
+        fn open_file_with(&self, _file_path: PathBuf, _app: App) {}
+        fn get_running_apps(&self) -> Vec<App> {
+            vec![]
+        }
+        fn get_frontmost_application(&self) -> Result<App> {
+            Err(NotSupported("no display".to_string()).into())
+        }
+        fn is_refreshing(&self) -> bool {
+            false
+        }
+        fn empty_cache(&mut self) {}
+    }
+
+    #[test]
+    fn test_default_methods_not_supported() {
+        let ctx = MinimalAppInfo;
+        let error = ctx.open("https://example.com").unwrap_err();
+        assert!(error.downcast_ref::<NotSupported>().is_some());
+        let error = ctx.activate(&App::default()).unwrap_err();
+        assert!(error.downcast_ref::<NotSupported>().is_some());
+    }
 }
@@ -30,16 +30,24 @@ pub mod common;
 // difference platforms may have different implementation and signatures for each function, so platforms will not be public
 mod platforms;
 pub mod prelude;
+pub mod provider;
 pub mod utils;
 
-pub use common::{App, AppInfo, AppInfoContext, AppTrait};
-pub use platforms::{get_all_apps, get_default_search_paths, load_icon};
+pub use api::get_all_apps;
+pub use common::{
+    Activation, App, AppInfo, AppInfoContext, AppKind, AppOrdering, AppSource, AppSourceKind,
+    AppTrait, Launch, NotSupported, ProcessTermination, RunningApp, SearchPath, SearchPathKind,
+    TerminationOutcome, Window, WindowGeometry, WindowId,
+};
+pub use platforms::{get_apps_in_search_paths, get_default_search_paths, load_icon};
+pub use provider::{AppProvider, MergeRule};
 
 #[cfg(test)]
 mod tests {
     use crate::AppInfo;
 
     #[test]
+    #[allow(clippy::len_zero)]
     fn get_all_apps() {
         let mut ctx = crate::common::AppInfoContext::new(vec![]);
         ctx.refresh_apps().unwrap();
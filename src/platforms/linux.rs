@@ -1,13 +1,49 @@
-use crate::common::{App, AppInfo, AppInfoContext, SearchPath};
+#[cfg(feature = "appimage")]
+pub mod appimage;
+pub mod flatpak;
+pub mod launch;
+pub mod mime;
+pub mod mimeapps;
+pub mod nix;
+pub mod packages;
+pub mod procfs;
+pub mod snap;
+pub mod terminate;
+#[cfg(feature = "wayland")]
+pub mod wayland;
+#[cfg(feature = "x11")]
+pub mod x11;
+
+use crate::common::{
+    App, AppSource, NotSupported, ProcessTermination, RunningApp, SearchPath, SearchPathKind,
+    Window, WindowId,
+};
+use crate::utils::executable::{app_from_executable, is_executable};
 use crate::utils::image::{RustImage, RustImageData};
+use crate::utils::matcher;
+use crate::utils::parallel;
+use crate::utils::xdg::BaseDirectories;
 use crate::AppTrait;
 use anyhow::Result;
+use flatpak::FlatpakInstallation;
 use ini::ini;
+use launch::{DesktopEntry, Target};
+use mime::MimeDatabase;
+use mimeapps::MimeAssociations;
+use nix::NixLayout;
+use packages::PackageDatabase;
+use procfs::{ProcLayout, Process};
 use serde_derive::{Deserialize, Serialize};
+use snap::{SnapLayout, SNAPD_DESKTOP_APPLICATIONS};
 use std::collections::{HashMap, HashSet};
 use std::io::{prelude::*, BufReader};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use walkdir::WalkDir;
+#[cfg(feature = "wayland")]
+use wayland::{Toplevel, Wayland};
+#[cfg(feature = "x11")]
+use x11::X11;
 
 #[derive(Debug, PartialEq, Clone, Default, Eq, Hash, Serialize, Deserialize)]
 pub struct AppIcon {
@@ -29,8 +65,10 @@ pub fn brute_force_find_entry(
                 for entry_name in entry_names.iter() {
                     if line.starts_with(entry_name) {
                         // let entry = line.split("=").last().unwrap();
-                        let entry = line[entry_name.len() + 1..line.len()].trim();
-                        return Ok(Some(entry.to_string()));
+                        let Some(entry) = line.get(entry_name.len() + 1..) else {
+                            continue;
+                        };
+                        return Ok(Some(entry.trim().to_string()));
                     }
                 }
             }
@@ -62,14 +100,78 @@ fn clean_exec_path(exec: &str) -> String {
     cleaned.join(" ")
 }
 
+/// The schemes of the `x-scheme-handler/<scheme>` types among the `MimeType` of a .desktop file
+fn url_schemes_from_mime_types(mime_types: &[String]) -> Vec<String> {
+    let mut schemes: Vec<String> = vec![];
+    for scheme in mime_types
+        .iter()
+        .map(|mime_type| mime_type.to_ascii_lowercase())
+        .filter_map(|mime_type| {
+            mime_type
+                .strip_prefix("x-scheme-handler/")
+                .map(str::to_string)
+        })
+    {
+        if !scheme.is_empty() && !schemes.contains(&scheme) {
+            schemes.push(scheme);
+        }
+    }
+    schemes
+}
+
+/// The locale messages are shown in: `LC_ALL`, `LC_MESSAGES` or `LANG`, the first one that's set
+fn messages_locale<F>(lookup: F) -> Option<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .find_map(|key| lookup(key).filter(|value| !value.is_empty()))
+}
+
+/// The locales to look localized keys up for, the best match first, e.g. `sr_RS.UTF-8@latin` gives
+/// `sr_RS@latin`, `sr_RS`, `sr@latin` and `sr`. Nothing for the `C` and `POSIX` locales.
+/// <https://specifications.freedesktop.org/desktop-entry-spec/latest/localized-keys.html>
+fn locale_variants(locale: &str) -> Vec<String> {
+    let (locale, modifier) = match locale.split_once('@') {
+        Some((locale, modifier)) => (locale, Some(modifier)),
+        None => (locale, None),
+    };
+    let locale = locale.split('.').next().unwrap_or_default();
+    let (lang, country) = match locale.split_once('_') {
+        Some((lang, country)) => (lang, Some(country)),
+        None => (locale, None),
+    };
+    if lang.is_empty() || lang == "C" || lang == "POSIX" {
+        return vec![];
+    }
+    let mut variants = vec![];
+    if let (Some(country), Some(modifier)) = (country, modifier) {
+        variants.push(format!("{lang}_{country}@{modifier}"));
+    }
+    if let Some(country) = country {
+        variants.push(format!("{lang}_{country}"));
+    }
+    if let Some(modifier) = modifier {
+        variants.push(format!("{lang}@{modifier}"));
+    }
+    variants.push(lang.to_string());
+    variants
+}
+
 /// return a tuple, first element is the app, second element is a boolean indicating if the desktop file has display
 /// Some apps like url handlers don't have display
 /// The display indicator is not reliable, default to true. It's false iff the desktop file contains `nodisplay=true`
-pub fn parse_desktop_file(desktop_file_path: &Path) -> (App, bool) {
-    let mut app = App::default();
-    app.app_desktop_path = desktop_file_path.to_path_buf();
-    let desktop_file_path_str = desktop_file_path.to_str().unwrap();
-    let map = ini!(desktop_file_path_str);
+pub fn parse_desktop_file(desktop_file_path: &Path) -> Result<(App, bool)> {
+    let mut app = App {
+        app_desktop_path: desktop_file_path.to_path_buf(),
+        ..Default::default()
+    };
+    let desktop_file_path_str = desktop_file_path
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("Non UTF-8 path: {:?}", desktop_file_path))?;
+    let map = ini!(safe desktop_file_path_str)
+        .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", desktop_file_path_str, e))?;
     let desktop_entry_exists = map.contains_key("desktop entry");
     let mut display = true;
     if desktop_entry_exists {
@@ -77,13 +179,10 @@ pub fn parse_desktop_file(desktop_file_path: &Path) -> (App, bool) {
         if desktop_entry.contains_key("nodisplay") {
             // I don't want apps like a url handler that doesn't have GUI
             let nodisplay = desktop_entry["nodisplay"].clone();
-            match nodisplay {
-                Some(nodisplay) => {
-                    if nodisplay == "true" {
-                        display = false;
-                    }
+            if let Some(nodisplay) = nodisplay {
+                if nodisplay == "true" {
+                    display = false;
                 }
-                None => {}
             }
         }
 
@@ -91,7 +190,7 @@ pub fn parse_desktop_file(desktop_file_path: &Path) -> (App, bool) {
             .get("exec")
             .cloned()
             // try to find it by brute if not found
-            .or_else(|| brute_force_find_exec(&desktop_file_path).ok())
+            .or_else(|| brute_force_find_exec(desktop_file_path).ok())
             .flatten();
 
         if let Some(exec) = raw_exec {
@@ -100,229 +199,850 @@ pub fn parse_desktop_file(desktop_file_path: &Path) -> (App, bool) {
 
         if desktop_entry.contains_key("icon") {
             let icon = desktop_entry["icon"].clone();
-            app.icon_path = Some(PathBuf::from(icon.unwrap()));
-        } else {
-            match brute_force_find_icon(&desktop_file_path) {
-                Ok(icon) => {
-                    app.icon_path = icon.map(|icon| PathBuf::from(icon));
-                }
-                Err(_) => {}
-            };
+            app.icon_path = icon.map(PathBuf::from);
+        } else if let Ok(icon) = brute_force_find_icon(desktop_file_path) {
+            app.icon_path = icon.map(PathBuf::from);
         }
-        if desktop_entry.contains_key("name") {
-            let name = desktop_entry["name"].clone();
-            app.name = name.unwrap();
+        if let Some(Some(name)) = desktop_entry.get("name") {
+            app.name = name.clone();
+        }
+        if let Some(Some(window_class)) = desktop_entry.get("startupwmclass") {
+            app.window_class = Some(window_class.clone());
+        }
+        // the ini crate cuts values at `;`, the separator of the MimeType list,
+        // and takes the `[` of localized keys like `Name[de]` for the start of a group
+        if let Ok(entry) = DesktopEntry::load(desktop_file_path) {
+            app.url_schemes = url_schemes_from_mime_types(&entry.mime_types);
+            let locales = messages_locale(|key| std::env::var(key).ok())
+                .map(|locale| locale_variants(&locale))
+                .unwrap_or_default();
+            if let Some(name) = entry.localized_name(&locales) {
+                app.name = name.to_string();
+            }
         }
     }
-    return (app, display);
+    Ok((app, display))
 }
 
+/// `applications` inside `$XDG_DATA_HOME` and each of `$XDG_DATA_DIRS`, in order of precedence,
+/// then the Flatpak, Snap and Nix profile exports, then the directories AppImages are usually dropped in: `~/Applications` and `~/.local/bin`
 pub fn get_default_search_paths() -> Vec<SearchPath> {
-    let mut search_paths = vec![];
-    // read XDG_DATA_DIRS env var
-    let xdg_data_dirs = std::env::var("XDG_DATA_DIRS").unwrap_or("/usr/share".to_string());
-    let xdg_data_dirs: Vec<&str> = xdg_data_dirs.split(':').collect();
-    // make a string sett from xdg_data_dirs
-    let home_dir = std::env::var("HOME").unwrap();
-    let home_path = PathBuf::from(home_dir);
-    let local_share_apps = home_path.join(".local/share/applications");
-    let mut default_search_paths = vec![
-        "/usr/share/applications",
-        "/usr/share/xsessions",
-        "/etc/xdg/autostart",
-        "/var/lib/snapd/desktop/applications",
-        local_share_apps.to_str().unwrap(),
-    ];
-    for path in xdg_data_dirs {
-        default_search_paths.push(path);
-    }
-
-    for path in default_search_paths {
-        search_paths.push(SearchPath::new(PathBuf::from(path), 1));
-    }
-    search_paths
+    default_search_paths(|key| std::env::var(key).ok())
 }
 
-pub fn get_all_apps(extra_search_paths: &Vec<SearchPath>) -> Result<Vec<App>> {
-    let default_search_paths = get_default_search_paths();
-    let mut search_dirs: HashSet<SearchPath> = default_search_paths
+/// The default search paths for an environment lookup function, e.g. for tests
+pub fn default_search_paths<F>(lookup: F) -> Vec<SearchPath>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let dirs = &BaseDirectories::from_env(&lookup);
+    let mut app_dirs = dirs.data_subdirs("applications");
+    // Flatpak, Snap and Nix exports are usually in $XDG_DATA_DIRS already, but not always (e.g. in non-login shells)
+    let nix_layout = NixLayout::from_env(dirs, &lookup);
+    let exports = FlatpakInstallation::from_env(dirs, &lookup)
+        .iter()
+        .map(|installation| installation.applications_dir())
+        .chain(std::iter::once(PathBuf::from(SNAPD_DESKTOP_APPLICATIONS)))
+        .chain(nix_layout.applications_dirs())
+        .collect::<Vec<_>>();
+    for dir in exports {
+        if !app_dirs.contains(&dir) {
+            app_dirs.push(dir);
+        }
+    }
+    // desktop files can be in subdirectories, e.g. applications/kde4/kate.desktop
+    // Nix profiles can link whole directories into the store
+    let mut search_paths: Vec<SearchPath> = app_dirs
         .into_iter()
-        .filter(|dir| dir.path.exists())
-        .map(|dir| SearchPath::new(dir.path, dir.depth))
+        .map(|dir| {
+            let follow_symlinks = nix_layout.is_in_profile(&dir);
+            SearchPath::new(dir, u8::MAX).with_follow_symlinks(follow_symlinks)
+        })
         .collect();
-    // Add extra search paths
-    for path in extra_search_paths {
-        search_dirs.insert(path.clone());
-    }
-    let icons_db = find_all_app_icons()?;
-    // for each dir, search for .desktop files
-    let mut apps: HashSet<App> = HashSet::new();
-    for dir in search_dirs {
-        if !dir.path.exists() {
-            continue;
-        }
-        for entry in WalkDir::new(dir.path.clone()).max_depth(dir.depth as usize) {
-            if entry.is_err() {
-                continue;
+    if let Some(home) = &dirs.home {
+        search_paths.extend(
+            [home.join("Applications"), home.join(".local/bin")]
+                .into_iter()
+                .map(|dir| SearchPath::new(dir, 1).with_kind(SearchPathKind::AppImages)),
+        );
+    }
+    search_paths
+}
+
+/// Desktop file ID as defined by the desktop entry spec: the path relative to the search root,
+/// with `/` replaced by `-`. e.g. `<root>/kde4/kate.desktop` has the ID `kde4-kate.desktop`.
+pub fn desktop_file_id(search_root: &Path, desktop_file_path: &Path) -> String {
+    let relative = desktop_file_path
+        .strip_prefix(search_root)
+        .unwrap_or(desktop_file_path);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// A file found while walking a search path
+enum ScanEntry {
+    DesktopFile {
+        id: String,
+        path: PathBuf,
+    },
+    Executable(PathBuf),
+    #[cfg(feature = "appimage")]
+    AppImage(PathBuf),
+}
+
+/// Entries with the same key shadow each other, the one from the earliest search path wins
+#[derive(PartialEq, Eq, Hash)]
+enum ScanKey {
+    DesktopFileId(String),
+    ExecutableName(std::ffi::OsString),
+}
+
+impl ScanEntry {
+    fn key(&self) -> ScanKey {
+        match self {
+            ScanEntry::DesktopFile { id, .. } => ScanKey::DesktopFileId(id.clone()),
+            ScanEntry::Executable(path) => {
+                ScanKey::ExecutableName(path.file_name().unwrap_or_default().to_os_string())
             }
-            let entry = entry.unwrap();
-            let path = entry.path();
-            if path.extension().is_none() {
-                continue;
+            #[cfg(feature = "appimage")]
+            ScanEntry::AppImage(path) => {
+                ScanKey::ExecutableName(path.file_name().unwrap_or_default().to_os_string())
             }
+        }
+    }
+}
 
-            if path.extension().unwrap() == "desktop" && path.is_file() {
-                let (mut app, has_display) = parse_desktop_file(&path);
-                // fill icon path if .desktop file contains only icon name
-                if !has_display {
-                    continue;
-                }
-                if app.icon_path.is_some() {
-                    let icon_path = app.icon_path.clone().unwrap();
-                    if !icon_path.exists() {
-                        // let icon_name = icon_path.file_name().unwrap().to_str().unwrap();
-                        if let Some(icons) = icons_db.get(icon_path.to_str().unwrap()) {
-                            if let Some(icon) = icons.first() {
-                                app.icon_path = Some(icon.path.clone());
-                            }
-                        } else {
-                            app.icon_path = None;
-                        }
-                    }
-                }
-                apps.insert(app);
-            }
+/// List the entries under a search path according to its kind. For .desktop files, the desktop file ID is computed too.
+/// Entries are sorted by file name so the result doesn't depend on directory order.
+fn find_entries(search_path: &SearchPath) -> Vec<ScanEntry> {
+    let entries = search_path
+        .walk()
+        .map(|entry| entry.into_path())
+        .filter(|path| path.is_file() && search_path.is_match(path));
+    match search_path.kind {
+        SearchPathKind::Auto | SearchPathKind::DesktopEntries => entries
+            .filter(|path| path.extension().is_some_and(|ext| ext == "desktop"))
+            .map(|path| ScanEntry::DesktopFile {
+                id: desktop_file_id(&search_path.path, &path),
+                path,
+            })
+            .collect(),
+        SearchPathKind::Executables => entries
+            .filter(|path| is_executable(path))
+            .map(ScanEntry::Executable)
+            .collect(),
+        #[cfg(feature = "appimage")]
+        SearchPathKind::AppImages => entries
+            .filter(|path| appimage::appimage_type(path).is_some())
+            .map(ScanEntry::AppImage)
+            .collect(),
+        #[cfg(not(feature = "appimage"))]
+        SearchPathKind::AppImages => {
+            log::debug!(
+                "Skipping {:?}, AppImages need the `appimage` feature",
+                search_path.path
+            );
+            vec![]
+        }
+        kind => {
+            log::warn!(
+                "Search path kind {:?} is not supported on Linux: {:?}",
+                kind,
+                search_path.path
+            );
+            vec![]
         }
     }
-    Ok(apps.iter().cloned().collect())
 }
 
-pub fn find_all_app_icons() -> Result<HashMap<String, Vec<AppIcon>>> {
-    let hicolor_path: PathBuf = PathBuf::from("/usr/share/icons");
-    let search_dirs = vec![hicolor_path];
-    // filter out search_dirs that do not exist
-    let search_dirs: Vec<PathBuf> = search_dirs.into_iter().filter(|dir| dir.exists()).collect();
+/// Tells where a .desktop file comes from
+pub struct SourceResolver {
+    dirs: BaseDirectories,
+    flatpak_installations: Vec<FlatpakInstallation>,
+    snap_layout: SnapLayout,
+    nix_layout: NixLayout,
+}
 
-    let mut set = HashSet::new();
+impl SourceResolver {
+    pub fn discover(dirs: BaseDirectories) -> Self {
+        Self {
+            flatpak_installations: FlatpakInstallation::discover(&dirs),
+            snap_layout: SnapLayout::default(),
+            nix_layout: NixLayout::discover(&dirs),
+            dirs,
+        }
+    }
 
-    for dir in search_dirs {
-        let dir = PathBuf::from(dir);
-        if !dir.exists() {
-            continue;
+    /// Set the source of an app from the location of its .desktop file: the Flatpak installation, snap or Nix package
+    /// that exported it, else the user's or a system data directory
+    pub fn attribute(&self, app: &mut App) {
+        let in_applications =
+            |dir: &PathBuf| app.app_desktop_path.starts_with(dir.join("applications"));
+        if self.dirs.data_home.as_ref().is_some_and(in_applications) {
+            app.source = AppSource::UserLocal;
+        } else if self.dirs.data_dirs.iter().any(in_applications) {
+            app.source = AppSource::Package(None);
         }
+        flatpak::attribute_app(app, &self.flatpak_installations);
+        snap::attribute_app(app, &self.snap_layout);
+        nix::attribute_app(app, &self.nix_layout);
+    }
+}
 
-        for entry in WalkDir::new(dir.clone()) {
-            if entry.is_err() {
-                continue;
-            }
-            let entry = entry.unwrap();
+/// Parse a .desktop file into an App, resolving its icon name against `icons_db` and setting its source.
+/// Returns None for entries that shouldn't be displayed.
+fn load_desktop_app(
+    path: &Path,
+    icons_db: &HashMap<String, Vec<AppIcon>>,
+    sources: &SourceResolver,
+) -> Option<App> {
+    let (mut app, has_display) = parse_desktop_file(path)
+        .map_err(|e| log::debug!("Skipping {:?}: {}", path, e))
+        .ok()?;
+    if !has_display {
+        return None;
+    }
+    sources.attribute(&mut app);
+    // fill icon path if .desktop file contains only icon name
+    if let Some(icon_path) = app.icon_path.clone() {
+        if !icon_path.exists() {
+            app.icon_path = icons_db
+                .get(icon_path.to_str().unwrap_or_default())
+                .and_then(|icons| icons.first())
+                .map(|icon| icon.path.clone());
+        }
+    }
+    Some(app)
+}
+
+/// Look up the distro package that owns the .desktop file of each [`AppSource::Package`] app
+pub fn resolve_package_owners(apps: &mut [App]) {
+    let paths: Vec<PathBuf> = apps
+        .iter()
+        .filter(|app| matches!(app.source, AppSource::Package(_)))
+        .map(|app| app.app_desktop_path.clone())
+        .collect();
+    if paths.is_empty() {
+        return;
+    }
+    let mut owners = PackageDatabase::load().owners(&paths);
+    for app in apps.iter_mut() {
+        if let AppSource::Package(info) = &mut app.source {
+            *info = owners.remove(&app.app_desktop_path);
+        }
+    }
+}
+
+/// Scan the given search paths only, without adding the default ones.
+///
+/// Search paths are handled in order: when two directories contain a .desktop file with the same
+/// desktop file ID, the one from the earlier search path wins, as described by the XDG spec.
+/// With the `parallel` feature, directories are walked and files parsed concurrently,
+/// but the output order is the same as a sequential scan.
+/// Apps whose .desktop file is in the exports of a Flatpak installation are Flatpak apps, wherever they're found.
+pub fn get_apps_in_search_paths(search_paths: &[SearchPath]) -> Result<Vec<App>> {
+    let mut seen_dirs = HashSet::new();
+    let search_dirs: Vec<&SearchPath> = search_paths
+        .iter()
+        .filter(|dir| dir.path.exists())
+        .filter(|dir| seen_dirs.insert(*dir))
+        .collect();
+
+    let (entries, icons_db) = parallel::join(
+        || parallel::map_ordered(search_dirs, find_entries),
+        find_all_app_icons,
+    );
+    let icons_db = icons_db?;
+
+    let mut seen_keys = HashSet::new();
+    let entries: Vec<ScanEntry> = entries
+        .into_iter()
+        .flatten()
+        .filter(|entry| seen_keys.insert(entry.key()))
+        .collect();
+
+    let sources = SourceResolver::discover(BaseDirectories::new());
+    let apps = parallel::filter_map_ordered(entries, |entry| match entry {
+        ScanEntry::DesktopFile { path, .. } => load_desktop_app(&path, &icons_db, &sources),
+        ScanEntry::Executable(path) => app_from_executable(&path),
+        #[cfg(feature = "appimage")]
+        ScanEntry::AppImage(path) => appimage::load_app(&path, &icons_db),
+    });
+    let mut seen_apps = HashSet::new();
+    Ok(apps
+        .into_iter()
+        .filter(|app| seen_apps.insert(app.clone()))
+        .collect())
+}
+
+pub fn get_all_apps(extra_search_paths: &[SearchPath]) -> Result<Vec<App>> {
+    let mut search_paths = get_default_search_paths();
+    search_paths.extend(extra_search_paths.iter().cloned());
+    get_apps_in_search_paths(&search_paths)
+}
+
+/// Parse the icon size from a theme path like `/usr/share/icons/hicolor/48x48/apps/foo.png`
+fn icon_dimensions(path: &Path) -> Option<u16> {
+    path.components().rev().find_map(|c| {
+        let (width, height) = c.as_os_str().to_str()?.split_once('x')?;
+        let width = width.parse::<u16>().ok()?;
+        (height.split('@').next()? == width.to_string()).then_some(width)
+    })
+}
+
+/// Symlinks below `dir` are only followed if `follow_links`, e.g. for icon themes in Nix profiles,
+/// which link whole directories into the store
+fn find_icons_in_dir((dir, follow_links): (PathBuf, bool)) -> Vec<AppIcon> {
+    WalkDir::new(dir)
+        .follow_links(follow_links)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "png"))
+        .filter_map(|entry| {
             let path = entry.path();
-            match path.extension() {
-                Some(ext) => {
-                    if ext == "png" {
-                        let path_str = path.to_string_lossy().to_string();
-                        let split: Vec<&str> = path_str.split("/").collect();
-                        let dim_str = if split.len() < 6 {
-                            None
-                        } else {
-                            split[5].split("x").last()
-                        };
-                        let dim = match dim_str {
-                            Some(dim) => match dim.parse::<u16>() {
-                                Ok(dim) => Some(dim),
-                                Err(_) => None,
-                            },
-                            None => None,
-                        };
-                        set.insert(AppIcon {
-                            name: path.file_name().unwrap().to_str().unwrap().to_string(),
-                            path: path.to_path_buf(),
-                            dimensions: dim, // dimensions,
-                        });
-                    }
-                }
-                None => {
-                    continue;
-                }
-            }
+            Some(AppIcon {
+                name: path.file_name()?.to_str()?.to_string(),
+                path: path.to_path_buf(),
+                dimensions: icon_dimensions(path),
+            })
+        })
+        .collect()
+}
+
+/// Directories to look for icons in, in order of precedence, as described by the icon theme spec:
+/// `$HOME/.icons`, `icons` inside each XDG data directory, the exports of `flatpak_installations` and Nix profiles,
+/// then `/usr/share/pixmaps`
+pub fn icon_search_dirs(
+    dirs: &BaseDirectories,
+    flatpak_installations: &[FlatpakInstallation],
+) -> Vec<PathBuf> {
+    let mut icon_dirs: Vec<PathBuf> = dirs.home.iter().map(|home| home.join(".icons")).collect();
+    icon_dirs.extend(dirs.data_subdirs("icons"));
+    let exports = flatpak_installations
+        .iter()
+        .map(|installation| installation.icons_dir())
+        .chain(NixLayout::discover(dirs).icons_dirs())
+        .collect::<Vec<_>>();
+    for dir in exports {
+        if !icon_dirs.contains(&dir) {
+            icon_dirs.push(dir);
         }
     }
+    icon_dirs.push(PathBuf::from("/usr/share/pixmaps"));
+    icon_dirs
+}
+
+pub fn find_all_app_icons() -> Result<HashMap<String, Vec<AppIcon>>> {
+    let dirs = BaseDirectories::new();
+    find_app_icons(
+        &icon_search_dirs(&dirs, &FlatpakInstallation::discover(&dirs)),
+        &NixLayout::discover(&dirs),
+    )
+}
+
+/// Index the png icons found in `search_dirs` by icon name.
+/// Symlinks are only followed in the themes of Nix profiles, or themes that resolve into the Nix store.
+pub fn find_app_icons(
+    search_dirs: &[PathBuf],
+    nix_layout: &NixLayout,
+) -> Result<HashMap<String, Vec<AppIcon>>> {
+    // each theme is walked separately so they can be processed in parallel
+    let theme_dirs: Vec<PathBuf> = search_dirs
+        .iter()
+        .filter(|dir| dir.exists())
+        .flat_map(|dir| match std::fs::read_dir(dir) {
+            Ok(entries) => {
+                let mut entries: Vec<PathBuf> =
+                    entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+                entries.sort();
+                entries
+            }
+            Err(_) => vec![],
+        })
+        .collect();
+
+    let theme_dirs: Vec<(PathBuf, bool)> = theme_dirs
+        .into_iter()
+        .map(|dir| {
+            let follow_links =
+                nix_layout.is_in_profile(&dir) || nix_layout.store_path(&dir).is_some();
+            (dir, follow_links)
+        })
+        .collect();
+
     let mut map: HashMap<String, Vec<AppIcon>> = HashMap::new();
-    for icon in set {
+    for icon in parallel::map_ordered(theme_dirs, find_icons_in_dir)
+        .into_iter()
+        .flatten()
+    {
         let name = icon.name.clone();
         let name = &name[0..name.len() - 4]; // remove .png
-        if map.contains_key(name) {
-            map.get_mut(name).unwrap().push(icon);
-        } else {
-            map.insert(name.to_string(), vec![icon]);
-        }
+        map.entry(name.to_string()).or_default().push(icon);
     }
-    // sort icons by dimensions
+    // sort icons by dimensions, largest first; icons without dimensions go last
     for (_, icons) in map.iter_mut() {
-        icons.sort_by(|a, b| {
-            if a.dimensions.is_none() && b.dimensions.is_none() {
-                return std::cmp::Ordering::Equal;
-            }
-            if a.dimensions.is_none() {
-                return std::cmp::Ordering::Greater;
-            }
-            if b.dimensions.is_none() {
-                return std::cmp::Ordering::Less;
-            }
-            b.dimensions.unwrap().cmp(&a.dimensions.unwrap())
+        icons.dedup();
+        icons.sort_by(|a, b| match (a.dimensions, b.dimensions) {
+            (None, None) => std::cmp::Ordering::Equal,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (Some(a), Some(b)) => b.cmp(&a),
         });
     }
     Ok(map)
 }
 
 pub fn open_file_with(file_path: PathBuf, app: App) {
+    if let AppSource::Flatpak(info) = &app.source {
+        if let Err(err) = flatpak::run_command(info, &[file_path]).spawn() {
+            log::warn!("Failed to run Flatpak app {}: {}", info.app_id, err);
+        }
+        return;
+    }
     let exe_path = app.app_path_exe.unwrap();
     let exec_path_str = exe_path.to_str().unwrap();
     let file_path_str = file_path.to_str().unwrap();
-    let output = std::process::Command::new(exec_path_str)
+    std::process::Command::new(exec_path_str)
         .arg(file_path_str)
         .output()
         .expect("failed to execute process");
 }
 
-pub fn get_running_apps() -> Vec<App> {
-    todo!()
+/// The .desktop file of the default app for a MIME type, from the `mimeapps.list` files and `mimeinfo.cache`
+pub fn default_app_for_mime_type(mime_type: &str) -> Result<Option<PathBuf>> {
+    let associations = MimeAssociations::discover(&BaseDirectories::new());
+    Ok(associations
+        .default_app(mime_type)
+        .and_then(|id| associations.desktop_file(&id).map(Path::to_path_buf)))
 }
 
-/// TODO: this is not working yet, xprop gives the current app name, but we need to locate its .desktop file if possible
-/// If I need to compare app name with app apps, then this function should be moved to AppInfoContext where there is a `cached_apps`
-pub fn get_frontmost_application() -> Result<App> {
-    let output = std::process::Command::new("xprop")
-        .arg("-root")
-        .arg("_NET_ACTIVE_WINDOW")
-        .output()
-        .expect("failed to execute process");
+/// The .desktop files of all apps that can open a MIME type, the most preferred first
+pub fn apps_for_mime_type(mime_type: &str) -> Result<Vec<PathBuf>> {
+    let associations = MimeAssociations::discover(&BaseDirectories::new());
+    Ok(associations
+        .associated_apps(mime_type)
+        .iter()
+        .filter_map(|id| associations.desktop_file(id).map(Path::to_path_buf))
+        .collect())
+}
 
-    let output = std::str::from_utf8(&output.stdout).unwrap();
-    let id = output.split_whitespace().last().unwrap();
+/// The .desktop files of all apps that can open a file, from its MIME type and the types it inherits from,
+/// the default app first
+pub fn apps_for_file(path: &Path) -> Result<Vec<PathBuf>> {
+    let dirs = BaseDirectories::new();
+    let mime_db = MimeDatabase::discover(&dirs);
+    let associations = MimeAssociations::discover(&dirs);
+    let mime_types = mime_db.with_parents(&mime_db.mime_type_for_file(path));
+    Ok(associations
+        .apps_for_mime_types(&mime_types)
+        .iter()
+        .filter_map(|id| associations.desktop_file(id).map(Path::to_path_buf))
+        .collect())
+}
 
-    let output = std::process::Command::new("xprop")
-        .arg("-id")
-        .arg(id)
-        .arg("WM_CLASS")
-        .output()
-        .expect("failed to execute process");
+/// The .desktop files of the apps associated with `x-scheme-handler/<scheme>`, the default handler first
+pub fn apps_for_scheme(scheme: &str) -> Result<Vec<PathBuf>> {
+    let mime_type = format!("x-scheme-handler/{}", scheme.to_ascii_lowercase());
+    let associations = MimeAssociations::discover(&BaseDirectories::new());
+    Ok(associations
+        .apps_for_mime_types(&[mime_type])
+        .iter()
+        .filter_map(|id| associations.desktop_file(id).map(Path::to_path_buf))
+        .collect())
+}
+
+/// Make the app of a .desktop file the default for a MIME type, in the user's `mimeapps.list`
+pub fn set_default_app_for_mime_type(mime_type: &str, app_path: &Path) -> Result<()> {
+    let desktops = mimeapps::current_desktops(|key| std::env::var(key).ok());
+    mimeapps::set_default_app(&BaseDirectories::new(), &desktops, mime_type, app_path)
+}
+
+/// The .desktop file of the default app for a file or URL, and the command line that opens it.
+/// URLs are opened by the default handler of `x-scheme-handler/<scheme>`.
+fn open_command(
+    target: &str,
+    dirs: &BaseDirectories,
+    associations: &MimeAssociations,
+) -> Result<(DesktopEntry, Vec<String>)> {
+    let target = Target::parse(target);
+    let mime_types = match &target {
+        Target::Path(path) => {
+            let mime_db = MimeDatabase::discover(dirs);
+            mime_db.with_parents(&mime_db.mime_type_for_file(path))
+        }
+        Target::Url { scheme, .. } => vec![format!("x-scheme-handler/{scheme}")],
+    };
+    let desktop_file = associations
+        .apps_for_mime_types(&mime_types)
+        .iter()
+        .find_map(|id| associations.desktop_file(id))
+        .ok_or_else(|| anyhow::anyhow!("No app found to open {} files", mime_types[0]))?;
+    let entry = DesktopEntry::load(desktop_file)?;
+    let argv = entry.command_line(&[target])?;
+    Ok((entry, argv))
+}
+
+/// Open a file or URL with its default app, or only resolve the command line if `dry_run`.
+/// Returns the .desktop file of the app and the command line.
+pub fn open(target: &str, dry_run: bool) -> Result<(PathBuf, Vec<String>)> {
+    let dirs = BaseDirectories::new();
+    let (entry, argv) = open_command(target, &dirs, &MimeAssociations::discover(&dirs))?;
+    if !dry_run {
+        launch::spawn(&argv, entry.working_dir.as_deref())?;
+    }
+    Ok((entry.path, argv))
+}
+
+/// The app a process or window belongs to, by the shared matcher. Falls back to the .desktop file the process
+/// was launched from when it isn't among `apps`.
+fn app_of_hints(hints: &matcher::ProcessHints, apps: &[App]) -> Option<App> {
+    if let Some(found) = matcher::best_match(hints, apps) {
+        return Some(found.app);
+    }
+    matcher::trusted_desktop_file(hints)
+        .filter(|desktop_file| desktop_file.is_file())
+        .and_then(|desktop_file| App::from_path(desktop_file).ok())
+}
+
+/// Sum up the processes of an app. Its main process is the one the others descend from, the oldest one if there are several.
+fn running_app(
+    app: App,
+    processes: &[Process],
+    layout: &ProcLayout,
+    boot_time: Option<u64>,
+    user_names: &HashMap<u32, String>,
+) -> RunningApp {
+    let pids: Vec<u32> = processes.iter().map(|process| process.pid).collect();
+    let main = processes
+        .iter()
+        .filter(|process| process.ppid.is_none_or(|ppid| !pids.contains(&ppid)))
+        .min_by_key(|process| (process.start_ticks, process.pid));
+    let started_at = main
+        .and_then(|main| main.start_ticks)
+        .zip(boot_time)
+        .map(|(ticks, boot_time)| boot_time + ticks / layout.ticks_per_second.max(1));
+    let rss: Vec<u64> = processes
+        .iter()
+        .filter_map(|process| process.rss_bytes)
+        .collect();
+    RunningApp {
+        app,
+        main_pid: main.map(|main| main.pid),
+        start_ticks: processes
+            .iter()
+            .filter_map(|process| Some((process.pid, process.start_ticks?)))
+            .collect(),
+        started_at,
+        user: main
+            .and_then(|main| main.uid)
+            .and_then(|uid| user_names.get(&uid).cloned()),
+        memory_bytes: (!rss.is_empty()).then(|| rss.iter().sum()),
+        is_frontmost: false,
+        pids,
+    }
+}
+
+/// The apps of the processes in a proc file system, in the order of their first process
+pub fn running_apps_in(layout: &ProcLayout, apps: &[App]) -> Vec<RunningApp> {
+    let mut running: Vec<(App, Vec<Process>)> = vec![];
+    for process in procfs::processes(&layout.proc_root) {
+        let Some(app) = app_of_hints(&process.hints(), apps) else {
+            continue;
+        };
+        match running
+            .iter_mut()
+            .find(|(other, _)| other.app_desktop_path == app.app_desktop_path)
+        {
+            Some((_, processes)) => processes.push(process),
+            None => running.push((app, vec![process])),
+        }
+    }
+    let boot_time = layout.boot_time();
+    let user_names = layout.user_names();
+    running
+        .into_iter()
+        .map(|(app, processes)| running_app(app, &processes, layout, boot_time, &user_names))
+        .collect()
+}
+
+/// The running apps with their processes, matched to `apps` where possible
+pub fn running_app_processes(apps: &[App]) -> Result<Vec<RunningApp>> {
+    Ok(running_apps_in(&ProcLayout::system(), apps))
+}
+
+/// Terminate the processes of a running app, see [`RunningApp::terminate`]
+pub fn terminate(
+    pids: &[u32],
+    start_ticks: &HashMap<u32, u64>,
+    main_pid: Option<u32>,
+    graceful: bool,
+    timeout: Duration,
+) -> Result<Vec<ProcessTermination>> {
+    let layout = terminate::TerminateLayout::system();
+    Ok(terminate::terminate(
+        &layout,
+        pids,
+        start_ticks,
+        main_pid,
+        graceful,
+        timeout,
+    ))
+}
+
+/// Run `f` on the connection to the Wayland compositor. The connection is kept, so its toplevels keep their
+/// numbers from one call to the next, unless it broke.
+#[cfg(feature = "wayland")]
+fn with_wayland<R>(f: impl FnOnce(&Wayland) -> Result<R>) -> Result<R> {
+    static WAYLAND: std::sync::Mutex<Option<Wayland>> = std::sync::Mutex::new(None);
+    let mut wayland = WAYLAND.lock().unwrap_or_else(|e| e.into_inner());
+    let connected = match wayland.take() {
+        Some(connected) => connected,
+        None => Wayland::connect()?,
+    };
+    let result = f(&connected);
+    if !result.as_ref().is_err_and(wayland::is_connection_error) {
+        *wayland = Some(connected);
+    }
+    result
+}
+
+/// The app of the focused toplevel of the Wayland compositor
+#[cfg(feature = "wayland")]
+fn wayland_frontmost_application(apps: &[App]) -> Result<App> {
+    let toplevel = with_wayland(Wayland::active_toplevel)?
+        .ok_or_else(|| anyhow::anyhow!("No active window"))?;
+    app_of_hints(&toplevel.hints(), apps).ok_or_else(|| {
+        anyhow::anyhow!(
+            "No app found for the active window of app ID {:?}",
+            toplevel.app_id
+        )
+    })
+}
+
+/// On Wayland, the compositor's toplevels, falling back to Xwayland if it doesn't list them
+pub fn frontmost_application(apps: &[App]) -> Result<App> {
+    #[cfg(feature = "wayland")]
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        match wayland_frontmost_application(apps) {
+            Err(e) if e.downcast_ref::<NotSupported>().is_some() => {
+                log::debug!("{}, trying X11", e)
+            }
+            result => return result,
+        }
+    }
+    x11_frontmost_application(apps)
+}
+
+/// What is known of an X11 window: its class and the process that owns it
+#[cfg(feature = "x11")]
+fn x11_window_hints(x11: &X11, window: u32, pid: Option<u32>) -> Result<matcher::ProcessHints> {
+    let mut hints = pid
+        .and_then(|pid| Process::read(Path::new(procfs::PROC), pid))
+        .map(|process| process.hints())
+        .unwrap_or_default();
+    hints.window_classes = x11.window_class(window)?;
+    Ok(hints)
+}
+
+/// The app of the X11 window in `_NET_ACTIVE_WINDOW`, matched by the process that owns it and its `WM_CLASS`.
+/// In Wayland sessions, only Xwayland windows are seen.
+#[cfg(feature = "x11")]
+fn x11_frontmost_application(apps: &[App]) -> Result<App> {
+    let x11 = X11::connect()?;
+    let window = x11
+        .active_window()?
+        .ok_or_else(|| anyhow::anyhow!("No active window"))?;
+    let hints = x11_window_hints(&x11, window, x11.window_pid(window)?)?;
+    app_of_hints(&hints, apps).ok_or_else(|| {
+        anyhow::anyhow!(
+            "No app found for the active window of class {:?}",
+            hints.window_classes
+        )
+    })
+}
 
-    let output = std::str::from_utf8(&output.stdout).unwrap();
-    let app_name = output.split('"').nth(1).unwrap();
+/// An X11 window and the app it belongs to
+#[cfg(feature = "x11")]
+fn x11_window(x11: &X11, window: u32, active: Option<u32>, apps: &[App]) -> Result<Window> {
+    let pid = x11.window_pid(window)?;
+    let hints = x11_window_hints(x11, window, pid)?;
+    Ok(Window {
+        id: WindowId::X11(window),
+        title: x11.window_title(window)?,
+        app: app_of_hints(&hints, apps),
+        pid,
+        class: hints.window_classes.last().cloned(),
+        workspace: x11.window_workspace(window)?,
+        geometry: Some(x11.window_geometry(window)?),
+        is_focused: active == Some(window),
+    })
+}
 
-    let apps = get_all_apps(&vec![])?;
-    for app in apps {
-        if app.name == app_name {
-            return Ok(app);
+/// A Wayland toplevel as a window. The toplevel protocols don't tell its PID, workspace or geometry.
+#[cfg(feature = "wayland")]
+fn wayland_window(toplevel: &Toplevel, apps: &[App]) -> Window {
+    Window {
+        id: WindowId::Wayland(toplevel.id),
+        title: toplevel.title.clone(),
+        app: app_of_hints(&toplevel.hints(), apps),
+        pid: None,
+        class: toplevel.app_id.clone(),
+        workspace: None,
+        geometry: None,
+        is_focused: toplevel.activated == Some(true),
+    }
+}
+
+/// The windows of the Wayland compositor's toplevels
+#[cfg(feature = "wayland")]
+fn wayland_windows(wayland: &Wayland, apps: &[App]) -> Result<Vec<Window>> {
+    Ok(wayland
+        .toplevels()?
+        .iter()
+        .map(|toplevel| wayland_window(toplevel, apps))
+        .collect())
+}
+
+/// On Wayland, the compositor's toplevels, else the windows in the X11 `_NET_CLIENT_LIST`
+pub fn windows(apps: &[App]) -> Result<Vec<Window>> {
+    #[cfg(feature = "wayland")]
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        match with_wayland(|wayland| wayland_windows(wayland, apps)) {
+            Err(e) if e.downcast_ref::<NotSupported>().is_some() => {
+                log::debug!("{}, trying X11", e)
+            }
+            result => return result,
         }
     }
+    x11_windows(apps)
+}
+
+/// The windows in the X11 `_NET_CLIENT_LIST`, which lists the windows of Xwayland clients only on Wayland
+#[cfg(feature = "x11")]
+fn x11_windows(apps: &[App]) -> Result<Vec<Window>> {
+    let x11 = X11::connect()?;
+    let active = x11.active_window()?;
+    let mut windows = vec![];
+    for window in x11.client_list()? {
+        // a window can be destroyed while the others are read
+        match x11_window(&x11, window, active, apps) {
+            Ok(window) => windows.push(window),
+            Err(e) => log::debug!("Skipping window {}: {}", window, e),
+        }
+    }
+    Ok(windows)
+}
+
+/// The most recently active of an app's windows: the focused one, else the top-most in the stacking order,
+/// else the last one mapped
+#[cfg_attr(not(any(feature = "x11", feature = "wayland")), allow(dead_code))]
+fn most_recent_window<'a>(windows: &'a [Window], stacking: &[u32]) -> Option<&'a Window> {
+    windows.iter().max_by_key(|window| {
+        let position = stacking
+            .iter()
+            .position(|stacked| window.id == WindowId::X11(*stacked));
+        (window.is_focused, position)
+    })
+}
+
+/// Focus the most recently active of an app's Wayland toplevels, by the number the listing gave it
+#[cfg(feature = "wayland")]
+fn wayland_activate_window(wayland: &Wayland, windows: &[Window]) -> Result<Window> {
+    let window =
+        most_recent_window(windows, &[]).ok_or_else(|| anyhow::anyhow!("No window to activate"))?;
+    let WindowId::Wayland(id) = window.id else {
+        return Err(anyhow::anyhow!("{:?} is not a Wayland toplevel", window.id));
+    };
+    wayland.activate(id)?;
+    Ok(window.clone())
+}
+
+/// Raise and focus the most recently active of an app's windows, see [`most_recent_window`],
+/// with the window system they were listed from
+pub fn activate_window(windows: &[Window]) -> Result<Window> {
+    match windows.first().map(|window| window.id) {
+        None => Err(anyhow::anyhow!("No window to activate")),
+        Some(WindowId::X11(_)) => x11_activate_window(windows),
+        #[cfg(feature = "wayland")]
+        Some(WindowId::Wayland(_)) => {
+            with_wayland(|wayland| wayland_activate_window(wayland, windows))
+        }
+        #[cfg(not(feature = "wayland"))]
+        Some(WindowId::Wayland(_)) => {
+            Err(NotSupported("Wayland windows need the `wayland` feature".to_string()).into())
+        }
+    }
+}
+
+/// Activate the most recently active of the windows, the top-most in `_NET_CLIENT_LIST_STACKING` unless one is focused
+#[cfg(feature = "x11")]
+fn x11_activate_window(windows: &[Window]) -> Result<Window> {
+    let x11 = X11::connect()?;
+    let window = most_recent_window(windows, &x11.client_list_stacking()?)
+        .ok_or_else(|| anyhow::anyhow!("No window to activate"))?;
+    let WindowId::X11(id) = window.id else {
+        return Err(anyhow::anyhow!("{:?} is not an X11 window", window.id));
+    };
+    x11.activate_window(id)?;
+    Ok(window.clone())
+}
+
+#[cfg(not(feature = "x11"))]
+fn x11_frontmost_application(_apps: &[App]) -> Result<App> {
+    Err(NotSupported("X11 windows need the `x11` feature".to_string()).into())
+}
+
+#[cfg(not(feature = "x11"))]
+fn x11_windows(_apps: &[App]) -> Result<Vec<Window>> {
+    Err(NotSupported("X11 windows need the `x11` feature".to_string()).into())
+}
+
+#[cfg(not(feature = "x11"))]
+fn x11_activate_window(_windows: &[Window]) -> Result<Window> {
+    Err(NotSupported("X11 windows need the `x11` feature".to_string()).into())
+}
+
+/// The command line that launches an app, from its .desktop file or else its executable,
+/// and the directory to run it in
+fn launch_command(app: &App) -> Result<(Vec<String>, Option<PathBuf>)> {
+    if app
+        .app_desktop_path
+        .extension()
+        .is_some_and(|extension| extension == "desktop")
+    {
+        let entry = DesktopEntry::load(&app.app_desktop_path)?;
+        return Ok((entry.command_line(&[])?, entry.working_dir));
+    }
+    let exe = app
+        .app_path_exe
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("{} has no executable", app.name))?;
+    Ok((vec![exe.to_string_lossy().into_owned()], None))
+}
 
-    Err(anyhow::Error::msg("No matching app found".to_string()))
+/// Launch an app without opening anything, returns the command line it ran
+pub fn launch_app(app: &App) -> Result<Vec<String>> {
+    let (argv, working_dir) = launch_command(app)?;
+    launch::spawn(&argv, working_dir.as_deref())?;
+    Ok(argv)
 }
 
 impl AppTrait for App {
     fn load_icon(&self) -> Result<crate::utils::image::RustImageData> {
+        #[cfg(feature = "appimage")]
+        if let (None, AppSource::AppImage(info)) = (&self.icon_path, &self.source) {
+            let path = self
+                .app_path_exe
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("AppImage path is None"))?;
+            let bytes = appimage::load_icon(path, info)?;
+            return RustImageData::from_bytes(&bytes)
+                .map_err(|e| anyhow::anyhow!("Failed to get icon: {}", e));
+        }
         match &self.icon_path {
             Some(icon_path) => {
                 let icon_path_str = icon_path
@@ -337,7 +1057,7 @@ impl AppTrait for App {
     }
 
     fn from_path(path: &Path) -> Result<Self> {
-        let (app, _) = parse_desktop_file(path);
+        let (app, _) = parse_desktop_file(path)?;
         Ok(app)
     }
 }
@@ -356,10 +1076,39 @@ pub fn load_icon(path: &Path) -> Result<RustImageData> {
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
+    #[allow(unused_imports)]
     use std::process::Command;
     use std::{str, vec};
 
     use super::*;
+    use std::fs;
+
+    fn write_desktop_file(dir: &Path, file_name: &str, name: &str) -> PathBuf {
+        fs::create_dir_all(dir).unwrap();
+        let path = dir.join(file_name);
+        fs::write(
+            &path,
+            format!("[Desktop Entry]\nType=Application\nName={name}\nExec={name} %U\n"),
+        )
+        .unwrap();
+        path
+    }
+
+    /// A resolver that knows no data directories, Flatpak installations, snaps or Nix profiles
+    fn no_sources() -> SourceResolver {
+        SourceResolver {
+            dirs: BaseDirectories::default(),
+            flatpak_installations: vec![],
+            snap_layout: SnapLayout {
+                desktop_dir: PathBuf::new(),
+                mount_dirs: vec![],
+            },
+            nix_layout: NixLayout {
+                store_dir: PathBuf::from(nix::NIX_STORE),
+                profiles: vec![],
+            },
+        }
+    }
 
     #[test]
     fn test_clean_exec_path() {
@@ -369,6 +1118,740 @@ mod tests {
     }
 
     #[test]
+    fn test_desktop_file_id() {
+        let root = Path::new("/usr/share/applications");
+        assert_eq!(
+            desktop_file_id(root, &root.join("firefox.desktop")),
+            "firefox.desktop"
+        );
+        assert_eq!(
+            desktop_file_id(root, &root.join("kde4/kate.desktop")),
+            "kde4-kate.desktop"
+        );
+    }
+
+    #[test]
+    fn test_url_schemes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("thunderbird.desktop");
+        fs::write(
+            &path,
+            "[Desktop Entry]\nName=Thunderbird\nExec=thunderbird %u\nMimeType=message/rfc822;x-scheme-handler/mailto;X-Scheme-Handler/WebCal;x-scheme-handler/mailto;x-scheme-handler/;\n",
+        )
+        .unwrap();
+        let (app, _) = parse_desktop_file(&path).unwrap();
+        assert_eq!(app.url_schemes, vec!["mailto", "webcal"]);
+
+        // only the key of the [Desktop Entry] group counts, not similar keys or other groups
+        fs::write(
+            &path,
+            "[Desktop Entry]\nName=Thunderbird\nMimeTypeHint=x-scheme-handler/news\nMimeType\n[Desktop Action compose]\nMimeType=x-scheme-handler/mailto\n",
+        )
+        .unwrap();
+        let (app, _) = parse_desktop_file(&path).unwrap();
+        assert!(app.url_schemes.is_empty());
+    }
+
+    #[test]
+    fn test_localized_name() {
+        let env = HashMap::from([
+            ("LC_MESSAGES", "sr_RS.UTF-8@latin"),
+            ("LANG", "en_US.UTF-8"),
+        ]);
+        let locale = messages_locale(|key| env.get(key).map(|value| value.to_string())).unwrap();
+        let locales = locale_variants(&locale);
+        assert_eq!(locales, vec!["sr_RS@latin", "sr_RS", "sr@latin", "sr"]);
+        assert_eq!(locale_variants("de_DE.UTF-8"), vec!["de_DE", "de"]);
+        assert!(locale_variants("C.UTF-8").is_empty());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("org.gnome.Nautilus.desktop");
+        fs::write(
+            &path,
+            "[Desktop Entry]\nName=Files\nName[sr]=Датотеке\nName[sr@latin]=Datoteke\nName[de]=Dateien\nExec=nautilus\n",
+        )
+        .unwrap();
+        let entry = DesktopEntry::load(&path).unwrap();
+        assert_eq!(entry.localized_name(&locales), Some("Datoteke"));
+        assert_eq!(
+            entry.localized_name(&locale_variants("de_AT")),
+            Some("Dateien")
+        );
+        assert_eq!(
+            entry.localized_name(&locale_variants("fr_FR")),
+            Some("Files")
+        );
+        // the keys after a localized one are still read
+        let (app, _) = parse_desktop_file(&path).unwrap();
+        assert_eq!(app.app_path_exe, Some(PathBuf::from("nautilus")));
+    }
+
+    #[test]
+    fn test_parse_malformed_desktop_file() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(App::from_path(&dir.path().join("missing.desktop")).is_err());
+
+        let path = dir.path().join("broken.desktop");
+        fs::write(&path, "[Desktop Entry\nName=Broken\n").unwrap();
+        assert!(App::from_path(&path).is_err());
+
+        // keys without a value
+        fs::write(&path, "[Desktop Entry]\nName\nIcon\nExec\n").unwrap();
+        let app = App::from_path(&path).unwrap();
+        assert_eq!(app.name, "");
+        assert_eq!(app.icon_path, None);
+    }
+
+    #[cfg(feature = "wayland")]
+    #[test]
+    fn test_wayland_windows() {
+        let wayland = wayland::tests::serve(wayland::tests::compositor(), true, false);
+        let tmp = tempfile::tempdir().unwrap();
+        let firefox = App {
+            name: "Firefox".to_string(),
+            app_desktop_path: write_desktop_file(tmp.path(), "firefox.desktop", "Firefox"),
+            ..Default::default()
+        };
+        let windows = wayland_windows(&wayland, std::slice::from_ref(&firefox)).unwrap();
+        assert_eq!(
+            windows,
+            vec![
+                Window {
+                    id: WindowId::Wayland(1),
+                    title: Some("Mozilla Firefox".to_string()),
+                    app: Some(firefox),
+                    pid: None,
+                    class: Some("firefox".to_string()),
+                    workspace: None,
+                    geometry: None,
+                    is_focused: false,
+                },
+                Window {
+                    id: WindowId::Wayland(2),
+                    title: Some("Home".to_string()),
+                    app: None,
+                    pid: None,
+                    class: Some("org.gnome.Nautilus".to_string()),
+                    workspace: None,
+                    geometry: None,
+                    is_focused: true,
+                },
+            ]
+        );
+    }
+
+    #[cfg(feature = "wayland")]
+    #[test]
+    fn test_wayland_activate_window() {
+        let compositor = wayland::tests::compositor();
+        let activations = compositor.activations.clone();
+        let wayland = wayland::tests::serve(compositor, true, false);
+        let windows = wayland_windows(&wayland, &[]).unwrap();
+        // the focused window wins
+        let window = wayland_activate_window(&wayland, &windows).unwrap();
+        assert_eq!(window.title.as_deref(), Some("Home"));
+        let window = wayland_activate_window(&wayland, &windows[..1]).unwrap();
+        assert_eq!(window.title.as_deref(), Some("Mozilla Firefox"));
+        assert_eq!(*activations.lock().unwrap(), vec![1, 0]);
+        assert!(wayland_activate_window(&wayland, &[]).is_err());
+    }
+
+    #[test]
+    fn test_most_recent_window() {
+        let window = |id: u32, is_focused: bool| Window {
+            id: WindowId::X11(id),
+            title: None,
+            app: None,
+            pid: None,
+            class: None,
+            workspace: None,
+            geometry: None,
+            is_focused,
+        };
+        let windows = [window(10, false), window(20, false), window(30, false)];
+        let id = |found: Option<&Window>| found.map(|window| window.id);
+        assert_eq!(
+            id(most_recent_window(&windows, &[5, 20, 30, 10, 6])),
+            Some(WindowId::X11(10))
+        );
+        assert_eq!(
+            id(most_recent_window(&windows, &[])),
+            Some(WindowId::X11(30))
+        );
+        let windows = [window(10, false), window(20, true)];
+        assert_eq!(
+            id(most_recent_window(&windows, &[20, 10])),
+            Some(WindowId::X11(20))
+        );
+        assert_eq!(id(most_recent_window(&[], &[20, 10])), None);
+    }
+
+    #[test]
+    fn test_launch_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_desktop_file(dir.path(), "editor.desktop", "editor");
+        let (argv, working_dir) = launch_command(&App::from_path(&path).unwrap()).unwrap();
+        assert_eq!(argv, vec!["editor"]);
+        assert_eq!(working_dir, None);
+
+        let appimage = App {
+            name: "Tool".to_string(),
+            app_path_exe: Some(PathBuf::from("/home/me/Applications/Tool.AppImage")),
+            app_desktop_path: PathBuf::from("/home/me/Applications/Tool.AppImage"),
+            ..Default::default()
+        };
+        let (argv, _) = launch_command(&appimage).unwrap();
+        assert_eq!(argv, vec!["/home/me/Applications/Tool.AppImage"]);
+        assert!(launch_command(&App::default()).is_err());
+    }
+
+    #[test]
+    fn test_icon_dimensions() {
+        let path = Path::new("/usr/share/icons/hicolor/48x48/apps/firefox.png");
+        assert_eq!(icon_dimensions(path), Some(48));
+        let path = Path::new("/home/me/.local/share/icons/hicolor/256x256@2/apps/code.png");
+        assert_eq!(icon_dimensions(path), Some(256));
+        let path = Path::new("/usr/share/icons/hicolor/scalable/apps/code.png");
+        assert_eq!(icon_dimensions(path), None);
+    }
+
+    #[test]
+    fn test_search_path_precedence() {
+        let tmp = tempfile::tempdir().unwrap();
+        let user = tmp.path().join("user/applications");
+        let system = tmp.path().join("system/applications");
+        write_desktop_file(&user, "editor.desktop", "User Editor");
+        write_desktop_file(&system, "editor.desktop", "System Editor");
+        write_desktop_file(&system, "browser.desktop", "Browser");
+        write_desktop_file(&system.join("kde4"), "kate.desktop", "Kate");
+
+        let search_paths = vec![SearchPath::new(user, 1), SearchPath::new(system.clone(), 2)];
+        let apps = get_apps_in_search_paths(&search_paths).unwrap();
+        let names: Vec<&str> = apps.iter().map(|app| app.name.as_str()).collect();
+        assert_eq!(names, vec!["User Editor", "Browser", "Kate"]);
+
+        // scanning twice gives the same order
+        let again = get_apps_in_search_paths(&search_paths).unwrap();
+        assert_eq!(apps, again);
+
+        // duplicated search paths are only scanned once
+        let apps = get_apps_in_search_paths(&[
+            SearchPath::new(system.clone(), 1),
+            SearchPath::new(system, 1),
+        ])
+        .unwrap();
+        assert_eq!(apps.len(), 2);
+    }
+
+    #[test]
+    fn test_search_path_options() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("applications");
+        write_desktop_file(&dir, "code.desktop", "Code");
+        write_desktop_file(&dir, "code-url-handler.desktop", "Code URL Handler");
+        write_desktop_file(&dir.join("wine"), "notepad.desktop", "Notepad");
+
+        let search_path = SearchPath::new(dir.clone(), 2).with_exclude("*-url-handler.desktop");
+        let apps = get_apps_in_search_paths(std::slice::from_ref(&search_path)).unwrap();
+        let names: Vec<&str> = apps.iter().map(|app| app.name.as_str()).collect();
+        assert_eq!(names, vec!["Code", "Notepad"]);
+
+        let apps = get_apps_in_search_paths(&[search_path.with_exclude("wine/*")]).unwrap();
+        let names: Vec<&str> = apps.iter().map(|app| app.name.as_str()).collect();
+        assert_eq!(names, vec!["Code"]);
+
+        let search_path = SearchPath::new(dir, 2).with_include("wine/*");
+        let apps = get_apps_in_search_paths(&[search_path]).unwrap();
+        let names: Vec<&str> = apps.iter().map(|app| app.name.as_str()).collect();
+        assert_eq!(names, vec!["Notepad"]);
+    }
+
+    #[test]
+    fn test_executables_search_path() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = tempfile::tempdir().unwrap();
+        let first = tmp.path().join("first");
+        let second = tmp.path().join("second");
+        for dir in [&first, &second] {
+            fs::create_dir_all(dir).unwrap();
+            let tool = dir.join("tool");
+            fs::write(&tool, "#!/bin/sh\n").unwrap();
+            fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        fs::write(first.join("README"), "not executable").unwrap();
+
+        let apps = get_apps_in_search_paths(&[
+            SearchPath::new(first.clone(), 1).with_kind(SearchPathKind::Executables),
+            SearchPath::new(second, 1).with_kind(SearchPathKind::Executables),
+        ])
+        .unwrap();
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].name, "tool");
+        assert_eq!(apps[0].app_path_exe, Some(first.join("tool")));
+    }
+
+    #[test]
+    fn test_sorted_fixture_output() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("applications");
+        write_desktop_file(&dir, "a.desktop", "zathura");
+        write_desktop_file(&dir, "b.desktop", "Évince");
+        write_desktop_file(&dir, "c.desktop", "Firefox");
+        write_desktop_file(&dir, "d.desktop", "btop");
+
+        let mut apps = get_apps_in_search_paths(&[SearchPath::new(dir, 1)]).unwrap();
+        crate::AppOrdering::Name.sort(&mut apps);
+        let names: Vec<&str> = apps.iter().map(|app| app.name.as_str()).collect();
+        assert_eq!(names, vec!["btop", "Évince", "Firefox", "zathura"]);
+    }
+
+    #[test]
+    fn test_xdg_default_search_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        let home = tmp.path().join("home");
+        let data_home = home.join(".local/share");
+        let vendor = tmp.path().join("opt/share");
+        let system = tmp.path().join("usr/share");
+        write_desktop_file(
+            &data_home.join("applications"),
+            "editor.desktop",
+            "My Editor",
+        );
+        write_desktop_file(
+            &vendor.join("applications"),
+            "editor.desktop",
+            "Vendor Editor",
+        );
+        write_desktop_file(&vendor.join("applications/kde4"), "kate.desktop", "Kate");
+        write_desktop_file(&system.join("applications"), "browser.desktop", "Browser");
+        // a data dir itself is not an applications dir
+        write_desktop_file(&system, "stray.desktop", "Stray");
+        write_desktop_file(&system.join("xsessions"), "gnome.desktop", "GNOME");
+
+        let xdg_data_dirs = format!("{}:{}", vendor.display(), system.display());
+        let lookup = |key: &str| match key {
+            "HOME" => Some(home.to_string_lossy().to_string()),
+            "XDG_DATA_DIRS" => Some(xdg_data_dirs.clone()),
+            _ => None,
+        };
+        let dirs = BaseDirectories::from_env(lookup);
+        let search_paths = default_search_paths(lookup);
+        let mut expected = vec![
+            data_home.join("applications"),
+            vendor.join("applications"),
+            system.join("applications"),
+            data_home.join("flatpak/exports/share/applications"),
+            PathBuf::from("/var/lib/flatpak/exports/share/applications"),
+            PathBuf::from(SNAPD_DESKTOP_APPLICATIONS),
+        ];
+        let nix_dirs = NixLayout::from_env(&dirs, lookup).applications_dirs();
+        assert_eq!(nix_dirs[0], home.join(".nix-profile/share/applications"));
+        expected.extend(nix_dirs);
+        expected.extend([home.join("Applications"), home.join(".local/bin")]);
+        assert_eq!(
+            search_paths
+                .iter()
+                .map(|search_path| search_path.path.clone())
+                .collect::<Vec<_>>(),
+            expected
+        );
+        assert!(search_paths[6].follow_symlinks);
+        assert!(!search_paths[0].follow_symlinks);
+        assert_eq!(search_paths.last().unwrap().kind, SearchPathKind::AppImages);
+
+        let apps = get_apps_in_search_paths(&search_paths[..3]).unwrap();
+        let names: Vec<&str> = apps.iter().map(|app| app.name.as_str()).collect();
+        assert_eq!(names, vec!["My Editor", "Kate", "Browser"]);
+    }
+
+    #[test]
+    fn test_find_app_icons() {
+        let tmp = tempfile::tempdir().unwrap();
+        let home_icons = tmp.path().join("home/.icons");
+        let data_icons = tmp.path().join("share/icons");
+        let pixmaps = tmp.path().join("pixmaps");
+        for path in [
+            home_icons.join("mytheme/32x32/apps/code.png"),
+            data_icons.join("hicolor/48x48/apps/code.png"),
+            data_icons.join("hicolor/256x256/apps/code.png"),
+            pixmaps.join("code.png"),
+            pixmaps.join("vim.png"),
+        ] {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        // a theme linking outside the Nix store isn't followed into
+        let elsewhere = tmp.path().join("elsewhere/64x64/apps");
+        fs::create_dir_all(&elsewhere).unwrap();
+        fs::write(elsewhere.join("code.png"), "").unwrap();
+        std::os::unix::fs::symlink(
+            tmp.path().join("elsewhere/64x64"),
+            data_icons.join("hicolor/64x64"),
+        )
+        .unwrap();
+        let nix_layout = NixLayout {
+            store_dir: tmp.path().join("nix/store"),
+            profiles: vec![],
+        };
+        let icons = find_app_icons(
+            &[home_icons, data_icons.clone(), pixmaps.clone()],
+            &nix_layout,
+        )
+        .unwrap();
+        let code: Vec<Option<u16>> = icons["code"].iter().map(|icon| icon.dimensions).collect();
+        assert_eq!(code, vec![Some(256), Some(48), Some(32), None]);
+        assert_eq!(
+            icons["code"][0].path,
+            data_icons.join("hicolor/256x256/apps/code.png")
+        );
+        assert_eq!(icons["vim"][0].path, pixmaps.join("vim.png"));
+    }
+
+    #[test]
+    fn test_open_command() {
+        let tmp = tempfile::tempdir().unwrap();
+        let share = tmp.path().join("usr/share");
+        mime::tests::install_fixture_database(&share);
+        let applications = share.join("applications");
+        fs::create_dir_all(&applications).unwrap();
+        fs::write(
+            applications.join("evince.desktop"),
+            "[Desktop Entry]\nName=Document Viewer\nExec=evince %U\n",
+        )
+        .unwrap();
+        fs::write(
+            applications.join("gedit.desktop"),
+            "[Desktop Entry]\nName=Text Editor\nExec=gedit --new-window %F\nPath=/tmp\n",
+        )
+        .unwrap();
+        fs::write(
+            applications.join("firefox.desktop"),
+            "[Desktop Entry]\nName=Firefox\nExec=firefox %u\n",
+        )
+        .unwrap();
+        fs::write(
+            applications.join("mimeapps.list"),
+            "[Default Applications]\napplication/pdf=evince.desktop\nx-scheme-handler/https=firefox.desktop\n[Added Associations]\ntext/plain=gedit.desktop\n",
+        )
+        .unwrap();
+        let document = tmp.path().join("document");
+        fs::write(&document, "%PDF-1.7\n").unwrap();
+        let script = tmp.path().join("install.sh");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+
+        let share_dir = share.to_str().unwrap().to_string();
+        let dirs = BaseDirectories::from_env(|key| match key {
+            "XDG_DATA_DIRS" => Some(share_dir.clone()),
+            _ => None,
+        });
+        let associations = MimeAssociations::load(&dirs, &[]);
+        let open = |target: &str| open_command(target, &dirs, &associations);
+
+        // the type is sniffed from the content
+        let (entry, argv) = open(document.to_str().unwrap()).unwrap();
+        assert_eq!(entry.path, applications.join("evince.desktop"));
+        assert_eq!(argv, vec!["evince", document.to_str().unwrap()]);
+        // shell scripts are opened with the text/plain app they inherit from
+        let (entry, argv) = open(&format!("file://{}", script.display())).unwrap();
+        assert_eq!(entry.working_dir, Some(PathBuf::from("/tmp")));
+        assert_eq!(
+            argv,
+            vec!["gedit", "--new-window", script.to_str().unwrap()]
+        );
+        let (_, argv) = open("https://example.com").unwrap();
+        assert_eq!(argv, vec!["firefox", "https://example.com"]);
+        assert!(open("mailto:me@example.com").is_err());
+    }
+
+    #[test]
+    fn test_desktop_entry_sources() {
+        let tmp = tempfile::tempdir().unwrap();
+        let home = tmp.path().join("home");
+        let system = tmp.path().join("usr/share");
+        let dirs = BaseDirectories::from_env(|key| match key {
+            "HOME" => Some(home.to_string_lossy().to_string()),
+            "XDG_DATA_DIRS" => Some(system.to_string_lossy().to_string()),
+            _ => None,
+        });
+        let sources = SourceResolver {
+            dirs,
+            ..no_sources()
+        };
+        let icons_db = HashMap::new();
+        let source = |path: PathBuf| load_desktop_app(&path, &icons_db, &sources).unwrap().source;
+
+        let user = write_desktop_file(
+            &home.join(".local/share/applications"),
+            "editor.desktop",
+            "Editor",
+        );
+        assert_eq!(source(user), AppSource::UserLocal);
+        let package = write_desktop_file(&system.join("applications/kde4"), "kate.desktop", "Kate");
+        assert_eq!(source(package), AppSource::Package(None));
+        let elsewhere = write_desktop_file(&tmp.path().join("opt"), "tool.desktop", "Tool");
+        assert_eq!(source(elsewhere), AppSource::Unknown);
+    }
+
+    #[test]
+    fn test_flatpak_apps() {
+        let tmp = tempfile::tempdir().unwrap();
+        let installation = tmp.path().join("flatpak");
+        flatpak::tests::install_fixture_app(&installation, "org.example.Calculator", "Calculator");
+        write_desktop_file(&tmp.path().join("applications"), "native.desktop", "Native");
+
+        let flatpak_installations = vec![FlatpakInstallation::new(
+            installation.clone(),
+            crate::common::FlatpakInstallationKind::System,
+        )];
+        let sources = SourceResolver {
+            flatpak_installations,
+            ..no_sources()
+        };
+        let icons_db = HashMap::new();
+        let exported =
+            installation.join("exports/share/applications/org.example.Calculator.desktop");
+        let app = load_desktop_app(&exported, &icons_db, &sources).unwrap();
+        assert_eq!(app.name, "Calculator");
+        match app.source {
+            AppSource::Flatpak(info) => {
+                assert_eq!(info.app_id, "org.example.Calculator");
+                assert_eq!(info.branch, "stable");
+                assert_eq!(info.arch, "x86_64");
+                assert_eq!(
+                    info.runtime.as_deref(),
+                    Some("org.gnome.Platform/x86_64/46")
+                );
+            }
+            source => panic!("expected a Flatpak app, got {:?}", source),
+        }
+
+        let native = tmp.path().join("applications/native.desktop");
+        let app = load_desktop_app(&native, &icons_db, &sources).unwrap();
+        assert_eq!(app.source, AppSource::Unknown);
+    }
+
+    #[test]
+    fn test_running_apps() {
+        use procfs::tests::{install_fixture_process, FakeProcess};
+        let app = |desktop_file: &str, exe: &str| App {
+            name: desktop_file.to_string(),
+            app_path_exe: Some(PathBuf::from(exe)),
+            app_desktop_path: PathBuf::from(desktop_file),
+            ..Default::default()
+        };
+        let firefox = app("/usr/share/applications/firefox.desktop", "firefox");
+        let terminal = app(
+            "/usr/share/applications/org.gnome.Terminal.desktop",
+            "gnome-terminal",
+        );
+        let editor = app("/usr/share/applications/org.gnome.gedit.desktop", "gedit");
+        let calculator = App {
+            source: AppSource::Flatpak(crate::common::FlatpakInfo {
+                app_id: "org.gnome.Calculator".to_string(),
+                ..Default::default()
+            }),
+            ..app(
+                "/var/lib/flatpak/exports/share/applications/org.gnome.Calculator.desktop",
+                "/usr/bin/flatpak",
+            )
+        };
+        let apps = vec![
+            firefox.clone(),
+            terminal.clone(),
+            editor.clone(),
+            calculator.clone(),
+        ];
+
+        let tmp = tempfile::tempdir().unwrap();
+        let layout = ProcLayout {
+            proc_root: tmp.path().join("proc"),
+            passwd: tmp.path().join("passwd"),
+            ticks_per_second: 100,
+        };
+        let proc_root = layout.proc_root.as_path();
+        fs::create_dir_all(proc_root).unwrap();
+        fs::write(proc_root.join("stat"), "btime 1760000000\n").unwrap();
+        fs::write(&layout.passwd, "me:x:1000:1000::/home/me:/bin/bash\n").unwrap();
+        let firefox_environ = [
+            "GIO_LAUNCHED_DESKTOP_FILE=/usr/share/applications/firefox.desktop",
+            "GIO_LAUNCHED_DESKTOP_FILE_PID=100",
+        ];
+        // the main process and one of its content processes
+        for (pid, ppid, start_ticks, rss_kib) in
+            [(100, 1, 5_000, 400_000), (101, 100, 5_100, 100_000)]
+        {
+            install_fixture_process(
+                proc_root,
+                pid,
+                FakeProcess {
+                    exe: Some("/usr/lib/firefox/firefox"),
+                    cmdline: &["/usr/lib/firefox/firefox"],
+                    environ: &firefox_environ,
+                    ppid,
+                    start_ticks,
+                    uid: 1000,
+                    rss_kib,
+                    ..Default::default()
+                },
+            );
+        }
+        install_fixture_process(
+            proc_root,
+            200,
+            FakeProcess {
+                exe: Some("/app/bin/gnome-calculator"),
+                cmdline: &["gnome-calculator"],
+                flatpak_app_id: Some("org.gnome.Calculator"),
+                ..Default::default()
+            },
+        );
+        install_fixture_process(
+            proc_root,
+            300,
+            FakeProcess {
+                exe: Some("/usr/libexec/gnome-terminal-server"),
+                cmdline: &["/usr/libexec/gnome-terminal-server"],
+                cgroup: "0::/user.slice/user-1000.slice/user@1000.service/app.slice/app-gnome-org.gnome.Terminal-300.scope\n",
+                ..Default::default()
+            },
+        );
+        install_fixture_process(
+            proc_root,
+            400,
+            FakeProcess {
+                exe: Some("/usr/bin/gedit"),
+                cmdline: &["gedit", "notes.txt"],
+                ..Default::default()
+            },
+        );
+        install_fixture_process(
+            proc_root,
+            500,
+            FakeProcess {
+                exe: Some("/usr/sbin/sshd"),
+                cmdline: &["sshd: /usr/sbin/sshd -D"],
+                ..Default::default()
+            },
+        );
+
+        let running = running_apps_in(&layout, &apps);
+        assert_eq!(
+            running
+                .iter()
+                .map(|running| (running.app.clone(), running.pids.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (firefox.clone(), vec![100, 101]),
+                (calculator, vec![200]),
+                (terminal, vec![300]),
+                (editor, vec![400]),
+            ]
+        );
+        assert_eq!(
+            running[0],
+            RunningApp {
+                app: firefox,
+                pids: vec![100, 101],
+                main_pid: Some(100),
+                start_ticks: HashMap::from([(100, 5_000), (101, 5_100)]),
+                started_at: Some(1_760_000_050),
+                user: Some("me".to_string()),
+                memory_bytes: Some(500_000 * 1024),
+                is_frontmost: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_snap_apps() {
+        let tmp = tempfile::tempdir().unwrap();
+        let snap_layout = snap::tests::fixture_layout(tmp.path());
+        snap::tests::install_fixture_snap(&snap_layout, "firefox", "4173", "Firefox");
+
+        let icons_db = HashMap::new();
+        let desktop_file = snap_layout.desktop_dir.join("firefox_firefox.desktop");
+        let sources = SourceResolver {
+            snap_layout,
+            ..no_sources()
+        };
+        let app = load_desktop_app(&desktop_file, &icons_db, &sources).unwrap();
+        assert_eq!(app.name, "Firefox");
+        assert_eq!(app.app_path_exe, Some(tmp.path().join("snap/bin/firefox")));
+        match app.source {
+            AppSource::Snap(info) => {
+                assert_eq!(info.name, "firefox");
+                assert_eq!(info.revision, "4173");
+                assert_eq!(info.version.as_deref(), Some("128.0.3-1"));
+                assert_eq!(info.confinement.as_deref(), Some("strict"));
+            }
+            source => panic!("expected a Snap app, got {:?}", source),
+        }
+    }
+
+    #[test]
+    fn test_nix_apps() {
+        let tmp = tempfile::tempdir().unwrap();
+        let nix_layout = nix::tests::install_fixture_package(tmp.path(), "firefox", "Firefox");
+        let profile = nix_layout.profiles[0].clone();
+
+        let icons_db = find_app_icons(&nix_layout.icons_dirs(), &nix_layout).unwrap();
+        assert_eq!(
+            icons_db["firefox"][0].path,
+            profile.join("share/icons/hicolor/48x48/apps/firefox.png")
+        );
+
+        let desktop_file = profile.join("share/applications/firefox.desktop");
+        let sources = SourceResolver {
+            nix_layout,
+            ..no_sources()
+        };
+        let app = load_desktop_app(&desktop_file, &icons_db, &sources).unwrap();
+        assert_eq!(app.name, "Firefox");
+        assert_eq!(app.app_desktop_path, desktop_file);
+        assert_eq!(
+            app.icon_path,
+            Some(profile.join("share/icons/hicolor/48x48/apps/firefox.png"))
+        );
+        match app.source {
+            AppSource::Nix(info) => {
+                assert_eq!(info.name, "firefox-1.0");
+                assert_eq!(info.profile, Some(profile));
+            }
+            source => panic!("expected a Nix app, got {:?}", source),
+        }
+    }
+
+    #[cfg(feature = "appimage")]
+    #[test]
+    fn test_appimage_apps() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("Applications");
+        fs::create_dir_all(&dir).unwrap();
+        let appimage = dir.join("Fixture-x86_64.AppImage");
+        appimage::tests::write_fixture_appimage(
+            &appimage,
+            "Fixture",
+            &appimage::tests::fixture_png(),
+        );
+        fs::set_permissions(&appimage, fs::Permissions::from_mode(0o755)).unwrap();
+        let script = dir.join("not-an-appimage");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let apps = get_apps_in_search_paths(&[
+            SearchPath::new(dir, 1).with_kind(SearchPathKind::AppImages)
+        ])
+        .unwrap();
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].name, "Fixture");
+        assert_eq!(apps[0].app_path_exe, Some(appimage));
+        assert!(matches!(apps[0].source, AppSource::AppImage(_)));
+        assert_eq!(apps[0].load_icon().unwrap().get_size(), (4, 4));
+    }
+
+    #[test]
+    #[allow(clippy::useless_vec, clippy::len_zero)]
     fn test_get_apps() {
         let apps = get_all_apps(&vec![]).unwrap();
         println!("Number of Apps: {}", apps.len());
@@ -382,6 +1865,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::len_zero)]
     fn test_find_all_app_icons() {
         let start = std::time::Instant::now();
         let icons_icons = find_all_app_icons().unwrap();
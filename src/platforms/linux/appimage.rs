@@ -0,0 +1,419 @@
+//! AppImage discovery
+//!
+//! An AppImage is an ELF runtime with a filesystem image appended to it. The ELF header is marked with
+//! `AI` followed by the AppImage type at offset 8:
+//!
+//! - type 1 images are ISO 9660 images, the runtime is embedded in the ISO's system area
+//! - type 2 images append a squashfs image right after the ELF runtime
+//!
+//! The image contains the app's .desktop file and its icon at the root, the icon is usually the `.DirIcon` symlink.
+//! Metadata is only read from type 2 images, type 1 images are listed under their file name.
+use crate::common::{App, AppImageInfo, AppSource};
+use anyhow::{anyhow, Result};
+use backhand::{FilesystemReader, InnerNode, SquashfsFileReader};
+use ini::inistr;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+
+use super::AppIcon;
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+const SQUASHFS_MAGIC: &[u8; 4] = b"hsqs";
+
+/// Read the AppImage type from the magic bytes of a file, None if it's not an AppImage
+pub fn appimage_type(path: &Path) -> Option<u8> {
+    let mut header = [0u8; 11];
+    File::open(path).ok()?.read_exact(&mut header).ok()?;
+    if &header[..4] != ELF_MAGIC || &header[8..10] != b"AI" {
+        return None;
+    }
+    matches!(header[10], 1 | 2).then_some(header[10])
+}
+
+/// Offset of the squashfs image in a type 2 AppImage: the end of the ELF runtime, i.e. the end of its section header table
+pub fn squashfs_offset(file: &mut File) -> Result<u64> {
+    let mut header = [0u8; 64];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut header)?;
+    if &header[..4] != ELF_MAGIC {
+        return Err(anyhow!("Not an ELF file"));
+    }
+    let little_endian = match header[5] {
+        1 => true,
+        2 => false,
+        data => return Err(anyhow!("Invalid ELF data encoding {}", data)),
+    };
+    let u16_at = |offset: usize| {
+        let bytes = [header[offset], header[offset + 1]];
+        if little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        }
+    };
+    let uint_at = |offset: usize, size: usize| {
+        let bytes = &header[offset..offset + size];
+        let fold = |acc: u64, byte: &u8| (acc << 8) | *byte as u64;
+        if little_endian {
+            bytes.iter().rev().fold(0, fold)
+        } else {
+            bytes.iter().fold(0, fold)
+        }
+    };
+    let (shoff, shentsize, shnum) = match header[4] {
+        1 => (uint_at(0x20, 4), u16_at(0x2E), u16_at(0x30)),
+        2 => (uint_at(0x28, 8), u16_at(0x3A), u16_at(0x3C)),
+        class => return Err(anyhow!("Invalid ELF class {}", class)),
+    };
+    // the header is untrusted, a crafted one mustn't overflow
+    let offset = (shentsize as u64)
+        .checked_mul(shnum as u64)
+        .and_then(|size| shoff.checked_add(size))
+        .ok_or_else(|| anyhow!("Invalid ELF section header table offset {}", shoff))?;
+
+    let mut magic = [0u8; 4];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut magic)?;
+    if &magic != SQUASHFS_MAGIC {
+        return Err(anyhow!("No squashfs image at offset {}", offset));
+    }
+    Ok(offset)
+}
+
+/// The squashfs image embedded in a type 2 AppImage
+pub struct AppImageFilesystem {
+    reader: FilesystemReader<'static>,
+}
+
+impl AppImageFilesystem {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut file = File::open(path)?;
+        let offset = squashfs_offset(&mut file)?;
+        let reader = FilesystemReader::from_reader_with_offset(BufReader::new(file), offset)?;
+        Ok(Self { reader })
+    }
+
+    /// Paths of the files and symlinks at the root of the image, e.g. `/firefox.desktop`
+    pub fn root_entries(&self) -> Vec<PathBuf> {
+        self.reader
+            .files()
+            .filter(|node| !matches!(node.inner, InnerNode::Dir(_)))
+            .filter(|node| node.fullpath.parent() == Some(Path::new("/")))
+            .map(|node| node.fullpath.clone())
+            .collect()
+    }
+
+    /// Follow symlinks until a regular file is found, like `.DirIcon -> usr/share/icons/hicolor/256x256/apps/firefox.png`
+    pub fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        let mut path = path.to_path_buf();
+        // guard against symlink loops
+        for _ in 0..8 {
+            let node = self.reader.files().find(|node| node.fullpath == path)?;
+            match &node.inner {
+                InnerNode::File(_) => return Some(path),
+                InnerNode::Symlink(symlink) => {
+                    let parent = path.parent().unwrap_or(Path::new("/"));
+                    path = normalize(&parent.join(&symlink.link));
+                }
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    fn file(&self, path: &Path) -> Option<&SquashfsFileReader> {
+        let path = self.resolve(path)?;
+        self.reader.files().find_map(|node| match &node.inner {
+            InnerNode::File(file) if node.fullpath == path => Some(file),
+            _ => None,
+        })
+    }
+
+    /// Read a file from the image, following symlinks
+    pub fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let file = self
+            .file(path)
+            .ok_or_else(|| anyhow!("{:?} not found in AppImage", path))?;
+        let mut bytes = vec![];
+        self.reader.file(file).reader().read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// Resolve `.` and `..` in a path inside the image, without touching the host file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(name) => normalized.push(name),
+            _ => {}
+        }
+    }
+    normalized
+}
+
+/// The fields we use from the embedded .desktop file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AppImageDesktopEntry {
+    pub name: Option<String>,
+    pub icon: Option<String>,
+    pub version: Option<String>,
+}
+
+pub fn parse_desktop_entry(content: &str) -> Option<AppImageDesktopEntry> {
+    let map = inistr!(safe content).ok()?;
+    let entry = map.get("desktop entry")?;
+    let get = |key: &str| entry.get(key).cloned().flatten();
+    Some(AppImageDesktopEntry {
+        name: get("name"),
+        icon: get("icon"),
+        version: get("x-appimage-version"),
+    })
+}
+
+/// Read the metadata of a type 2 AppImage. The icon is the `.DirIcon`, or the icon named by the .desktop file
+pub fn read_metadata(path: &Path) -> Result<(AppImageDesktopEntry, AppImageInfo)> {
+    let filesystem = AppImageFilesystem::open(path)?;
+    let root_entries = filesystem.root_entries();
+    let desktop_file = root_entries
+        .iter()
+        .find(|entry| entry.extension().is_some_and(|ext| ext == "desktop"))
+        .cloned();
+    let entry = match &desktop_file {
+        Some(desktop_file) => {
+            let content = filesystem.read(desktop_file)?;
+            parse_desktop_entry(&String::from_utf8_lossy(&content)).unwrap_or_default()
+        }
+        None => AppImageDesktopEntry::default(),
+    };
+    let icon_candidates =
+        std::iter::once(PathBuf::from("/.DirIcon")).chain(entry.icon.iter().flat_map(|icon| {
+            ["png", "svg", "xpm"].map(|ext| PathBuf::from(format!("/{icon}.{ext}")))
+        }));
+    let icon_file = icon_candidates
+        .filter_map(|candidate| filesystem.resolve(&candidate))
+        .next();
+    let info = AppImageInfo {
+        appimage_type: 2,
+        version: entry.version.clone(),
+        desktop_file,
+        icon_file,
+    };
+    Ok((entry, info))
+}
+
+/// Build an App for an AppImage file. Its executable is the AppImage itself, metadata is best effort:
+/// when it can't be read the app is named after the file.
+/// The icon path is set only if the .desktop file's icon is installed on the system, see [`load_icon`] for the embedded one.
+pub fn load_app(path: &Path, icons_db: &HashMap<String, Vec<AppIcon>>) -> Option<App> {
+    let appimage_type = appimage_type(path)?;
+    let (entry, info) = match appimage_type {
+        2 => read_metadata(path).unwrap_or_else(|e| {
+            log::warn!("Failed to read AppImage metadata from {:?}: {}", path, e);
+            (AppImageDesktopEntry::default(), AppImageInfo::default())
+        }),
+        _ => Default::default(),
+    };
+    let name = match entry.name {
+        Some(name) => name,
+        None => path.file_stem()?.to_str()?.to_string(),
+    };
+    let icon_path = entry
+        .icon
+        .and_then(|icon| icons_db.get(&icon))
+        .and_then(|icons| icons.first())
+        .map(|icon| icon.path.clone());
+    Some(App {
+        name,
+        icon_path,
+        app_path_exe: Some(path.to_path_buf()),
+        app_desktop_path: path.to_path_buf(),
+        source: AppSource::AppImage(AppImageInfo {
+            appimage_type,
+            ..info
+        }),
+        ..Default::default()
+    })
+}
+
+/// Read the icon embedded in an AppImage, as found by [`read_metadata`]
+pub fn load_icon(path: &Path, info: &AppImageInfo) -> Result<Vec<u8>> {
+    let icon_file = info
+        .icon_file
+        .as_ref()
+        .ok_or_else(|| anyhow!("AppImage has no embedded icon"))?;
+    AppImageFilesystem::open(path)?.read(icon_file)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use backhand::{FilesystemWriter, NodeHeader};
+    use std::io::Cursor;
+
+    /// A minimal 64-bit little endian ELF header with the AppImage magic, whose section header table ends at `size`
+    fn elf_runtime(appimage_type: u8, size: u64) -> Vec<u8> {
+        let mut runtime = vec![0u8; size as usize];
+        runtime[..4].copy_from_slice(ELF_MAGIC);
+        runtime[4] = 2; // 64-bit
+        runtime[5] = 1; // little endian
+        runtime[6] = 1;
+        runtime[8..11].copy_from_slice(&[b'A', b'I', appimage_type]);
+        let shentsize: u16 = 64;
+        let shnum: u16 = 2;
+        let shoff = size - shentsize as u64 * shnum as u64;
+        runtime[0x28..0x30].copy_from_slice(&shoff.to_le_bytes());
+        runtime[0x3A..0x3C].copy_from_slice(&shentsize.to_le_bytes());
+        runtime[0x3C..0x3E].copy_from_slice(&shnum.to_le_bytes());
+        runtime
+    }
+
+    /// Write a type 2 AppImage with a .desktop file and a `.DirIcon` symlink to a png icon
+    pub(crate) fn write_fixture_appimage(path: &Path, name: &str, icon_png: &[u8]) {
+        let mut image = FilesystemWriter::default();
+        let header = NodeHeader::default();
+        let desktop_entry = format!(
+            "[Desktop Entry]\nType=Application\nName={name}\nExec=AppRun %U\nIcon=fixture\nX-AppImage-Version=1.2.3\n"
+        );
+        image
+            .push_file(
+                Cursor::new(desktop_entry.into_bytes()),
+                "fixture.desktop",
+                header,
+            )
+            .unwrap();
+        image
+            .push_dir_all("usr/share/icons/hicolor/256x256/apps", header)
+            .unwrap();
+        image
+            .push_file(
+                Cursor::new(icon_png.to_vec()),
+                "usr/share/icons/hicolor/256x256/apps/fixture.png",
+                header,
+            )
+            .unwrap();
+        image
+            .push_symlink(
+                "usr/share/icons/hicolor/256x256/apps/fixture.png",
+                ".DirIcon",
+                header,
+            )
+            .unwrap();
+        image
+            .push_file(Cursor::new(b"#!/bin/sh\n".to_vec()), "AppRun", header)
+            .unwrap();
+
+        let runtime = elf_runtime(2, 4096);
+        let mut file = std::fs::File::create(path).unwrap();
+        std::io::Write::write_all(&mut file, &runtime).unwrap();
+        image
+            .write_with_offset(&mut file, runtime.len() as u64)
+            .unwrap();
+    }
+
+    pub(crate) fn fixture_png() -> Vec<u8> {
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgba8(4, 4)
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn test_appimage_type() {
+        let tmp = tempfile::tempdir().unwrap();
+        let type1 = tmp.path().join("legacy.AppImage");
+        std::fs::write(&type1, elf_runtime(1, 256)).unwrap();
+        assert_eq!(appimage_type(&type1), Some(1));
+
+        let type2 = tmp.path().join("app.AppImage");
+        write_fixture_appimage(&type2, "Fixture", &fixture_png());
+        assert_eq!(appimage_type(&type2), Some(2));
+
+        let plain_elf = tmp.path().join("tool");
+        std::fs::write(&plain_elf, &elf_runtime(2, 256)[..8]).unwrap();
+        assert_eq!(appimage_type(&plain_elf), None);
+        let script = tmp.path().join("script.AppImage");
+        std::fs::write(&script, "#!/bin/sh\necho AI\n").unwrap();
+        assert_eq!(appimage_type(&script), None);
+    }
+
+    #[test]
+    fn test_squashfs_offset_overflow() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("crafted.AppImage");
+        let mut runtime = elf_runtime(2, 256);
+        runtime[0x28..0x30].copy_from_slice(&u64::MAX.to_le_bytes());
+        std::fs::write(&path, runtime).unwrap();
+        let mut file = File::open(&path).unwrap();
+        assert!(squashfs_offset(&mut file).is_err());
+    }
+
+    #[test]
+    fn test_read_metadata() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("Fixture-1.2.3-x86_64.AppImage");
+        let png = fixture_png();
+        write_fixture_appimage(&path, "Fixture App", &png);
+
+        let mut file = File::open(&path).unwrap();
+        assert_eq!(squashfs_offset(&mut file).unwrap(), 4096);
+
+        let (entry, info) = read_metadata(&path).unwrap();
+        assert_eq!(entry.name.as_deref(), Some("Fixture App"));
+        assert_eq!(
+            info,
+            AppImageInfo {
+                appimage_type: 2,
+                version: Some("1.2.3".to_string()),
+                desktop_file: Some(PathBuf::from("/fixture.desktop")),
+                icon_file: Some(PathBuf::from(
+                    "/usr/share/icons/hicolor/256x256/apps/fixture.png"
+                )),
+            }
+        );
+        assert_eq!(load_icon(&path, &info).unwrap(), png);
+    }
+
+    #[test]
+    fn test_load_app() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("Fixture.AppImage");
+        write_fixture_appimage(&path, "Fixture App", &fixture_png());
+        let app = load_app(&path, &HashMap::new()).unwrap();
+        assert_eq!(app.name, "Fixture App");
+        assert_eq!(app.app_path_exe, Some(path.clone()));
+        assert_eq!(app.icon_path, None);
+        assert!(matches!(app.source, AppSource::AppImage(ref info) if info.appimage_type == 2));
+
+        // type 1 images are listed under their file name
+        let legacy = tmp.path().join("Legacy.AppImage");
+        std::fs::write(&legacy, elf_runtime(1, 256)).unwrap();
+        let app = load_app(&legacy, &HashMap::new()).unwrap();
+        assert_eq!(app.name, "Legacy");
+        assert_eq!(
+            app.source,
+            AppSource::AppImage(AppImageInfo {
+                appimage_type: 1,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize(Path::new("/usr/bin/../share/./icons/app.png")),
+            PathBuf::from("/usr/share/icons/app.png")
+        );
+        assert_eq!(
+            normalize(Path::new("/../app.png")),
+            PathBuf::from("/app.png")
+        );
+    }
+}
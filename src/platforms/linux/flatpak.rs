@@ -0,0 +1,305 @@
+//! Flatpak app discovery
+//!
+//! A Flatpak installation looks like this:
+//!
+//! ```text
+//! /var/lib/flatpak
+//! ├── app/org.gnome.Calculator
+//! │   ├── current -> x86_64/stable
+//! │   └── x86_64/stable
+//! │       ├── active -> <commit>
+//! │       └── <commit>/metadata
+//! └── exports/share
+//!     ├── applications/org.gnome.Calculator.desktop
+//!     └── icons/hicolor/...
+//! ```
+use crate::common::{App, FlatpakInfo, FlatpakInstallationKind};
+use crate::utils::xdg::BaseDirectories;
+use ini::ini;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const SYSTEM_INSTALLATION: &str = "/var/lib/flatpak";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlatpakInstallation {
+    pub path: PathBuf,
+    pub kind: FlatpakInstallationKind,
+}
+
+impl FlatpakInstallation {
+    pub fn new(path: PathBuf, kind: FlatpakInstallationKind) -> Self {
+        Self { path, kind }
+    }
+
+    /// Installations for the current process' environment, see [`FlatpakInstallation::from_env`]
+    pub fn discover(dirs: &BaseDirectories) -> Vec<FlatpakInstallation> {
+        Self::from_env(dirs, |key| std::env::var(key).ok())
+    }
+
+    /// Installations in the order flatpak itself prefers them: the user installation, then the system one.
+    /// `$FLATPAK_USER_DIR` and `$FLATPAK_SYSTEM_DIR` override the default locations, like they do for `flatpak`.
+    pub fn from_env<F>(dirs: &BaseDirectories, lookup: F) -> Vec<FlatpakInstallation>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let var = |key: &str| lookup(key).filter(|value| !value.is_empty());
+        let user_dir = var("FLATPAK_USER_DIR")
+            .map(PathBuf::from)
+            .or_else(|| dirs.data_home.as_ref().map(|dir| dir.join("flatpak")));
+        let system_dir = var("FLATPAK_SYSTEM_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(SYSTEM_INSTALLATION));
+        user_dir
+            .map(|path| FlatpakInstallation::new(path, FlatpakInstallationKind::User))
+            .into_iter()
+            .chain(std::iter::once(FlatpakInstallation::new(
+                system_dir,
+                FlatpakInstallationKind::System,
+            )))
+            .collect()
+    }
+
+    /// Where the installation exports the .desktop files of its apps
+    pub fn applications_dir(&self) -> PathBuf {
+        self.path.join("exports/share/applications")
+    }
+
+    /// Where the installation exports the icons of its apps
+    pub fn icons_dir(&self) -> PathBuf {
+        self.path.join("exports/share/icons")
+    }
+
+    /// Read the metadata of an installed app from its current deployment
+    pub fn app_info(&self, app_id: &str) -> Option<FlatpakInfo> {
+        let app_dir = self.path.join("app").join(app_id);
+        let current = app_dir.join("current");
+        // `current` is a symlink to `<arch>/<branch>`
+        let deployment = match std::fs::read_link(&current) {
+            Ok(target) => target,
+            Err(_) => current
+                .canonicalize()
+                .ok()?
+                .strip_prefix(&app_dir)
+                .ok()?
+                .to_path_buf(),
+        };
+        let mut components = deployment.components();
+        let arch = components.next()?.as_os_str().to_str()?.to_string();
+        let branch = components.next()?.as_os_str().to_str()?.to_string();
+        let runtime =
+            read_metadata(&current.join("active/metadata")).and_then(|metadata| metadata.runtime);
+        Some(FlatpakInfo {
+            app_id: app_id.to_string(),
+            branch,
+            arch,
+            runtime,
+            installation: self.path.clone(),
+            installation_kind: self.kind,
+        })
+    }
+
+    /// Find the app a .desktop file was exported by, if it's in this installation's exports.
+    /// Apps can export several .desktop files, whose names all start with the app ID.
+    pub fn app_info_for_desktop_file(&self, desktop_file: &Path) -> Option<FlatpakInfo> {
+        let file_name = desktop_file
+            .strip_prefix(self.applications_dir())
+            .ok()?
+            .to_str()?;
+        let mut app_id = file_name.strip_suffix(".desktop")?;
+        loop {
+            if self.path.join("app").join(app_id).is_dir() {
+                return self.app_info(app_id);
+            }
+            app_id = &app_id[..app_id.rfind('.')?];
+        }
+    }
+}
+
+/// The fields we use from an app's `metadata` keyfile
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlatpakMetadata {
+    pub name: Option<String>,
+    pub runtime: Option<String>,
+    pub command: Option<String>,
+}
+
+pub fn read_metadata(path: &Path) -> Option<FlatpakMetadata> {
+    let map = ini!(safe path.to_str()?).ok()?;
+    let application = map.get("application")?;
+    let get = |key: &str| application.get(key).cloned().flatten();
+    Some(FlatpakMetadata {
+        name: get("name"),
+        runtime: get("runtime"),
+        command: get("command"),
+    })
+}
+
+/// Tag an app as a Flatpak app if its .desktop file was exported by one of `installations`
+pub fn attribute_app(app: &mut App, installations: &[FlatpakInstallation]) {
+    if let Some(info) = installations
+        .iter()
+        .find_map(|installation| installation.app_info_for_desktop_file(&app.app_desktop_path))
+    {
+        app.source = crate::common::AppSource::Flatpak(info);
+    }
+}
+
+/// `flatpak run` command for the exact deployment an app was discovered in.
+/// Files are passed with `--file-forwarding`, so the sandboxed app is granted access to them.
+pub fn run_command(info: &FlatpakInfo, files: &[PathBuf]) -> Command {
+    let mut command = Command::new("flatpak");
+    command.arg("run");
+    command.arg(match info.installation_kind {
+        FlatpakInstallationKind::User => "--user",
+        FlatpakInstallationKind::System => "--system",
+    });
+    command.arg(format!("--branch={}", info.branch));
+    command.arg(format!("--arch={}", info.arch));
+    if files.is_empty() {
+        command.arg(&info.app_id);
+    } else {
+        command.arg("--file-forwarding");
+        command.arg(&info.app_id);
+        command.arg("@@");
+        command.args(files);
+        command.arg("@@");
+    }
+    command
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Create a fake installation with a single app, like `flatpak install` would
+    pub(crate) fn install_fixture_app(installation: &Path, app_id: &str, name: &str) {
+        let deployment = installation.join("app").join(app_id).join("x86_64/stable");
+        let commit = deployment.join("0123abcd");
+        fs::create_dir_all(commit.join("export/share/applications")).unwrap();
+        fs::write(
+            commit.join("metadata"),
+            format!("[Application]\nname={app_id}\nruntime=org.gnome.Platform/x86_64/46\nsdk=org.gnome.Sdk/x86_64/46\ncommand=calc\n\n[Context]\nshared=network;ipc;\n"),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink("0123abcd", deployment.join("active")).unwrap();
+        std::os::unix::fs::symlink(
+            "x86_64/stable",
+            installation.join("app").join(app_id).join("current"),
+        )
+        .unwrap();
+
+        let desktop_file = commit
+            .join("export/share/applications")
+            .join(format!("{app_id}.desktop"));
+        fs::write(
+            &desktop_file,
+            format!("[Desktop Entry]\nType=Application\nName={name}\nExec=/usr/bin/flatpak run --branch=stable --arch=x86_64 --command=calc {app_id} @@u %U @@\nIcon={app_id}\n"),
+        )
+        .unwrap();
+        let exports = installation.join("exports/share/applications");
+        fs::create_dir_all(&exports).unwrap();
+        std::os::unix::fs::symlink(&desktop_file, exports.join(format!("{app_id}.desktop")))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_from_env() {
+        let lookup = |key: &str| match key {
+            "HOME" => Some("/home/me".to_string()),
+            "FLATPAK_SYSTEM_DIR" => Some("/opt/flatpak".to_string()),
+            _ => None,
+        };
+        let dirs = BaseDirectories::from_env(lookup);
+        assert_eq!(
+            FlatpakInstallation::from_env(&dirs, lookup),
+            vec![
+                FlatpakInstallation::new(
+                    PathBuf::from("/home/me/.local/share/flatpak"),
+                    FlatpakInstallationKind::User
+                ),
+                FlatpakInstallation::new(
+                    PathBuf::from("/opt/flatpak"),
+                    FlatpakInstallationKind::System
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_app_info() {
+        let tmp = tempfile::tempdir().unwrap();
+        install_fixture_app(tmp.path(), "org.example.Calculator", "Calculator");
+        let installation =
+            FlatpakInstallation::new(tmp.path().to_path_buf(), FlatpakInstallationKind::User);
+
+        let info = installation.app_info("org.example.Calculator").unwrap();
+        assert_eq!(
+            info,
+            FlatpakInfo {
+                app_id: "org.example.Calculator".to_string(),
+                branch: "stable".to_string(),
+                arch: "x86_64".to_string(),
+                runtime: Some("org.gnome.Platform/x86_64/46".to_string()),
+                installation: tmp.path().to_path_buf(),
+                installation_kind: FlatpakInstallationKind::User,
+            }
+        );
+        assert!(installation.app_info("org.example.Missing").is_none());
+
+        let exports = installation.applications_dir();
+        let info = installation
+            .app_info_for_desktop_file(&exports.join("org.example.Calculator.desktop"))
+            .unwrap();
+        assert_eq!(info.app_id, "org.example.Calculator");
+        // extra .desktop files exported by the same app
+        let info = installation
+            .app_info_for_desktop_file(&exports.join("org.example.Calculator.Scientific.desktop"))
+            .unwrap();
+        assert_eq!(info.app_id, "org.example.Calculator");
+        assert!(installation
+            .app_info_for_desktop_file(Path::new(
+                "/usr/share/applications/org.example.Calculator.desktop"
+            ))
+            .is_none());
+    }
+
+    #[test]
+    fn test_run_command() {
+        let info = FlatpakInfo {
+            app_id: "org.example.Calculator".to_string(),
+            branch: "beta".to_string(),
+            arch: "aarch64".to_string(),
+            installation_kind: FlatpakInstallationKind::User,
+            ..Default::default()
+        };
+        let command = run_command(&info, &[]);
+        assert_eq!(command.get_program(), "flatpak");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            vec![
+                "run",
+                "--user",
+                "--branch=beta",
+                "--arch=aarch64",
+                "org.example.Calculator"
+            ]
+        );
+        let command = run_command(&info, &[PathBuf::from("/tmp/a.txt")]);
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            vec![
+                "run",
+                "--user",
+                "--branch=beta",
+                "--arch=aarch64",
+                "--file-forwarding",
+                "org.example.Calculator",
+                "@@",
+                "/tmp/a.txt",
+                "@@"
+            ]
+        );
+    }
+}
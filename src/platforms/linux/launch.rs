@@ -0,0 +1,441 @@
+//! Launching desktop entries
+//!
+//! <https://specifications.freedesktop.org/desktop-entry-spec/latest/exec-variables.html>
+//!
+//! The `Exec` key is a command line with field codes that are replaced by the files or URLs to open:
+//! `%f` a single file, `%F` a list of files, `%u` a single URL, `%U` a list of URLs,
+//! `%i` the icon, `%c` the name and `%k` the location of the .desktop file.
+use crate::utils::executable::{is_executable, path_dirs};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Terminal emulators to run `Terminal=true` entries in, in order of preference,
+/// with the arguments that come before the command line to run
+const TERMINALS: &[(&str, &[&str])] = &[
+    ("xdg-terminal-exec", &[]),
+    ("x-terminal-emulator", &["-e"]),
+    ("gnome-terminal", &["--"]),
+    ("konsole", &["-e"]),
+    ("xfce4-terminal", &["-x"]),
+    ("alacritty", &["-e"]),
+    ("kitty", &[]),
+    ("foot", &[]),
+    ("xterm", &["-e"]),
+];
+
+/// What to open: a local file, or a URL of any other scheme
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Path(PathBuf),
+    Url { scheme: String, url: String },
+}
+
+/// Decode the `%XX` escapes of a URL path
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+impl Target {
+    /// Tell a URL from a path. `file://` URLs are local paths, strings that aren't URLs are paths.
+    /// Relative paths are made absolute against the current directory, as the app may run in another one.
+    pub fn parse(target: &str) -> Self {
+        let scheme = target
+            .split_once(':')
+            .map(|(scheme, _)| scheme)
+            .filter(|scheme| {
+                scheme.len() > 1
+                    && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && scheme
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+            });
+        match scheme {
+            Some(scheme) if scheme.eq_ignore_ascii_case("file") => {
+                let path = target[scheme.len() + 1..].trim_start_matches("//");
+                // skip the host of `file://host/path`
+                let path = path.find('/').map_or(path, |start| &path[start..]);
+                Target::Path(PathBuf::from(percent_decode(path)))
+            }
+            Some(scheme) if !Path::new(target).exists() => Target::Url {
+                scheme: scheme.to_ascii_lowercase(),
+                url: target.to_string(),
+            },
+            _ => {
+                Target::Path(std::path::absolute(target).unwrap_or_else(|_| PathBuf::from(target)))
+            }
+        }
+    }
+
+    fn as_arg(&self) -> String {
+        match self {
+            Target::Path(path) => path.to_string_lossy().into_owned(),
+            Target::Url { url, .. } => url.clone(),
+        }
+    }
+}
+
+/// The keys of the `[Desktop Entry]` group needed to launch it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DesktopEntry {
+    pub path: PathBuf,
+    pub name: Option<String>,
+    /// `Name[<locale>]`, by locale
+    pub localized_names: HashMap<String, String>,
+    pub exec: Option<String>,
+    pub icon: Option<String>,
+    /// `Path`, the working directory to run the program in
+    pub working_dir: Option<PathBuf>,
+    /// `Terminal`, whether the program runs in a terminal window
+    pub terminal: bool,
+    /// `MimeType`, the types the program can open, including `x-scheme-handler/<scheme>` for URLs
+    pub mime_types: Vec<String>,
+}
+
+/// Undo the escapes of string values: `\s`, `\n`, `\t`, `\r` and `\\`
+fn unescape_value(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+impl DesktopEntry {
+    pub fn parse(path: &Path, content: &str) -> Self {
+        let mut keys: HashMap<&str, String> = HashMap::new();
+        let mut in_entry = false;
+        for line in content.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_entry = line == "[Desktop Entry]";
+            } else if let (true, Some((key, value))) = (in_entry, line.split_once('=')) {
+                keys.entry(key.trim())
+                    .or_insert_with(|| unescape_value(value.trim()));
+            }
+        }
+        let localized_names = keys
+            .iter()
+            .filter_map(|(key, value)| {
+                let locale = key.strip_prefix("Name[")?.strip_suffix(']')?;
+                Some((locale.to_string(), value.clone()))
+            })
+            .collect();
+        Self {
+            path: path.to_path_buf(),
+            name: keys.remove("Name"),
+            localized_names,
+            exec: keys.remove("Exec"),
+            icon: keys.remove("Icon"),
+            working_dir: keys.remove("Path").map(PathBuf::from),
+            terminal: keys.remove("Terminal").is_some_and(|value| value == "true"),
+            mime_types: keys
+                .remove("MimeType")
+                .map(|value| {
+                    value
+                        .split(';')
+                        .map(str::trim)
+                        .filter(|mime_type| !mime_type.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The `Name` for the first of `locales` it's translated to, else the untranslated one
+    pub fn localized_name(&self, locales: &[String]) -> Option<&str> {
+        locales
+            .iter()
+            .find_map(|locale| self.localized_names.get(locale))
+            .or(self.name.as_ref())
+            .map(String::as_str)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(Self::parse(path, &std::fs::read_to_string(path)?))
+    }
+
+    /// The command line that opens `targets`, with the field codes of `Exec` expanded,
+    /// inside a terminal emulator found on `$PATH` if the entry needs one
+    pub fn command_line(&self, targets: &[Target]) -> Result<Vec<String>> {
+        let exec = self
+            .exec
+            .as_deref()
+            .ok_or_else(|| anyhow!("{:?} has no Exec key", self.path))?;
+        let mut argv: Vec<String> = vec![];
+        for arg in split_exec(exec)? {
+            match arg.as_str() {
+                "%f" | "%u" => argv.extend(targets.first().map(Target::as_arg)),
+                "%F" | "%U" => argv.extend(targets.iter().map(Target::as_arg)),
+                "%i" => {
+                    if let Some(icon) = &self.icon {
+                        argv.push("--icon".to_string());
+                        argv.push(icon.clone());
+                    }
+                }
+                _ => argv.push(self.expand_field_codes(&arg, targets)),
+            }
+        }
+        if argv.is_empty() {
+            return Err(anyhow!("{:?} has an empty Exec key", self.path));
+        }
+        if self.terminal {
+            return self.in_terminal(argv, &path_dirs());
+        }
+        Ok(argv)
+    }
+
+    /// Prefix a command line with the first of [`TERMINALS`] found in `dirs`
+    fn in_terminal(&self, argv: Vec<String>, dirs: &[PathBuf]) -> Result<Vec<String>> {
+        let (terminal, args) = TERMINALS
+            .iter()
+            .find(|(terminal, _)| dirs.iter().any(|dir| is_executable(&dir.join(terminal))))
+            .ok_or_else(|| anyhow!("{:?} runs in a terminal, but none was found", self.path))?;
+        Ok(std::iter::once(terminal.to_string())
+            .chain(args.iter().map(|arg| arg.to_string()))
+            .chain(argv)
+            .collect())
+    }
+
+    /// Expand the field codes inside an argument, deprecated and unknown ones are dropped
+    fn expand_field_codes(&self, arg: &str, targets: &[Target]) -> String {
+        let mut expanded = String::with_capacity(arg.len());
+        let mut chars = arg.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                expanded.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('%') => expanded.push('%'),
+                Some('f' | 'u') => expanded.extend(targets.first().map(Target::as_arg)),
+                Some('c') => expanded.push_str(self.name.as_deref().unwrap_or_default()),
+                Some('k') => expanded.push_str(&self.path.to_string_lossy()),
+                _ => {}
+            }
+        }
+        expanded
+    }
+}
+
+/// Split an `Exec` value into arguments. Arguments with reserved characters are quoted with `"`,
+/// in which `"`, `` ` ``, `$` and `\` are escaped with a backslash.
+pub fn split_exec(exec: &str) -> Result<Vec<String>> {
+    let mut args: Vec<String> = vec![];
+    let mut current: Option<String> = None;
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' => args.extend(current.take()),
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped @ ('"' | '`' | '$' | '\\')) => arg.push(escaped),
+                            Some(other) => {
+                                arg.push('\\');
+                                arg.push(other);
+                            }
+                            None => return Err(anyhow!("Unterminated quote in Exec {:?}", exec)),
+                        },
+                        Some(other) => arg.push(other),
+                        None => return Err(anyhow!("Unterminated quote in Exec {:?}", exec)),
+                    }
+                }
+            }
+            _ => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+/// Start a command line without waiting for it
+pub fn spawn(argv: &[String], working_dir: Option<&Path>) -> Result<()> {
+    let (program, args) = argv
+        .split_first()
+        .ok_or_else(|| anyhow!("Empty command line"))?;
+    let mut command = std::process::Command::new(program);
+    command
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    if let Some(dir) = working_dir.filter(|dir| dir.is_dir()) {
+        command.current_dir(dir);
+    }
+    command
+        .spawn()
+        .map_err(|e| anyhow!("Failed to run {:?}: {}", program, e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(
+            Target::parse("https://example.com/a?b=c"),
+            Target::Url {
+                scheme: "https".to_string(),
+                url: "https://example.com/a?b=c".to_string()
+            }
+        );
+        assert_eq!(
+            Target::parse("mailto:me@example.com"),
+            Target::Url {
+                scheme: "mailto".to_string(),
+                url: "mailto:me@example.com".to_string()
+            }
+        );
+        assert_eq!(
+            Target::parse("file:///home/me/My%20Notes.txt"),
+            Target::Path(PathBuf::from("/home/me/My Notes.txt"))
+        );
+        assert_eq!(
+            Target::parse("file://localhost/tmp/a.txt"),
+            Target::Path(PathBuf::from("/tmp/a.txt"))
+        );
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(
+            Target::parse("notes.txt"),
+            Target::Path(cwd.join("notes.txt"))
+        );
+        assert_eq!(
+            Target::parse("C:/Users/me"),
+            Target::Path(cwd.join("C:/Users/me"))
+        );
+        assert_eq!(
+            Target::parse("/tmp/a.txt"),
+            Target::Path(PathBuf::from("/tmp/a.txt"))
+        );
+    }
+
+    #[test]
+    fn test_split_exec() {
+        assert_eq!(
+            split_exec(r#""/opt/My App/bin/app" --name "say \"hi\" \$HOME" %U"#).unwrap(),
+            vec!["/opt/My App/bin/app", "--name", "say \"hi\" $HOME", "%U"]
+        );
+        assert!(split_exec(r#"app "unterminated"#).is_err());
+    }
+
+    #[test]
+    fn test_command_line() {
+        let entry = DesktopEntry::parse(
+            Path::new("/usr/share/applications/org.gnome.TextEditor.desktop"),
+            "[Desktop Entry]\nName=Text Editor\nExec=gnome-text-editor --title=%c %i %F\nIcon=org.gnome.TextEditor\n[Desktop Action new-window]\nExec=gnome-text-editor --new-window\n",
+        );
+        let files = [
+            Target::Path(PathBuf::from("/tmp/a.txt")),
+            Target::Path(PathBuf::from("/tmp/b c.txt")),
+        ];
+        assert_eq!(
+            entry.command_line(&files).unwrap(),
+            vec![
+                "gnome-text-editor",
+                "--title=Text Editor",
+                "--icon",
+                "org.gnome.TextEditor",
+                "/tmp/a.txt",
+                "/tmp/b c.txt"
+            ]
+        );
+        // field codes without targets are dropped
+        assert_eq!(
+            entry.command_line(&[]).unwrap(),
+            vec![
+                "gnome-text-editor",
+                "--title=Text Editor",
+                "--icon",
+                "org.gnome.TextEditor"
+            ]
+        );
+
+        let entry = DesktopEntry::parse(
+            Path::new("/apps/firefox.desktop"),
+            "[Desktop Entry]\nName=Firefox\nExec=firefox --url=%u %k 100%%\n",
+        );
+        let url = Target::parse("https://example.com");
+        assert_eq!(
+            entry.command_line(&[url]).unwrap(),
+            vec![
+                "firefox",
+                "--url=https://example.com",
+                "/apps/firefox.desktop",
+                "100%"
+            ]
+        );
+        assert!(
+            DesktopEntry::parse(Path::new("/apps/empty.desktop"), "[Desktop Entry]\n")
+                .command_line(&[])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_terminal() {
+        let entry = DesktopEntry::parse(
+            Path::new("/usr/share/applications/htop.desktop"),
+            "[Desktop Entry]\nName=Htop\nExec=htop\nTerminal=true\n",
+        );
+        assert!(entry.terminal);
+        let dir = tempfile::tempdir().unwrap();
+        let argv = vec!["htop".to_string()];
+        assert!(entry
+            .in_terminal(argv.clone(), &[dir.path().to_path_buf()])
+            .is_err());
+
+        for terminal in ["xterm", "gnome-terminal"] {
+            let path = dir.path().join(terminal);
+            std::fs::write(&path, "#!/bin/sh\n").unwrap();
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        assert_eq!(
+            entry
+                .in_terminal(argv, &[dir.path().to_path_buf()])
+                .unwrap(),
+            vec!["gnome-terminal", "--", "htop"]
+        );
+    }
+}
@@ -0,0 +1,697 @@
+//! File type detection with the shared-mime-info database
+//!
+//! <https://specifications.freedesktop.org/shared-mime-info-spec/latest/>
+//!
+//! `update-mime-database` compiles the database into each `mime` data directory:
+//!
+//! - `globs2`: `weight:type:pattern[:flags]` lines matched against file names, `cs` marks case-sensitive patterns
+//! - `magic`: byte rules matched against the content, grouped in `[priority:type]` sections
+//! - `subclasses`: `type parent` lines, e.g. `application/x-shellscript text/plain`
+//! - `aliases`: `alias type` lines, e.g. `application/x-pdf application/pdf`
+use crate::utils::xdg::BaseDirectories;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Type of files whose content doesn't match any rule
+pub const OCTET_STREAM: &str = "application/octet-stream";
+pub const TEXT_PLAIN: &str = "text/plain";
+
+/// Largest prefix of a file read for magic sniffing
+const MAX_MAGIC_BYTES: usize = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobRule {
+    pub weight: u32,
+    pub mime_type: String,
+    pub pattern: glob::Pattern,
+    pub case_sensitive: bool,
+}
+
+impl GlobRule {
+    /// Patterns without wildcards match whole file names, and take precedence over other patterns
+    fn is_literal(&self) -> bool {
+        !self.pattern.as_str().contains(['*', '?', '['])
+    }
+
+    fn matches(&self, file_name: &str) -> bool {
+        let options = glob::MatchOptions {
+            case_sensitive: self.case_sensitive,
+            require_literal_separator: false,
+            require_literal_leading_dot: false,
+        };
+        self.pattern.matches_with(file_name, options)
+    }
+}
+
+/// Parse a `globs2` file. `__NOGLOBS__` patterns are kept, they drop the globs of lower precedence directories.
+pub fn parse_globs2(content: &str) -> Vec<GlobRule> {
+    content
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, ':');
+            let weight = fields.next()?.parse().ok()?;
+            let mime_type = fields.next()?.to_string();
+            let pattern = fields.next()?;
+            let case_sensitive = fields
+                .next()
+                .is_some_and(|flags| flags.split(',').any(|flag| flag == "cs"));
+            let pattern = match glob::Pattern::new(pattern) {
+                Ok(pattern) => pattern,
+                Err(e) => {
+                    log::debug!("Invalid MIME glob {:?}: {}", pattern, e);
+                    return None;
+                }
+            };
+            Some(GlobRule {
+                weight,
+                mime_type,
+                pattern,
+                case_sensitive,
+            })
+        })
+        .collect()
+}
+
+/// A line of a magic section: `[indent]>offset=value[&mask][~word-size][+range-length]`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MagicRule {
+    pub indent: u32,
+    pub offset: usize,
+    pub value: Vec<u8>,
+    pub mask: Option<Vec<u8>>,
+    /// Number of offsets the value is searched at, starting from `offset`
+    pub range: usize,
+}
+
+impl MagicRule {
+    fn matches(&self, data: &[u8]) -> bool {
+        (self.offset..self.offset + self.range).any(|start| {
+            let Some(window) = data.get(start..start + self.value.len()) else {
+                return false;
+            };
+            match &self.mask {
+                Some(mask) => window
+                    .iter()
+                    .zip(&self.value)
+                    .zip(mask)
+                    .all(|((byte, value), mask)| byte & mask == value & mask),
+                None => window == self.value.as_slice(),
+            }
+        })
+    }
+
+    /// How many bytes of a file the rule looks at
+    fn extent(&self) -> usize {
+        self.offset + self.range + self.value.len()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MagicSection {
+    pub priority: u32,
+    pub mime_type: String,
+    /// Rules in file order. A rule matches if its test matches and, when it has rules indented below it, one of them matches.
+    pub rules: Vec<MagicRule>,
+    /// `__NOMAGIC__`: drop the magic of lower precedence directories for this type
+    pub no_magic: bool,
+}
+
+/// Check if any top level rule of `rules` matches along with its children
+fn rules_match(rules: &[MagicRule], data: &[u8]) -> bool {
+    let mut i = 0;
+    while i < rules.len() {
+        let indent = rules[i].indent;
+        let end = rules[i + 1..]
+            .iter()
+            .position(|rule| rule.indent <= indent)
+            .map_or(rules.len(), |position| i + 1 + position);
+        let children = &rules[i + 1..end];
+        if rules[i].matches(data) && (children.is_empty() || rules_match(children, data)) {
+            return true;
+        }
+        i = end;
+    }
+    false
+}
+
+impl MagicSection {
+    pub fn matches(&self, data: &[u8]) -> bool {
+        rules_match(&self.rules, data)
+    }
+}
+
+/// Cursor over the bytes of a `magic` file
+struct MagicReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl MagicReader<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn take(&mut self, len: usize) -> Option<&[u8]> {
+        let bytes = self.data.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|byte| byte.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.data[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    fn skip_line(&mut self) {
+        while let Some(byte) = self.peek() {
+            self.pos += 1;
+            if byte == b'\n' {
+                break;
+            }
+        }
+    }
+
+    /// `[priority:type]`
+    fn section_header(&mut self) -> Option<(u32, String)> {
+        self.pos += 1;
+        let end = self.data[self.pos..]
+            .iter()
+            .position(|&byte| byte == b']')?;
+        let header = std::str::from_utf8(&self.data[self.pos..self.pos + end]).ok()?;
+        self.pos += end + 1;
+        let (priority, mime_type) = header.split_once(':')?;
+        Some((priority.parse().ok()?, mime_type.to_string()))
+    }
+
+    fn rule(&mut self) -> Option<MagicRule> {
+        let indent = self.number().unwrap_or(0) as u32;
+        if self.take(1)? != b">" {
+            return None;
+        }
+        let offset = self.number()?;
+        if self.take(1)? != b"=" {
+            return None;
+        }
+        let len = self.take(2)?;
+        let len = u16::from_be_bytes([len[0], len[1]]) as usize;
+        let mut rule = MagicRule {
+            indent,
+            offset,
+            value: self.take(len)?.to_vec(),
+            range: 1,
+            ..Default::default()
+        };
+        let mut word_size = 1;
+        loop {
+            match self.take(1)?[0] {
+                b'&' => rule.mask = Some(self.take(len)?.to_vec()),
+                b'~' => word_size = self.number()?,
+                b'+' => rule.range = self.number()?.max(1),
+                b'\n' => break,
+                _ => return None,
+            }
+        }
+        // values of word-size > 1 are stored big-endian, and compared with the file in host order
+        if word_size > 1 && cfg!(target_endian = "little") {
+            rule.value = swap_words(&rule.value, word_size);
+            rule.mask = rule.mask.map(|mask| swap_words(&mask, word_size));
+        }
+        Some(rule)
+    }
+}
+
+fn swap_words(bytes: &[u8], word_size: usize) -> Vec<u8> {
+    bytes
+        .chunks(word_size)
+        .flat_map(|word| word.iter().rev().copied())
+        .collect()
+}
+
+/// Parse a binary `magic` file. Lines that can't be parsed are skipped.
+pub fn parse_magic(data: &[u8]) -> Vec<MagicSection> {
+    let Some(data) = data.strip_prefix(b"MIME-Magic\0\n") else {
+        return vec![];
+    };
+    let mut reader = MagicReader { data, pos: 0 };
+    let mut sections: Vec<MagicSection> = vec![];
+    while let Some(byte) = reader.peek() {
+        if byte == b'[' {
+            match reader.section_header() {
+                Some((priority, mime_type)) => sections.push(MagicSection {
+                    priority,
+                    mime_type,
+                    ..Default::default()
+                }),
+                None => reader.skip_line(),
+            }
+            if reader.peek() == Some(b'\n') {
+                reader.pos += 1;
+            }
+            continue;
+        }
+        let Some(section) = sections.last_mut() else {
+            reader.skip_line();
+            continue;
+        };
+        if reader.data[reader.pos..].starts_with(b"__NOMAGIC__") {
+            section.no_magic = true;
+            reader.skip_line();
+            continue;
+        }
+        let start = reader.pos;
+        match reader.rule() {
+            Some(rule) => section.rules.push(rule),
+            None => {
+                reader.pos = start;
+                reader.skip_line();
+            }
+        }
+    }
+    sections
+}
+
+/// Parse the `subclasses` or `aliases` file: two MIME types per line
+fn parse_pairs(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (first, second) = line.split_once(' ')?;
+            Some((first.to_string(), second.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Check if the start of a file looks like text: valid UTF-8 without NUL bytes. A character cut at the end is fine.
+fn looks_like_text(data: &[u8]) -> bool {
+    !data.contains(&0)
+        && match std::str::from_utf8(data) {
+            Ok(_) => true,
+            Err(e) => e.error_len().is_none(),
+        }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MimeDatabase {
+    pub globs: Vec<GlobRule>,
+    /// Sections from the highest priority to the lowest
+    pub magic: Vec<MagicSection>,
+    /// Direct parents of each type
+    pub subclasses: HashMap<String, Vec<String>>,
+    /// Canonical type of each alias
+    pub aliases: HashMap<String, String>,
+}
+
+impl MimeDatabase {
+    /// Load the database from the `mime` directory of each data directory
+    pub fn discover(dirs: &BaseDirectories) -> Self {
+        Self::load(&dirs.data_subdirs("mime"))
+    }
+
+    /// Load the database from `mime` directories in order of precedence
+    pub fn load(mime_dirs: &[PathBuf]) -> Self {
+        let mut db = Self::default();
+        for dir in mime_dirs.iter().rev() {
+            db.load_dir(dir);
+        }
+        db.globs
+            .retain(|glob| glob.pattern.as_str() != "__NOGLOBS__");
+        db.magic.retain(|section| !section.rules.is_empty());
+        db.magic
+            .sort_by_key(|section| std::cmp::Reverse(section.priority));
+        db
+    }
+
+    /// Add a directory that takes precedence over the ones loaded before
+    fn load_dir(&mut self, dir: &Path) {
+        let read = |file_name: &str| std::fs::read_to_string(dir.join(file_name)).ok();
+        if let Some(content) = read("globs2") {
+            for glob in parse_globs2(&content) {
+                if glob.pattern.as_str() == "__NOGLOBS__" {
+                    self.globs.retain(|other| other.mime_type != glob.mime_type);
+                }
+                self.globs.push(glob);
+            }
+        }
+        if let Ok(data) = std::fs::read(dir.join("magic")) {
+            for section in parse_magic(&data) {
+                if section.no_magic {
+                    self.magic
+                        .retain(|other| other.mime_type != section.mime_type);
+                }
+                self.magic.push(section);
+            }
+        }
+        for (child, parent) in parse_pairs(&read("subclasses").unwrap_or_default()) {
+            let parents = self.subclasses.entry(child).or_default();
+            if !parents.contains(&parent) {
+                parents.push(parent);
+            }
+        }
+        for (alias, mime_type) in parse_pairs(&read("aliases").unwrap_or_default()) {
+            self.aliases.insert(alias, mime_type);
+        }
+    }
+
+    /// The canonical name of a type, e.g. `application/pdf` for `application/x-pdf`
+    pub fn unalias(&self, mime_type: &str) -> String {
+        let mime_type = mime_type.to_ascii_lowercase();
+        self.aliases.get(&mime_type).cloned().unwrap_or(mime_type)
+    }
+
+    /// A type followed by all the types it inherits from, closest first.
+    /// Besides the `subclasses` file, `text/*` types are subclasses of `text/plain` and all files of `application/octet-stream`.
+    pub fn with_parents(&self, mime_type: &str) -> Vec<String> {
+        let mime_type = self.unalias(mime_type);
+        let mut types = vec![mime_type.clone()];
+        let mut i = 0;
+        while i < types.len() {
+            let current = types[i].clone();
+            let mut parents: Vec<String> = self
+                .subclasses
+                .get(&current)
+                .into_iter()
+                .flatten()
+                .map(|parent| self.unalias(parent))
+                .collect();
+            if current.starts_with("text/") && current != TEXT_PLAIN {
+                parents.push(TEXT_PLAIN.to_string());
+            }
+            for parent in parents {
+                if !types.contains(&parent) {
+                    types.push(parent);
+                }
+            }
+            i += 1;
+        }
+        if !mime_type.starts_with("inode/") && !types.iter().any(|t| t == OCTET_STREAM) {
+            types.push(OCTET_STREAM.to_string());
+        }
+        types
+    }
+
+    /// Check if `mime_type` is `parent` or inherits from it
+    pub fn is_subclass(&self, mime_type: &str, parent: &str) -> bool {
+        self.with_parents(mime_type).contains(&self.unalias(parent))
+    }
+
+    /// Types whose globs match a file name best: literal names first, then case-sensitive patterns,
+    /// then the highest weight, then the longest pattern
+    pub fn mime_types_for_name(&self, file_name: &str) -> Vec<String> {
+        let matches: Vec<&GlobRule> = self
+            .globs
+            .iter()
+            .filter(|glob| glob.matches(file_name))
+            .collect();
+        let literals: Vec<&GlobRule> = matches
+            .iter()
+            .copied()
+            .filter(|glob| glob.is_literal())
+            .collect();
+        let mut candidates = if literals.is_empty() {
+            matches
+        } else {
+            literals
+        };
+        if candidates.iter().any(|glob| glob.case_sensitive) {
+            candidates.retain(|glob| glob.case_sensitive);
+        }
+        let best = candidates
+            .iter()
+            .map(|glob| (glob.weight, glob.pattern.as_str().len()))
+            .max();
+        let mut types: Vec<String> = vec![];
+        for glob in candidates {
+            if Some((glob.weight, glob.pattern.as_str().len())) == best {
+                let mime_type = self.unalias(&glob.mime_type);
+                if !types.contains(&mime_type) {
+                    types.push(mime_type);
+                }
+            }
+        }
+        types
+    }
+
+    /// The type of the highest priority magic section matching the start of a file
+    pub fn mime_type_for_data(&self, data: &[u8]) -> Option<String> {
+        self.magic
+            .iter()
+            .find(|section| section.matches(data))
+            .map(|section| self.unalias(&section.mime_type))
+    }
+
+    fn magic_extent(&self) -> usize {
+        self.magic
+            .iter()
+            .flat_map(|section| &section.rules)
+            .map(MagicRule::extent)
+            .max()
+            .unwrap_or(0)
+            .min(MAX_MAGIC_BYTES)
+    }
+
+    /// Detect the type of a file from its name, then from its content when the name is ambiguous or unknown
+    pub fn mime_type_for_file(&self, path: &Path) -> String {
+        if path.is_dir() {
+            return "inode/directory".to_string();
+        }
+        let globs = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| self.mime_types_for_name(name))
+            .unwrap_or_default();
+        if globs.len() == 1 {
+            return globs[0].clone();
+        }
+
+        let mut data = vec![];
+        if let Ok(file) = std::fs::File::open(path) {
+            let limit = self.magic_extent().max(512) as u64;
+            if let Err(e) = file.take(limit).read_to_end(&mut data) {
+                log::debug!("Failed to read {:?}: {}", path, e);
+            }
+        }
+        let magic = self.mime_type_for_data(&data);
+        if let Some(first) = globs.first() {
+            // the name is ambiguous, the content decides between the candidates
+            return magic
+                .and_then(|magic| {
+                    globs
+                        .iter()
+                        .find(|glob| self.is_subclass(&magic, glob))
+                        .cloned()
+                })
+                .unwrap_or_else(|| first.clone());
+        }
+        match magic {
+            Some(magic) => magic,
+            None if path.is_file() && data.is_empty() => "application/x-zerosize".to_string(),
+            None if path.is_file() && looks_like_text(&data) => TEXT_PLAIN.to_string(),
+            None => OCTET_STREAM.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::fs;
+
+    /// A magic rule as written in a `magic` file
+    fn magic_rule(indent: &str, offset: usize, value: &[u8], extra: &[u8]) -> Vec<u8> {
+        let mut line = format!("{indent}>{offset}=").into_bytes();
+        line.extend((value.len() as u16).to_be_bytes());
+        line.extend(value);
+        line.extend(extra);
+        line.push(b'\n');
+        line
+    }
+
+    /// Write a small database to `<root>/mime`
+    pub(crate) fn install_fixture_database(root: &Path) -> PathBuf {
+        let dir = root.join("mime");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("globs2"),
+            "# generated\n50:application/pdf:*.pdf\n50:text/x-csrc:*.c\n50:text/x-c++src:*.C:cs\n50:application/x-shellscript:*.sh\n50:text/plain:*.txt\n50:text/x-makefile:makefile\n10:text/x-makefile:*.mk\n50:application/x-sharedlib:*.so\n50:application/x-object:*.o\n50:image/png:*.png\n50:image/x-fake-png:*.png\n",
+        )
+        .unwrap();
+        let mut magic = b"MIME-Magic\0\n".to_vec();
+        magic.extend(b"[50:application/pdf]\n");
+        magic.extend(magic_rule("", 0, b"%PDF-", b""));
+        magic.extend(b"[50:image/png]\n");
+        magic.extend(magic_rule("", 0, b"\x89PNG", b""));
+        magic.extend(b"[50:application/x-executable]\n");
+        magic.extend(magic_rule("", 0, b"\x7fELF", b""));
+        magic.extend(magic_rule("1", 16, b"\x02\x00", b""));
+        magic.extend(b"[40:application/x-sharedlib]\n");
+        magic.extend(magic_rule("", 0, b"\x7fELF", b""));
+        magic.extend(magic_rule("1", 16, b"\x03\x00", b""));
+        magic.extend(b"[30:application/x-shellscript]\n");
+        magic.extend(magic_rule("", 0, b"#!", b""));
+        magic.extend(magic_rule("1", 2, b"/bin/sh", b"+8"));
+        magic.extend(b"[20:text/x-csrc]\n");
+        magic.extend(magic_rule("", 0, b"#INC", b"&\xff\xdf\xdf\xdf"));
+        fs::write(dir.join("magic"), magic).unwrap();
+        fs::write(
+            dir.join("subclasses"),
+            "application/x-shellscript text/plain\ntext/x-csrc text/plain\n",
+        )
+        .unwrap();
+        fs::write(dir.join("aliases"), "application/x-pdf application/pdf\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_parse_globs2() {
+        let globs = parse_globs2("# comment\n80:text/html:*.html\n50:text/x-c++src:*.C:cs\n");
+        assert_eq!(globs.len(), 2);
+        assert_eq!(globs[0].weight, 80);
+        assert_eq!(globs[0].mime_type, "text/html");
+        assert!(!globs[0].case_sensitive);
+        assert!(globs[1].case_sensitive);
+    }
+
+    #[test]
+    fn test_parse_magic() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = install_fixture_database(tmp.path());
+        let sections = parse_magic(&fs::read(dir.join("magic")).unwrap());
+        assert_eq!(sections.len(), 6);
+        assert_eq!(sections[2].mime_type, "application/x-executable");
+        assert_eq!(sections[2].rules.len(), 2);
+        assert_eq!(sections[2].rules[1].indent, 1);
+        assert_eq!(sections[4].rules[1].range, 8);
+        assert_eq!(
+            sections[5].rules[0].mask,
+            Some(b"\xff\xdf\xdf\xdf".to_vec())
+        );
+
+        // host-endian values are stored big-endian
+        let mut magic = b"MIME-Magic\0\n[50:application/x-host]\n".to_vec();
+        magic.extend(magic_rule("", 4, b"\x00\x01\x00\x02", b"~2"));
+        let sections = parse_magic(&magic);
+        let expected: &[u8] = if cfg!(target_endian = "little") {
+            b"\x01\x00\x02\x00"
+        } else {
+            b"\x00\x01\x00\x02"
+        };
+        assert_eq!(sections[0].rules[0].value, expected);
+    }
+
+    #[test]
+    fn test_mime_types_for_name() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = MimeDatabase::load(&[install_fixture_database(tmp.path())]);
+        assert_eq!(
+            db.mime_types_for_name("report.PDF"),
+            vec!["application/pdf"]
+        );
+        assert_eq!(db.mime_types_for_name("main.c"), vec!["text/x-csrc"]);
+        assert_eq!(db.mime_types_for_name("main.C"), vec!["text/x-c++src"]);
+        // literal names win over patterns with a higher weight
+        assert_eq!(db.mime_types_for_name("makefile"), vec!["text/x-makefile"]);
+        assert_eq!(
+            db.mime_types_for_name("logo.png"),
+            vec!["image/png", "image/x-fake-png"]
+        );
+        assert!(db.mime_types_for_name("README").is_empty());
+    }
+
+    #[test]
+    fn test_mime_type_for_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = MimeDatabase::load(&[install_fixture_database(tmp.path())]);
+        let file = |name: &str, content: &[u8]| {
+            let path = tmp.path().join(name);
+            fs::write(&path, content).unwrap();
+            path
+        };
+        // unknown names are sniffed
+        assert_eq!(
+            db.mime_type_for_file(&file("document", b"%PDF-1.7\n")),
+            "application/pdf"
+        );
+        assert_eq!(
+            db.mime_type_for_file(&file(
+                "library",
+                b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0\x03\0"
+            )),
+            "application/x-sharedlib"
+        );
+        assert_eq!(
+            db.mime_type_for_file(&file("install", b"#!/usr/bin/sh\n")),
+            "application/x-shellscript"
+        );
+        assert_eq!(
+            db.mime_type_for_file(&file("header", b"#include <stdio.h>\n")),
+            "text/x-csrc"
+        );
+        assert_eq!(
+            db.mime_type_for_file(&file("notes", b"hello\n")),
+            TEXT_PLAIN
+        );
+        assert_eq!(
+            db.mime_type_for_file(&file("blob", b"\0\x01\x02")),
+            OCTET_STREAM
+        );
+        assert_eq!(
+            db.mime_type_for_file(&file("empty", b"")),
+            "application/x-zerosize"
+        );
+        // names win over content, unless they're ambiguous
+        assert_eq!(
+            db.mime_type_for_file(&file("script.txt", b"#!/bin/sh\n")),
+            TEXT_PLAIN
+        );
+        assert_eq!(
+            db.mime_type_for_file(&file("logo.png", b"\x89PNG\r\n")),
+            "image/png"
+        );
+        assert_eq!(db.mime_type_for_file(tmp.path()), "inode/directory");
+    }
+
+    #[test]
+    fn test_subclasses() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = MimeDatabase::load(&[install_fixture_database(tmp.path())]);
+        assert_eq!(
+            db.with_parents("application/x-shellscript"),
+            vec!["application/x-shellscript", TEXT_PLAIN, OCTET_STREAM]
+        );
+        assert_eq!(
+            db.with_parents("text/x-makefile"),
+            vec!["text/x-makefile", TEXT_PLAIN, OCTET_STREAM]
+        );
+        assert_eq!(
+            db.with_parents("application/x-pdf"),
+            vec!["application/pdf", OCTET_STREAM]
+        );
+        assert_eq!(db.with_parents("inode/directory"), vec!["inode/directory"]);
+        assert!(db.is_subclass("text/x-csrc", "text/plain"));
+        assert!(!db.is_subclass("application/pdf", "text/plain"));
+    }
+
+    #[test]
+    fn test_directory_precedence() {
+        let tmp = tempfile::tempdir().unwrap();
+        let system = install_fixture_database(&tmp.path().join("system"));
+        let user = tmp.path().join("user/mime");
+        fs::create_dir_all(&user).unwrap();
+        fs::write(
+            user.join("globs2"),
+            "50:image/x-fake-png:__NOGLOBS__\n50:text/markdown:*.md\n",
+        )
+        .unwrap();
+        let db = MimeDatabase::load(&[user, system]);
+        assert_eq!(db.mime_types_for_name("logo.png"), vec!["image/png"]);
+        assert_eq!(db.mime_types_for_name("README.md"), vec!["text/markdown"]);
+    }
+}
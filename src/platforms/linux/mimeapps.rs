@@ -0,0 +1,601 @@
+//! MIME type associations
+//!
+//! <https://specifications.freedesktop.org/mime-apps-spec/latest/>
+//!
+//! The default app and the apps associated with a MIME type come from `mimeapps.list` files, looked up in order in:
+//!
+//! - `$XDG_CONFIG_HOME`, then `$XDG_CONFIG_DIRS`
+//! - `$XDG_DATA_HOME/applications`, then each of `$XDG_DATA_DIRS/applications`
+//!
+//! In each directory, `$desktop-mimeapps.list` for each desktop of `$XDG_CURRENT_DESKTOP` comes before `mimeapps.list`.
+//! Data directories also have a `mimeinfo.cache`, built by `update-desktop-database` from the `MimeType` key of their .desktop files.
+use super::desktop_file_id;
+use crate::utils::xdg::BaseDirectories;
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// The groups of a `mimeapps.list` file, mapping MIME types to desktop file IDs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MimeAppsList {
+    /// `[Default Applications]`
+    pub default_apps: HashMap<String, Vec<String>>,
+    /// `[Added Associations]`
+    pub added: HashMap<String, Vec<String>>,
+    /// `[Removed Associations]`
+    pub removed: HashMap<String, Vec<String>>,
+}
+
+/// Parse the groups of a `mimeapps.list` or `mimeinfo.cache` file. Each key is a MIME type,
+/// each value a `;` separated list of desktop file IDs.
+fn parse_groups(content: &str) -> HashMap<String, HashMap<String, Vec<String>>> {
+    let mut groups: HashMap<String, HashMap<String, Vec<String>>> = HashMap::new();
+    let mut current: Option<String> = None;
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(group) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            current = Some(group.to_string());
+            continue;
+        }
+        let (Some(group), Some((key, value))) = (&current, line.split_once('=')) else {
+            continue;
+        };
+        let ids = value
+            .split(';')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(str::to_string);
+        groups
+            .entry(group.clone())
+            .or_default()
+            .entry(key.trim().to_ascii_lowercase())
+            .or_default()
+            .extend(ids);
+    }
+    groups
+}
+
+/// Parse a `mimeapps.list` file
+pub fn parse_mimeapps_list(content: &str) -> MimeAppsList {
+    let mut groups = parse_groups(content);
+    let mut take = |group: &str| groups.remove(group).unwrap_or_default();
+    MimeAppsList {
+        default_apps: take("Default Applications"),
+        added: take("Added Associations"),
+        removed: take("Removed Associations"),
+    }
+}
+
+/// Parse the `[MIME Cache]` group of a `mimeinfo.cache` file
+pub fn parse_mimeinfo_cache(content: &str) -> HashMap<String, Vec<String>> {
+    parse_groups(content)
+        .remove("MIME Cache")
+        .unwrap_or_default()
+}
+
+/// The files of a directory that takes part in the lookup
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MimeAppsDir {
+    pub path: PathBuf,
+    /// The desktop-specific lists first, then `mimeapps.list`
+    pub lists: Vec<MimeAppsList>,
+    /// `mimeinfo.cache`, only in the `applications` data directories
+    pub cache: HashMap<String, Vec<String>>,
+}
+
+impl MimeAppsDir {
+    fn load(path: PathBuf, desktops: &[String], with_cache: bool) -> Self {
+        let read = |file_name: &str| std::fs::read_to_string(path.join(file_name)).ok();
+        let lists = desktops
+            .iter()
+            .map(|desktop| format!("{desktop}-mimeapps.list"))
+            .chain(std::iter::once("mimeapps.list".to_string()))
+            .filter_map(|file_name| read(&file_name))
+            .map(|content| parse_mimeapps_list(&content))
+            .collect();
+        let cache = with_cache
+            .then(|| read("mimeinfo.cache"))
+            .flatten()
+            .map(|content| parse_mimeinfo_cache(&content))
+            .unwrap_or_default();
+        Self { path, lists, cache }
+    }
+}
+
+/// The MIME type associations of the current user
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MimeAssociations {
+    /// Directories in order of precedence
+    pub dirs: Vec<MimeAppsDir>,
+    /// Installed .desktop files by desktop file ID, only installed apps are returned
+    pub desktop_files: HashMap<String, PathBuf>,
+}
+
+/// Lowercased names of `$XDG_CURRENT_DESKTOP`, e.g. `ubuntu:GNOME` gives `ubuntu` and `gnome`
+pub fn current_desktops<F>(lookup: F) -> Vec<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    lookup("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|desktop| !desktop.is_empty())
+        .map(|desktop| desktop.to_lowercase())
+        .collect()
+}
+
+/// Find the .desktop files in `applications_dirs` by desktop file ID, the first directory wins.
+/// Entries with `Hidden=true` count as deleted.
+pub fn installed_desktop_files(applications_dirs: &[PathBuf]) -> HashMap<String, PathBuf> {
+    let mut desktop_files = HashMap::new();
+    let mut hidden = HashSet::new();
+    for dir in applications_dirs {
+        for entry in WalkDir::new(dir)
+            .follow_links(true)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+        {
+            let path = entry.path();
+            if path.extension() != Some(std::ffi::OsStr::new("desktop")) {
+                continue;
+            }
+            let id = desktop_file_id(dir, path);
+            if desktop_files.contains_key(&id) || hidden.contains(&id) {
+                continue;
+            }
+            if is_hidden(path) {
+                hidden.insert(id);
+            } else {
+                desktop_files.insert(id, path.to_path_buf());
+            }
+        }
+    }
+    desktop_files
+}
+
+fn is_hidden(path: &Path) -> bool {
+    std::fs::read_to_string(path).is_ok_and(|content| {
+        content
+            .lines()
+            .any(|line| line.replace(' ', "").eq_ignore_ascii_case("hidden=true"))
+    })
+}
+
+impl MimeAssociations {
+    /// Load the associations for the current process' environment
+    pub fn discover(dirs: &BaseDirectories) -> Self {
+        Self::load(dirs, &current_desktops(|key| std::env::var(key).ok()))
+    }
+
+    /// Load the associations of the directories in `dirs` for the given desktops, e.g. `["gnome"]`
+    pub fn load(dirs: &BaseDirectories, desktops: &[String]) -> Self {
+        let applications_dirs = dirs.data_subdirs("applications");
+        let config_dirs = dirs
+            .config_search_dirs()
+            .into_iter()
+            .map(|dir| MimeAppsDir::load(dir, desktops, false));
+        let data_dirs = applications_dirs
+            .iter()
+            .map(|dir| MimeAppsDir::load(dir.clone(), desktops, true));
+        Self {
+            dirs: config_dirs.chain(data_dirs).collect(),
+            desktop_files: installed_desktop_files(&applications_dirs),
+        }
+    }
+
+    fn is_installed(&self, id: &str) -> bool {
+        self.desktop_files.contains_key(id)
+    }
+
+    /// Desktop file ID of the default app for a MIME type: the first installed app of `[Default Applications]`
+    /// in order of precedence, else the most preferred associated app
+    pub fn default_app(&self, mime_type: &str) -> Option<String> {
+        let mime_type = mime_type.to_ascii_lowercase();
+        self.dirs
+            .iter()
+            .flat_map(|dir| &dir.lists)
+            .filter_map(|list| list.default_apps.get(&mime_type))
+            .flatten()
+            .find(|id| self.is_installed(id))
+            .cloned()
+            .or_else(|| self.associated_apps(&mime_type).into_iter().next())
+    }
+
+    /// Desktop file IDs of the installed apps associated with a MIME type, the most preferred first.
+    /// A directory's `[Removed Associations]` hide the associations of its `mimeinfo.cache` and of the directories after it.
+    pub fn associated_apps(&self, mime_type: &str) -> Vec<String> {
+        let mime_type = mime_type.to_ascii_lowercase();
+        let mut removed: HashSet<&String> = HashSet::new();
+        let mut apps: Vec<String> = vec![];
+        let mut add = |ids: Option<&Vec<String>>, removed: &HashSet<&String>| {
+            for id in ids.into_iter().flatten() {
+                if !removed.contains(id) && self.is_installed(id) && !apps.contains(id) {
+                    apps.push(id.clone());
+                }
+            }
+        };
+        for dir in &self.dirs {
+            for list in &dir.lists {
+                add(list.added.get(&mime_type), &removed);
+            }
+            for list in &dir.lists {
+                removed.extend(list.removed.get(&mime_type).into_iter().flatten());
+            }
+            add(dir.cache.get(&mime_type), &removed);
+        }
+        apps
+    }
+
+    /// Desktop file IDs of the apps that can open any of `mime_types`, e.g. a type followed by its parents.
+    /// The default app of the first type that has one comes first, then the associated apps of each type in order.
+    pub fn apps_for_mime_types(&self, mime_types: &[String]) -> Vec<String> {
+        let mut apps: Vec<String> = mime_types
+            .iter()
+            .find_map(|mime_type| self.default_app(mime_type))
+            .into_iter()
+            .collect();
+        for id in mime_types
+            .iter()
+            .flat_map(|mime_type| self.associated_apps(mime_type))
+        {
+            if !apps.contains(&id) {
+                apps.push(id);
+            }
+        }
+        apps
+    }
+
+    /// Path of an installed .desktop file
+    pub fn desktop_file(&self, id: &str) -> Option<&Path> {
+        self.desktop_files.get(id).map(PathBuf::as_path)
+    }
+
+    /// Desktop file ID of an installed .desktop file, from its path
+    pub fn desktop_file_id(&self, path: &Path) -> Option<String> {
+        let canonical = path.canonicalize().ok();
+        self.desktop_files
+            .iter()
+            .find(|(_, installed)| {
+                installed.as_path() == path || installed.canonicalize().ok() == canonical
+            })
+            .map(|(id, _)| id.clone())
+    }
+}
+
+/// Set `key` in `group` of a `mimeapps.list` file, keeping the other lines as they are.
+/// A missing key is added at the end of its group, a missing group at the end of the file.
+pub fn set_list_entry(content: &str, group: &str, key: &str, value: &str) -> String {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let entry = format!("{key}={value}");
+    let header = format!("[{group}]");
+    match lines.iter().position(|line| line.trim() == header) {
+        Some(start) => {
+            let end = lines[start + 1..]
+                .iter()
+                .position(|line| line.trim_start().starts_with('['))
+                .map_or(lines.len(), |position| start + 1 + position);
+            let existing = (start + 1..end).find(|&i| {
+                lines[i]
+                    .split_once('=')
+                    .is_some_and(|(other, _)| other.trim().eq_ignore_ascii_case(key))
+            });
+            match existing {
+                Some(i) => lines[i] = entry,
+                None => {
+                    let last = (start + 1..end)
+                        .rev()
+                        .find(|&i| !lines[i].trim().is_empty())
+                        .unwrap_or(start);
+                    lines.insert(last + 1, entry);
+                }
+            }
+        }
+        None => {
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(header);
+            lines.push(entry);
+        }
+    }
+    let mut content = lines.join("\n");
+    content.push('\n');
+    content
+}
+
+/// Replace a file with new content by writing a temporary file next to it and renaming it, following symlinks
+fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let (Some(dir), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return Err(anyhow!("Invalid path {:?}", path));
+    };
+    std::fs::create_dir_all(dir)?;
+    let tmp = dir.join(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    std::fs::write(&tmp, content)?;
+    if let Err(e) = std::fs::rename(&tmp, &path) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Make an installed app the default for a MIME type in the user's `$XDG_CONFIG_HOME/mimeapps.list`,
+/// then read the associations back to check it's the default now
+pub fn set_default_app(
+    dirs: &BaseDirectories,
+    desktops: &[String],
+    mime_type: &str,
+    desktop_file: &Path,
+) -> Result<()> {
+    let id = MimeAssociations::load(dirs, desktops)
+        .desktop_file_id(desktop_file)
+        .ok_or_else(|| anyhow!("{:?} is not an installed .desktop file", desktop_file))?;
+    let path = dirs
+        .config_home
+        .as_ref()
+        .ok_or_else(|| anyhow!("Neither $XDG_CONFIG_HOME nor $HOME is set"))?
+        .join("mimeapps.list");
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let mime_type = mime_type.to_ascii_lowercase();
+    let content = set_list_entry(
+        &content,
+        "Default Applications",
+        &mime_type,
+        &format!("{id};"),
+    );
+    write_atomic(&path, &content)?;
+
+    let default = MimeAssociations::load(dirs, desktops).default_app(&mime_type);
+    if default.as_deref() != Some(id.as_str()) {
+        return Err(anyhow!(
+            "{} was written to {:?} but the default for {} is {:?}, a desktop-specific mimeapps.list probably overrides it",
+            id,
+            path,
+            mime_type,
+            default
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn install(applications: &Path, id: &str) {
+        write(
+            &applications.join(id),
+            &format!("[Desktop Entry]\nType=Application\nName={id}\nExec={id} %f\n"),
+        );
+    }
+
+    #[test]
+    fn test_parse_mimeapps_list() {
+        let list = parse_mimeapps_list(
+            "# comment\n[Default Applications]\ntext/plain=org.gnome.TextEditor.desktop;code.desktop;\n\n[Added Associations]\nimage/PNG=gimp.desktop;\n[Removed Associations]\ntext/plain=vim.desktop\n",
+        );
+        assert_eq!(
+            list.default_apps["text/plain"],
+            vec!["org.gnome.TextEditor.desktop", "code.desktop"]
+        );
+        assert_eq!(list.added["image/png"], vec!["gimp.desktop"]);
+        assert_eq!(list.removed["text/plain"], vec!["vim.desktop"]);
+        assert_eq!(
+            parse_mimeinfo_cache("[MIME Cache]\napplication/pdf=org.gnome.Evince.desktop;\n")
+                ["application/pdf"],
+            vec!["org.gnome.Evince.desktop"]
+        );
+    }
+
+    #[test]
+    fn test_current_desktops() {
+        let desktops = current_desktops(|_| Some("ubuntu:GNOME".to_string()));
+        assert_eq!(desktops, vec!["ubuntu", "gnome"]);
+        assert!(current_desktops(|_| None).is_empty());
+    }
+
+    #[test]
+    fn test_mime_associations() {
+        let tmp = tempfile::tempdir().unwrap();
+        let home = tmp.path().join("home");
+        let system = tmp.path().join("usr/share");
+        let user_apps = home.join(".local/share/applications");
+        let system_apps = system.join("applications");
+        for id in [
+            "org.gnome.TextEditor.desktop",
+            "vim.desktop",
+            "gimp.desktop",
+        ] {
+            install(&system_apps, id);
+        }
+        install(&system_apps.join("kde4"), "kate.desktop");
+        install(&user_apps, "code.desktop");
+        write(
+            &user_apps.join("hidden.desktop"),
+            "[Desktop Entry]\nHidden=true\n",
+        );
+        install(&system_apps, "hidden.desktop");
+
+        write(
+            &system_apps.join("mimeinfo.cache"),
+            "[MIME Cache]\ntext/plain=vim.desktop;org.gnome.TextEditor.desktop;kde4-kate.desktop;hidden.desktop;\nimage/png=gimp.desktop;\n",
+        );
+        write(
+            &system_apps.join("mimeapps.list"),
+            "[Default Applications]\ntext/plain=org.gnome.TextEditor.desktop\n",
+        );
+        write(
+            &tmp.path().join("etc/xdg/gnome-mimeapps.list"),
+            "[Default Applications]\ntext/plain=missing.desktop;kde4-kate.desktop\n",
+        );
+        write(
+            &home.join(".config/mimeapps.list"),
+            "[Added Associations]\ntext/plain=code.desktop;missing.desktop\n[Removed Associations]\ntext/plain=vim.desktop\n",
+        );
+
+        let etc_xdg = tmp.path().join("etc/xdg");
+        let dirs = BaseDirectories::from_env(|key| match key {
+            "HOME" => home.to_str().map(str::to_string),
+            "XDG_CONFIG_DIRS" => etc_xdg.to_str().map(str::to_string),
+            "XDG_DATA_DIRS" => system.to_str().map(str::to_string),
+            _ => None,
+        });
+        let gnome = MimeAssociations::load(&dirs, &["gnome".to_string()]);
+        assert_eq!(
+            gnome.associated_apps("text/plain"),
+            vec![
+                "code.desktop",
+                "org.gnome.TextEditor.desktop",
+                "kde4-kate.desktop"
+            ]
+        );
+        // the desktop-specific default is skipped when not installed, the next one wins
+        assert_eq!(
+            gnome.default_app("text/plain").as_deref(),
+            Some("kde4-kate.desktop")
+        );
+        assert_eq!(
+            gnome.desktop_file("kde4-kate.desktop"),
+            Some(system_apps.join("kde4/kate.desktop").as_path())
+        );
+        // without a default, the most preferred association wins
+        assert_eq!(
+            gnome.default_app("IMAGE/PNG").as_deref(),
+            Some("gimp.desktop")
+        );
+        assert_eq!(gnome.default_app("application/pdf"), None);
+
+        let kde = MimeAssociations::load(&dirs, &["kde".to_string()]);
+        assert_eq!(
+            kde.default_app("text/plain").as_deref(),
+            Some("org.gnome.TextEditor.desktop")
+        );
+    }
+
+    #[test]
+    fn test_set_list_entry() {
+        let content = "# my associations\n[Added Associations]\ntext/plain=code.desktop;\n\n[Default Applications]\napplication/pdf=evince.desktop;\nText/Plain = vim.desktop;\n\n[Removed Associations]\nimage/png=gimp.desktop;\n";
+        assert_eq!(
+            set_list_entry(content, "Default Applications", "text/plain", "code.desktop;"),
+            "# my associations\n[Added Associations]\ntext/plain=code.desktop;\n\n[Default Applications]\napplication/pdf=evince.desktop;\ntext/plain=code.desktop;\n\n[Removed Associations]\nimage/png=gimp.desktop;\n"
+        );
+        assert_eq!(
+            set_list_entry(content, "Default Applications", "image/png", "gimp.desktop;"),
+            "# my associations\n[Added Associations]\ntext/plain=code.desktop;\n\n[Default Applications]\napplication/pdf=evince.desktop;\nText/Plain = vim.desktop;\nimage/png=gimp.desktop;\n\n[Removed Associations]\nimage/png=gimp.desktop;\n"
+        );
+        assert_eq!(
+            set_list_entry("[Added Associations]\ntext/plain=code.desktop;", "Default Applications", "text/plain", "code.desktop;"),
+            "[Added Associations]\ntext/plain=code.desktop;\n\n[Default Applications]\ntext/plain=code.desktop;\n"
+        );
+        assert_eq!(
+            set_list_entry("", "Default Applications", "text/plain", "code.desktop;"),
+            "[Default Applications]\ntext/plain=code.desktop;\n"
+        );
+    }
+
+    #[test]
+    fn test_set_default_app() {
+        let tmp = tempfile::tempdir().unwrap();
+        let home = tmp.path().join("home");
+        let system = tmp.path().join("usr/share");
+        let system_apps = system.join("applications");
+        for id in [
+            "org.gnome.Evince.desktop",
+            "okular.desktop",
+            "firefox.desktop",
+        ] {
+            install(&system_apps, id);
+        }
+        write(
+            &system_apps.join("mimeapps.list"),
+            "[Default Applications]\napplication/pdf=org.gnome.Evince.desktop\n",
+        );
+        // the user's list is managed in a dotfiles repository
+        let dotfiles = tmp.path().join("dotfiles/mimeapps.list");
+        write(
+            &dotfiles,
+            "[Added Associations]\napplication/pdf=okular.desktop;\n",
+        );
+        fs::create_dir_all(home.join(".config")).unwrap();
+        std::os::unix::fs::symlink(&dotfiles, home.join(".config/mimeapps.list")).unwrap();
+
+        let system_dir = system.to_str().unwrap().to_string();
+        let home_dir = home.to_str().unwrap().to_string();
+        let dirs = BaseDirectories::from_env(|key| match key {
+            "HOME" => Some(home_dir.clone()),
+            "XDG_DATA_DIRS" => Some(system_dir.clone()),
+            _ => None,
+        });
+        set_default_app(
+            &dirs,
+            &[],
+            "application/PDF",
+            &system_apps.join("okular.desktop"),
+        )
+        .unwrap();
+        set_default_app(
+            &dirs,
+            &[],
+            "x-scheme-handler/https",
+            &system_apps.join("firefox.desktop"),
+        )
+        .unwrap();
+        let associations = MimeAssociations::load(&dirs, &[]);
+        assert_eq!(
+            associations.default_app("application/pdf").as_deref(),
+            Some("okular.desktop")
+        );
+        assert_eq!(
+            associations
+                .default_app("x-scheme-handler/https")
+                .as_deref(),
+            Some("firefox.desktop")
+        );
+        assert!(home.join(".config/mimeapps.list").is_symlink());
+        assert_eq!(
+            fs::read_to_string(&dotfiles).unwrap(),
+            "[Added Associations]\napplication/pdf=okular.desktop;\n\n[Default Applications]\napplication/pdf=okular.desktop;\nx-scheme-handler/https=firefox.desktop;\n"
+        );
+
+        assert!(set_default_app(
+            &dirs,
+            &[],
+            "text/plain",
+            &tmp.path().join("missing.desktop")
+        )
+        .is_err());
+        // a desktop-specific list of the user takes precedence, the read-back check catches it
+        write(
+            &home.join(".config/gnome-mimeapps.list"),
+            "[Default Applications]\napplication/pdf=org.gnome.Evince.desktop\n",
+        );
+        let gnome = ["gnome".to_string()];
+        assert!(set_default_app(
+            &dirs,
+            &gnome,
+            "application/pdf",
+            &system_apps.join("okular.desktop")
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! Nix profile discovery
+//!
+//! Nix profiles are symlink farms into the Nix store. A profile's `share/applications` directory, or each
+//! .desktop file in it, is a symlink to the package that provides it:
+//!
+//! ```text
+//! ~/.nix-profile -> /nix/var/nix/profiles/per-user/me/profile -> profile-42-link -> /nix/store/<hash>-user-environment
+//! /nix/store/<hash>-user-environment/share/applications/firefox.desktop
+//!   -> /nix/store/<hash>-firefox-128.0.3/share/applications/firefox.desktop
+//! ```
+use crate::common::{App, AppSource, NixInfo};
+use crate::utils::xdg::BaseDirectories;
+use std::path::{Component, Path, PathBuf};
+
+pub const NIX_STORE: &str = "/nix/store";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NixLayout {
+    /// `$NIX_STORE_DIR`, usually `/nix/store`
+    pub store_dir: PathBuf,
+    /// Profiles in order of precedence, the user's own profiles first
+    pub profiles: Vec<PathBuf>,
+}
+
+impl NixLayout {
+    /// Profiles of the current user and of the system, for the current process' environment
+    pub fn discover(dirs: &BaseDirectories) -> Self {
+        Self::from_env(dirs, |key| std::env::var(key).ok())
+    }
+
+    /// The profiles Nix, Home Manager and NixOS install to:
+    /// `~/.nix-profile`, `$XDG_STATE_HOME/nix/profile` (with `use-xdg-base-directories`),
+    /// `/etc/profiles/per-user/$USER`, `/nix/var/nix/profiles/default` and `/run/current-system/sw`
+    pub fn from_env<F>(dirs: &BaseDirectories, lookup: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let var = |key: &str| lookup(key).filter(|value| !value.is_empty());
+        let mut profiles: Vec<PathBuf> = vec![];
+        profiles.extend(dirs.home.iter().map(|home| home.join(".nix-profile")));
+        profiles.extend(
+            dirs.state_home
+                .iter()
+                .map(|state| state.join("nix/profile")),
+        );
+        profiles.extend(
+            var("USER")
+                .into_iter()
+                .map(|user| Path::new("/etc/profiles/per-user").join(user)),
+        );
+        profiles.push(PathBuf::from("/nix/var/nix/profiles/default"));
+        profiles.push(PathBuf::from("/run/current-system/sw"));
+        Self {
+            store_dir: var("NIX_STORE_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from(NIX_STORE)),
+            profiles,
+        }
+    }
+
+    /// `share/applications` of each profile, in order of precedence
+    pub fn applications_dirs(&self) -> Vec<PathBuf> {
+        self.profiles
+            .iter()
+            .map(|profile| profile.join("share/applications"))
+            .collect()
+    }
+
+    /// `share/icons` of each profile, in order of precedence
+    pub fn icons_dirs(&self) -> Vec<PathBuf> {
+        self.profiles
+            .iter()
+            .map(|profile| profile.join("share/icons"))
+            .collect()
+    }
+
+    /// Check if a path is inside one of the profiles, without resolving symlinks
+    pub fn is_in_profile(&self, path: &Path) -> bool {
+        self.profiles
+            .iter()
+            .any(|profile| path.starts_with(profile))
+    }
+
+    /// Resolve all symlinks of a path and return the store path it ends up in, e.g. `/nix/store/<hash>-firefox-128.0.3`
+    pub fn store_path(&self, path: &Path) -> Option<PathBuf> {
+        let resolved = path.canonicalize().ok()?;
+        let store_dir = self
+            .store_dir
+            .canonicalize()
+            .unwrap_or_else(|_| self.store_dir.clone());
+        match resolved
+            .strip_prefix(&store_dir)
+            .ok()?
+            .components()
+            .next()?
+        {
+            Component::Normal(name) => Some(store_dir.join(name)),
+            _ => None,
+        }
+    }
+
+    /// Find the package a .desktop file comes from, if it resolves into the Nix store
+    pub fn nix_info_for_desktop_file(&self, desktop_file: &Path) -> Option<NixInfo> {
+        let store_path = self.store_path(desktop_file)?;
+        let (_, name) = parse_store_path_name(store_path.file_name()?.to_str()?)?;
+        let profile = self
+            .profiles
+            .iter()
+            .find(|profile| desktop_file.starts_with(profile))
+            .cloned();
+        Some(NixInfo {
+            name: name.to_string(),
+            store_path,
+            profile,
+        })
+    }
+}
+
+/// Split the base name of a store path into its hash and derivation name,
+/// e.g. `0c0ldqmz...-firefox-128.0.3` into `0c0ldqmz...` and `firefox-128.0.3`
+pub fn parse_store_path_name(base_name: &str) -> Option<(&str, &str)> {
+    let (hash, name) = base_name.split_once('-')?;
+    // store path hashes are 32 characters of Nix's base32 alphabet
+    let is_hash = hash.len() == 32
+        && hash
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit());
+    (is_hash && !name.is_empty()).then_some((hash, name))
+}
+
+/// Tag an app as a Nix app if its .desktop file resolves into the Nix store
+pub fn attribute_app(app: &mut App, layout: &NixLayout) {
+    if let Some(info) = layout.nix_info_for_desktop_file(&app.app_desktop_path) {
+        app.source = AppSource::Nix(info);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    pub(crate) const FIXTURE_HASH: &str = "0c0ldqmz3b6i4fmp3yv1jd4a0g6pk9qx";
+
+    /// Create a store and a profile in `root`, with a package that provides a .desktop file and an icon,
+    /// both linked into the profile like `nix profile install` would
+    pub(crate) fn install_fixture_package(
+        root: &Path,
+        name: &str,
+        desktop_name: &str,
+    ) -> NixLayout {
+        let layout = NixLayout {
+            store_dir: root.join("nix/store"),
+            profiles: vec![root.join("home/.nix-profile")],
+        };
+        let package = layout.store_dir.join(format!("{FIXTURE_HASH}-{name}-1.0"));
+        let applications = package.join("share/applications");
+        let icons = package.join("share/icons/hicolor/48x48/apps");
+        fs::create_dir_all(&applications).unwrap();
+        fs::create_dir_all(&icons).unwrap();
+        fs::write(
+            applications.join(format!("{name}.desktop")),
+            format!("[Desktop Entry]\nType=Application\nName={desktop_name}\nExec={name} %U\nIcon={name}\n"),
+        )
+        .unwrap();
+        fs::write(icons.join(format!("{name}.png")), "").unwrap();
+
+        // the profile links files and whole directories into the package
+        let profile_share = layout.profiles[0].join("share");
+        fs::create_dir_all(profile_share.join("applications")).unwrap();
+        symlink(
+            applications.join(format!("{name}.desktop")),
+            profile_share.join(format!("applications/{name}.desktop")),
+        )
+        .unwrap();
+        fs::create_dir_all(profile_share.join("icons/hicolor")).unwrap();
+        symlink(
+            package.join("share/icons/hicolor/48x48"),
+            profile_share.join("icons/hicolor/48x48"),
+        )
+        .unwrap();
+        layout
+    }
+
+    #[test]
+    fn test_parse_store_path_name() {
+        assert_eq!(
+            parse_store_path_name(&format!("{FIXTURE_HASH}-firefox-128.0.3")),
+            Some((FIXTURE_HASH, "firefox-128.0.3"))
+        );
+        assert_eq!(parse_store_path_name("firefox-128.0.3"), None);
+        assert_eq!(parse_store_path_name(&format!("{FIXTURE_HASH}-")), None);
+    }
+
+    #[test]
+    fn test_from_env() {
+        let dirs = BaseDirectories::from_env(|key| match key {
+            "HOME" => Some("/home/me".to_string()),
+            _ => None,
+        });
+        let layout = NixLayout::from_env(&dirs, |key| match key {
+            "USER" => Some("me".to_string()),
+            _ => None,
+        });
+        assert_eq!(layout.store_dir, PathBuf::from(NIX_STORE));
+        assert_eq!(
+            layout.applications_dirs(),
+            vec![
+                PathBuf::from("/home/me/.nix-profile/share/applications"),
+                PathBuf::from("/home/me/.local/state/nix/profile/share/applications"),
+                PathBuf::from("/etc/profiles/per-user/me/share/applications"),
+                PathBuf::from("/nix/var/nix/profiles/default/share/applications"),
+                PathBuf::from("/run/current-system/sw/share/applications"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nix_info_for_desktop_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let layout = install_fixture_package(tmp.path(), "firefox", "Firefox");
+        let store_dir = layout.store_dir.canonicalize().unwrap();
+
+        let desktop_file = layout.applications_dirs()[0].join("firefox.desktop");
+        assert_eq!(
+            layout.nix_info_for_desktop_file(&desktop_file),
+            Some(NixInfo {
+                name: "firefox-1.0".to_string(),
+                store_path: store_dir.join(format!("{FIXTURE_HASH}-firefox-1.0")),
+                profile: Some(layout.profiles[0].clone()),
+            })
+        );
+
+        let native = tmp.path().join("usr/share/applications/native.desktop");
+        fs::create_dir_all(native.parent().unwrap()).unwrap();
+        fs::write(&native, "[Desktop Entry]\nName=Native\n").unwrap();
+        assert_eq!(layout.nix_info_for_desktop_file(&native), None);
+    }
+}
@@ -0,0 +1,325 @@
+//! Package ownership of installed files
+//!
+//! Reads the local databases of the system's package managers to find the package a file belongs to:
+//!
+//! - dpkg: `/var/lib/dpkg/info/<package>[:<arch>].list` lists the files, `/var/lib/dpkg/status` has the versions
+//! - pacman: `/var/lib/pacman/local/<package>-<version>/desc` and `files`
+//! - rpm: its database isn't a plain text format, `rpm -qa` lists the files instead when rpm is installed
+use crate::common::{PackageInfo, PackageManager};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageDatabase {
+    /// The system root the databases were read from, `/` except in tests
+    root: PathBuf,
+    owners: HashMap<PathBuf, PackageInfo>,
+}
+
+/// Only the files we could be asked about are indexed, the full lists are large
+fn is_indexed(path: &str) -> bool {
+    path.ends_with(".desktop")
+}
+
+/// Parse the stanzas of dpkg's `status` file into the installed version of each package
+pub fn parse_dpkg_status(content: &str) -> HashMap<String, String> {
+    let mut versions = HashMap::new();
+    for stanza in content.split("\n\n") {
+        let field = |name: &str| {
+            stanza
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+                .map(str::trim)
+        };
+        let installed = field("Status").is_some_and(|status| status.ends_with(" installed"));
+        if let (true, Some(package), Some(version)) =
+            (installed, field("Package"), field("Version"))
+        {
+            versions.insert(package.to_string(), version.to_string());
+        }
+    }
+    versions
+}
+
+/// Parse a pacman `desc` or `files` file into its `%SECTION%`s
+pub fn parse_pacman_sections(content: &str) -> HashMap<String, Vec<String>> {
+    let mut sections: HashMap<String, Vec<String>> = HashMap::new();
+    let mut current: Option<String> = None;
+    for line in content.lines() {
+        if let Some(name) = line
+            .strip_prefix('%')
+            .and_then(|line| line.strip_suffix('%'))
+        {
+            current = Some(name.to_string());
+            sections.entry(name.to_string()).or_default();
+        } else if line.is_empty() {
+            current = None;
+        } else if let Some(name) = &current {
+            sections
+                .entry(name.clone())
+                .or_default()
+                .push(line.to_string());
+        }
+    }
+    sections
+}
+
+/// `rpm -qa` query format listing every file of every package, one `<file>\t<name>\t<version>` line per file
+const RPM_FILES_QUERY_FORMAT: &str = "[%{FILENAMES}\t%{NAME}\t%{VERSION}-%{RELEASE}\n]";
+
+/// Parse the output of `rpm -qa` with [`RPM_FILES_QUERY_FORMAT`] into the owner of each indexed file
+pub fn parse_rpm_files(output: &str) -> Vec<(String, PackageInfo)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let (file, name, version) = (fields.next()?, fields.next()?, fields.next()?);
+            is_indexed(file).then(|| {
+                let info = PackageInfo {
+                    name: name.to_string(),
+                    version: Some(version.to_string()),
+                    manager: PackageManager::Rpm,
+                };
+                (file.to_string(), info)
+            })
+        })
+        .collect()
+}
+
+impl PackageDatabase {
+    /// Read the package databases of the running system
+    pub fn load() -> Self {
+        Self::load_from(Path::new("/"))
+    }
+
+    /// Read the package databases of the system installed at `root`
+    pub fn load_from(root: &Path) -> Self {
+        let mut db = PackageDatabase {
+            root: root.to_path_buf(),
+            owners: HashMap::new(),
+        };
+        db.load_dpkg();
+        db.load_pacman();
+        db.load_rpm();
+        db
+    }
+
+    /// Turn a path from a package database into a path on this system
+    fn system_path(&self, path: &str) -> PathBuf {
+        self.root.join(path.trim_start_matches('/'))
+    }
+
+    fn load_dpkg(&mut self) {
+        let dpkg = self.root.join("var/lib/dpkg");
+        let Ok(entries) = std::fs::read_dir(dpkg.join("info")) else {
+            return;
+        };
+        let versions = std::fs::read_to_string(dpkg.join("status"))
+            .map(|content| parse_dpkg_status(&content))
+            .unwrap_or_default();
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            let Some(package) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".list"))
+            else {
+                continue;
+            };
+            // multi-arch packages are listed as `<package>:<arch>.list`
+            let package = package.split(':').next().unwrap_or(package).to_string();
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            for file in content.lines().filter(|line| is_indexed(line)) {
+                let info = PackageInfo {
+                    name: package.clone(),
+                    version: versions.get(&package).cloned(),
+                    manager: PackageManager::Dpkg,
+                };
+                self.owners.insert(self.system_path(file), info);
+            }
+        }
+    }
+
+    fn load_pacman(&mut self) {
+        let Ok(entries) = std::fs::read_dir(self.root.join("var/lib/pacman/local")) else {
+            return;
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let dir = entry.path();
+            let read = |file: &str| {
+                std::fs::read_to_string(dir.join(file))
+                    .map(|content| parse_pacman_sections(&content))
+                    .unwrap_or_default()
+            };
+            let desc = read("desc");
+            let Some(name) = desc.get("NAME").and_then(|values| values.first()) else {
+                continue;
+            };
+            let version = desc.get("VERSION").and_then(|values| values.first());
+            let files = read("files");
+            for file in files
+                .get("FILES")
+                .into_iter()
+                .flatten()
+                .filter(|file| is_indexed(file))
+            {
+                let info = PackageInfo {
+                    name: name.clone(),
+                    version: version.cloned(),
+                    manager: PackageManager::Pacman,
+                };
+                self.owners.insert(self.system_path(file), info);
+            }
+        }
+    }
+
+    /// rpm is only queried for the running system, it can't be pointed at another root without privileges
+    fn load_rpm(&mut self) {
+        if self.root != Path::new("/") || !Path::new("/var/lib/rpm").is_dir() {
+            return;
+        }
+        let output = Command::new("rpm")
+            .arg("-qa")
+            .arg("--queryformat")
+            .arg(RPM_FILES_QUERY_FORMAT)
+            .output();
+        match output {
+            Ok(output) => {
+                for (file, info) in parse_rpm_files(&String::from_utf8_lossy(&output.stdout)) {
+                    self.owners.insert(self.system_path(&file), info);
+                }
+            }
+            Err(e) => log::debug!("Failed to query the rpm database: {}", e),
+        }
+    }
+
+    /// Find the package that installed a file, if any. Symlinks are resolved, e.g. for `/usr` merged systems.
+    pub fn owner(&self, path: &Path) -> Option<PackageInfo> {
+        self.owners.get(path).cloned().or_else(|| {
+            let resolved = path.canonicalize().ok()?;
+            self.owners.get(&resolved).cloned()
+        })
+    }
+
+    /// Find the owners of many files at once
+    pub fn owners(&self, paths: &[PathBuf]) -> HashMap<PathBuf, PackageInfo> {
+        paths
+            .iter()
+            .filter_map(|path| Some((path.clone(), self.owner(path)?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_dpkg_database() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        write(
+            &root.join("var/lib/dpkg/status"),
+            "Package: firefox-esr\nStatus: install ok installed\nArchitecture: amd64\nVersion: 115.12.0esr-1~deb12u1\n\nPackage: gimp\nStatus: deinstall ok config-files\nVersion: 2.10.34-1\n\nPackage: libreoffice-writer\nStatus: install ok installed\nVersion: 4:7.4.7-1\n",
+        );
+        write(
+            &root.join("var/lib/dpkg/info/firefox-esr.list"),
+            "/.\n/usr\n/usr/bin/firefox-esr\n/usr/share/applications/firefox-esr.desktop\n",
+        );
+        write(
+            &root.join("var/lib/dpkg/info/libreoffice-writer:amd64.list"),
+            "/usr/share/applications/libreoffice-writer.desktop\n",
+        );
+
+        let db = PackageDatabase::load_from(root);
+        assert_eq!(
+            db.owner(&root.join("usr/share/applications/firefox-esr.desktop")),
+            Some(PackageInfo {
+                name: "firefox-esr".to_string(),
+                version: Some("115.12.0esr-1~deb12u1".to_string()),
+                manager: PackageManager::Dpkg,
+            })
+        );
+        let writer = db
+            .owner(&root.join("usr/share/applications/libreoffice-writer.desktop"))
+            .unwrap();
+        assert_eq!(writer.name, "libreoffice-writer");
+        assert_eq!(writer.version.as_deref(), Some("4:7.4.7-1"));
+        // only .desktop files are indexed
+        assert_eq!(db.owner(&root.join("usr/bin/firefox-esr")), None);
+    }
+
+    #[test]
+    fn test_pacman_database() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let package = root.join("var/lib/pacman/local/code-1.91.1-1");
+        write(
+            &package.join("desc"),
+            "%NAME%\ncode\n\n%VERSION%\n1.91.1-1\n\n%DESC%\nThe Open Source build of Visual Studio Code\n",
+        );
+        write(
+            &package.join("files"),
+            "%FILES%\nusr/\nusr/bin/code\nusr/share/applications/code-oss.desktop\nusr/share/applications/code-oss-url-handler.desktop\n\n%BACKUP%\n",
+        );
+        fs::write(root.join("var/lib/pacman/local/ALPM_DB_VERSION"), "9\n").unwrap();
+
+        let db = PackageDatabase::load_from(root);
+        let desktop_file = root.join("usr/share/applications/code-oss.desktop");
+        assert_eq!(
+            db.owners(std::slice::from_ref(&desktop_file)),
+            HashMap::from([(
+                desktop_file,
+                PackageInfo {
+                    name: "code".to_string(),
+                    version: Some("1.91.1-1".to_string()),
+                    manager: PackageManager::Pacman,
+                }
+            )])
+        );
+    }
+
+    #[test]
+    fn test_owner_through_symlink() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        write(
+            &root.join("var/lib/dpkg/info/vim.list"),
+            "/usr/share/applications/vim.desktop\n",
+        );
+        write(&root.join("usr/share/applications/vim.desktop"), "");
+        std::os::unix::fs::symlink(root.join("usr/share"), root.join("share")).unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let db = PackageDatabase::load_from(&root);
+        let owner = db.owner(&root.join("share/applications/vim.desktop"));
+        assert_eq!(owner.map(|owner| owner.name), Some("vim".to_string()));
+    }
+
+    #[test]
+    fn test_parse_rpm_files() {
+        let owners = parse_rpm_files(
+            "/usr/bin/nautilus\tnautilus\t46.2-1.fc40\n/usr/share/applications/org.gnome.Nautilus.desktop\tnautilus\t46.2-1.fc40\n(contains no files)\tgpg-pubkey\t3c3359c4-5c6ae44d\n",
+        );
+        assert_eq!(
+            owners,
+            vec![(
+                "/usr/share/applications/org.gnome.Nautilus.desktop".to_string(),
+                PackageInfo {
+                    name: "nautilus".to_string(),
+                    version: Some("46.2-1.fc40".to_string()),
+                    manager: PackageManager::Rpm,
+                }
+            )]
+        );
+    }
+}
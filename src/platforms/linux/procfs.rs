@@ -0,0 +1,709 @@
+//! Running processes, read from `/proc`
+//!
+//! Each process tells which app it belongs to in a few ways, none of them always there:
+//!
+//! - `exe` and `cmdline`, the program it runs
+//! - `environ`, where GLib launchers leave the .desktop file they started in `GIO_LAUNCHED_DESKTOP_FILE` and the PID
+//!   they started in `GIO_LAUNCHED_DESKTOP_FILE_PID`. Children inherit both, so the file is only trusted for that PID
+//!   and its descendants.
+//! - `cgroup`, where desktops following <https://systemd.io/DESKTOP_ENVIRONMENTS/> put each app in a
+//!   `app-[<launcher>-]<desktop file ID>-<random>.scope` or `app-[<launcher>-]<desktop file ID>[@<random>].service` unit,
+//!   and snapd puts snap apps in `snap.<instance>.<app>-<uuid>.scope`
+//! - `root/.flatpak-info`, only in Flatpak sandboxes, with the app ID
+//!
+//! `stat` and `status` give the parent, start time, owner and memory of the process.
+use super::flatpak::read_metadata;
+use crate::utils::matcher::ProcessHints;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub const PROC: &str = "/proc";
+
+/// Where process information is read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcLayout {
+    pub proc_root: PathBuf,
+    /// The user database, to name the owners of processes
+    pub passwd: PathBuf,
+    /// Unit of the times in `/proc/<pid>/stat`, `sysconf(_SC_CLK_TCK)`
+    pub ticks_per_second: u64,
+}
+
+impl ProcLayout {
+    /// `/proc` and `/etc/passwd` of the running system
+    pub fn system() -> Self {
+        // SAFETY: sysconf has no preconditions
+        let ticks_per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+        ProcLayout {
+            proc_root: PathBuf::from(PROC),
+            passwd: PathBuf::from("/etc/passwd"),
+            ticks_per_second: u64::try_from(ticks_per_second).unwrap_or(100),
+        }
+    }
+
+    /// When the system booted, in seconds since the Unix epoch, from the `btime` line of `stat`
+    pub fn boot_time(&self) -> Option<u64> {
+        std::fs::read_to_string(self.proc_root.join("stat"))
+            .ok()?
+            .lines()
+            .find_map(|line| line.strip_prefix("btime "))?
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    /// User names by UID
+    pub fn user_names(&self) -> HashMap<u32, String> {
+        std::fs::read_to_string(&self.passwd)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split(':');
+                let name = fields.next()?;
+                let uid = fields.nth(1)?.parse().ok()?;
+                Some((uid, name.to_string()))
+            })
+            .collect()
+    }
+}
+
+/// What we know of a running process
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Process {
+    pub pid: u32,
+    /// Path of the running executable
+    pub exe: Option<PathBuf>,
+    pub cmdline: Vec<String>,
+    /// The .desktop file the process, or the ancestor in `desktop_file_pid`, was launched from
+    pub desktop_file: Option<PathBuf>,
+    /// The process that was launched from `desktop_file`, the process itself or one of its ancestors
+    pub desktop_file_pid: Option<u32>,
+    /// The .desktop file in `BAMF_DESKTOP_FILE_HINT`, set by Unity launchers and snap wrappers. It comes
+    /// without a PID, so it's no telling whether the process itself or an ancestor was launched from it.
+    pub hinted_desktop_file: Option<PathBuf>,
+    /// The systemd unit the process runs in, e.g. `app-gnome-firefox-4213.scope`
+    pub systemd_unit: Option<String>,
+    /// ID of the Flatpak app, if the process runs in a Flatpak sandbox
+    pub flatpak_app_id: Option<String>,
+    pub ppid: Option<u32>,
+    /// When the process started, in clock ticks since boot
+    pub start_ticks: Option<u64>,
+    /// Real user ID of the owner
+    pub uid: Option<u32>,
+    /// Resident memory, in bytes
+    pub rss_bytes: Option<u64>,
+}
+
+/// Split a NUL separated file like `cmdline` or `environ`
+fn read_nul_separated(path: &Path) -> Vec<String> {
+    std::fs::read(path)
+        .map(|content| {
+            content
+                .split(|byte| *byte == 0)
+                .filter(|field| !field.is_empty())
+                .map(|field| String::from_utf8_lossy(field).into_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The parent PID and start time of a `stat` file: `<pid> (<comm>) <state> <ppid> ...`, where
+/// the start time is the 22nd field. `comm` may contain spaces and parentheses, so fields are counted from the last `)`.
+fn parse_stat(stat: &str) -> Option<(u32, u64)> {
+    let mut fields = stat[stat.rfind(')')? + 1..].split_whitespace();
+    let ppid = fields.nth(1)?.parse().ok()?;
+    let start_ticks = fields.nth(17)?.parse().ok()?;
+    Some((ppid, start_ticks))
+}
+
+/// The real UID and resident memory in bytes of a `status` file
+fn parse_status(status: &str) -> (Option<u32>, Option<u64>) {
+    let value = |key: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(key))
+            .and_then(|value| value.split_whitespace().next())
+    };
+    let uid = value("Uid:").and_then(|uid| uid.parse().ok());
+    let rss_bytes = value("VmRSS:")
+        .and_then(|kib| kib.parse::<u64>().ok())
+        .map(|kib| kib * 1024);
+    (uid, rss_bytes)
+}
+
+/// A non-empty variable of an `environ`
+fn environ_var<'a>(environ: &'a [String], name: &str) -> Option<&'a str> {
+    environ.iter().find_map(|entry| {
+        entry
+            .strip_prefix(name)
+            .and_then(|entry| entry.strip_prefix('='))
+            .filter(|value| !value.is_empty())
+    })
+}
+
+/// `GIO_LAUNCHED_DESKTOP_FILE` and `GIO_LAUNCHED_DESKTOP_FILE_PID`, `None` unless both are set
+fn desktop_file_from_environ(environ: &[String]) -> Option<(PathBuf, u32)> {
+    let desktop_file = environ_var(environ, "GIO_LAUNCHED_DESKTOP_FILE")?;
+    let pid = environ_var(environ, "GIO_LAUNCHED_DESKTOP_FILE_PID")?
+        .parse()
+        .ok()?;
+    Some((PathBuf::from(desktop_file), pid))
+}
+
+/// `BAMF_DESKTOP_FILE_HINT`, or the `BAMF_DESKTOP_FILE_HINTS` spelling some launchers use
+fn bamf_desktop_file_from_environ(environ: &[String]) -> Option<PathBuf> {
+    environ_var(environ, "BAMF_DESKTOP_FILE_HINT")
+        .or_else(|| environ_var(environ, "BAMF_DESKTOP_FILE_HINTS"))
+        .map(PathBuf::from)
+}
+
+/// The parent PID and start time of a process, from its `stat`
+pub fn read_stat(proc_root: &Path, pid: u32) -> Option<(u32, u64)> {
+    let stat = std::fs::read_to_string(proc_root.join(pid.to_string()).join("stat")).ok()?;
+    parse_stat(&stat)
+}
+
+/// How many ancestors are walked up a process tree, in case it's cyclic, e.g. as PIDs are reused while it's read
+pub const MAX_ANCESTORS: usize = 64;
+
+/// The .desktop file and PID in the environment of a process, if that PID is the process itself, or an ancestor
+/// whose own environment names the same file and itself. Any other process only inherited the variables, e.g. an
+/// app started from a terminal that was launched from its .desktop file, or its launcher was reparented.
+fn launched_desktop_file(
+    proc_root: &Path,
+    pid: u32,
+    ppid: Option<u32>,
+    environ: &[String],
+) -> Option<(PathBuf, u32)> {
+    let (desktop_file, launched_pid) = desktop_file_from_environ(environ)?;
+    if launched_pid == pid {
+        return Some((desktop_file, launched_pid));
+    }
+    let mut ancestor = ppid;
+    for _ in 0..MAX_ANCESTORS {
+        let current = ancestor.filter(|ancestor| *ancestor > 1)?;
+        if current == launched_pid {
+            let environ = read_nul_separated(&proc_root.join(current.to_string()).join("environ"));
+            return (desktop_file_from_environ(&environ)
+                == Some((desktop_file.clone(), launched_pid)))
+            .then_some((desktop_file, launched_pid));
+        }
+        ancestor = read_stat(proc_root, current).map(|(ppid, _)| ppid);
+    }
+    None
+}
+
+pub fn is_app_unit(unit: &str) -> bool {
+    (unit.starts_with("app-") || unit.starts_with("snap."))
+        && (unit.ends_with(".scope") || unit.ends_with(".service"))
+}
+
+/// The path of a process' cgroup in the unified cgroup v2 hierarchy, e.g. `/user.slice/.../app-gnome-firefox-4213.scope`
+pub fn unified_cgroup(cgroup: &str) -> Option<&str> {
+    cgroup.lines().find_map(|line| line.strip_prefix("0::"))
+}
+
+/// The innermost app or snap unit in a `cgroup` file, units of the cgroup v2 hierarchy first
+fn systemd_unit_from_cgroup(cgroup: &str) -> Option<String> {
+    let mut lines: Vec<&str> = cgroup.lines().collect();
+    lines.sort_by_key(|line| !line.starts_with("0::"));
+    lines.iter().find_map(|line| {
+        let path = line.splitn(3, ':').nth(2)?;
+        path.rsplit('/')
+            .find(|unit| is_app_unit(unit))
+            .map(str::to_string)
+    })
+}
+
+/// Undo the `\xNN` escapes of systemd unit names
+fn unescape_unit_name(name: &str) -> String {
+    let mut bytes: Vec<u8> = Vec::with_capacity(name.len());
+    let mut rest = name.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = tail
+            .strip_prefix(b"x")
+            .and_then(|hex| hex.get(..2))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (byte, escaped) {
+            (b'\\', Some(unescaped)) => {
+                bytes.push(unescaped);
+                rest = &tail[3..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// The desktop file IDs, without `.desktop`, an app unit may stand for.
+/// The launcher part is optional, so `app-gnome-org.gnome.Terminal-8829.scope` gives
+/// `gnome-org.gnome.Terminal` and `org.gnome.Terminal`.
+pub fn app_ids_from_unit(unit: &str) -> Vec<String> {
+    let Some(name) = unit.strip_prefix("app-") else {
+        return vec![];
+    };
+    let name = if let Some(scope) = name.strip_suffix(".scope") {
+        scope.rsplit_once('-').map_or(scope, |(name, _random)| name)
+    } else if let Some(service) = name.strip_suffix(".service") {
+        service
+            .split_once('@')
+            .map_or(service, |(name, _random)| name)
+    } else {
+        return vec![];
+    };
+    let mut ids = vec![unescape_unit_name(name)];
+    if let Some((_launcher, id)) = name.split_once('-') {
+        ids.push(unescape_unit_name(id));
+    }
+    ids.retain(|id| !id.is_empty());
+    ids
+}
+
+/// The snap instance and app names of a snap unit. Scopes end with a UUID, after a `-`
+/// (or a `.` with older snapd), e.g. `snap.firefox.firefox-8a5b0b4e-1e5b-4c41-a1e7-37d5a2e6c9d1.scope`.
+pub fn snap_app_from_unit(unit: &str) -> Option<(String, String)> {
+    let name = unit.strip_prefix("snap.")?;
+    let name = match name.strip_suffix(".scope") {
+        Some(scope) => {
+            let uuid_start = scope.len().checked_sub(36)?;
+            let is_uuid = scope[uuid_start..]
+                .chars()
+                .all(|c| c.is_ascii_hexdigit() || c == '-');
+            let separator = scope[..uuid_start].chars().last();
+            match (is_uuid, separator) {
+                (true, Some('-' | '.')) => &scope[..uuid_start - 1],
+                _ => scope,
+            }
+        }
+        None => name.strip_suffix(".service")?,
+    };
+    let (instance_name, app) = name.split_once('.')?;
+    Some((instance_name.to_string(), app.to_string()))
+}
+
+impl Process {
+    /// Read a process from a proc file system mounted at `proc_root`, `None` if it's gone
+    pub fn read(proc_root: &Path, pid: u32) -> Option<Self> {
+        let dir = proc_root.join(pid.to_string());
+        if !dir.is_dir() {
+            return None;
+        }
+        let exe = std::fs::read_link(dir.join("exe")).ok().map(|exe| {
+            // the executable was replaced, e.g. by an update
+            match exe.to_str().and_then(|exe| exe.strip_suffix(" (deleted)")) {
+                Some(exe) => PathBuf::from(exe),
+                None => exe,
+            }
+        });
+        let systemd_unit = std::fs::read_to_string(dir.join("cgroup"))
+            .ok()
+            .and_then(|cgroup| systemd_unit_from_cgroup(&cgroup));
+        let flatpak_app_id =
+            read_metadata(&dir.join("root/.flatpak-info")).and_then(|metadata| metadata.name);
+        let stat = std::fs::read_to_string(dir.join("stat"))
+            .ok()
+            .and_then(|stat| parse_stat(&stat));
+        let (uid, rss_bytes) = std::fs::read_to_string(dir.join("status"))
+            .map(|status| parse_status(&status))
+            .unwrap_or_default();
+        let ppid = stat.map(|(ppid, _)| ppid);
+        let environ = read_nul_separated(&dir.join("environ"));
+        let launched = launched_desktop_file(proc_root, pid, ppid, &environ);
+        Some(Process {
+            pid,
+            exe,
+            cmdline: read_nul_separated(&dir.join("cmdline")),
+            desktop_file: launched
+                .as_ref()
+                .map(|(desktop_file, _)| desktop_file.clone()),
+            desktop_file_pid: launched.map(|(_, pid)| pid),
+            hinted_desktop_file: bamf_desktop_file_from_environ(&environ),
+            systemd_unit,
+            flatpak_app_id,
+            ppid,
+            start_ticks: stat.map(|(_, start_ticks)| start_ticks),
+            uid,
+            rss_bytes,
+        })
+    }
+
+    /// The desktop file IDs, without `.desktop`, of the app unit the process runs in
+    pub fn unit_app_ids(&self) -> Vec<String> {
+        self.systemd_unit
+            .as_deref()
+            .map(app_ids_from_unit)
+            .unwrap_or_default()
+    }
+
+    /// The snap instance and app names, if the process runs in a snap's unit
+    pub fn snap_app(&self) -> Option<(String, String)> {
+        self.systemd_unit.as_deref().and_then(snap_app_from_unit)
+    }
+
+    /// What the process tells of its app, for [`crate::utils::matcher`]
+    pub fn hints(&self) -> ProcessHints {
+        ProcessHints {
+            exe: self.exe.clone(),
+            argv0: self.cmdline.first().cloned(),
+            desktop_file: self
+                .desktop_file
+                .clone()
+                .filter(|_| self.desktop_file_pid == Some(self.pid)),
+            inherited_desktop_file: self
+                .desktop_file
+                .clone()
+                .filter(|_| self.desktop_file_pid != Some(self.pid))
+                .or_else(|| self.hinted_desktop_file.clone()),
+            desktop_file_ids: self.unit_app_ids(),
+            window_classes: vec![],
+            flatpak_app_id: self.flatpak_app_id.clone(),
+            snap_app: self.snap_app(),
+            bundle_id: None,
+        }
+    }
+
+    /// Kernel threads have neither an executable nor a command line
+    pub fn is_kernel_thread(&self) -> bool {
+        self.exe.is_none() && self.cmdline.is_empty()
+    }
+}
+
+/// All the processes in a proc file system, by PID. Processes of other users may miss
+/// the fields we aren't allowed to read.
+pub fn processes(proc_root: &Path) -> Vec<Process> {
+    let mut pids: Vec<u32> = match std::fs::read_dir(proc_root) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .collect(),
+        Err(e) => {
+            log::warn!("Failed to read {:?}: {}", proc_root, e);
+            vec![]
+        }
+    };
+    pids.sort_unstable();
+    pids.into_iter()
+        .filter_map(|pid| Process::read(proc_root, pid))
+        .filter(|process| !process.is_kernel_thread())
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::fs;
+
+    /// What a fixture process is made of
+    #[derive(Default)]
+    pub(crate) struct FakeProcess<'a> {
+        pub exe: Option<&'a str>,
+        pub cmdline: &'a [&'a str],
+        pub environ: &'a [&'a str],
+        pub cgroup: &'a str,
+        pub flatpak_app_id: Option<&'a str>,
+        pub ppid: u32,
+        pub start_ticks: u64,
+        pub uid: u32,
+        pub rss_kib: u64,
+    }
+
+    /// Write a process to a fake proc root
+    pub(crate) fn install_fixture_process(proc_root: &Path, pid: u32, process: FakeProcess) {
+        let dir = proc_root.join(pid.to_string());
+        fs::create_dir_all(dir.join("root")).unwrap();
+        if let Some(exe) = process.exe {
+            std::os::unix::fs::symlink(exe, dir.join("exe")).unwrap();
+        }
+        let nul_separated = |fields: &[&str]| {
+            fields
+                .iter()
+                .map(|field| format!("{field}\0"))
+                .collect::<String>()
+        };
+        fs::write(dir.join("cmdline"), nul_separated(process.cmdline)).unwrap();
+        fs::write(dir.join("environ"), nul_separated(process.environ)).unwrap();
+        fs::write(dir.join("cgroup"), process.cgroup).unwrap();
+        // 52 fields, the 4th is the parent and the 22nd the start time
+        let mut stat: Vec<String> = vec!["0".to_string(); 52];
+        stat[0] = pid.to_string();
+        stat[1] = "(Web Content)".to_string();
+        stat[2] = "S".to_string();
+        stat[3] = process.ppid.to_string();
+        stat[21] = process.start_ticks.to_string();
+        fs::write(dir.join("stat"), stat.join(" ")).unwrap();
+        fs::write(
+            dir.join("status"),
+            format!(
+                "Name:\tfixture\nPPid:\t{}\nUid:\t{uid}\t{uid}\t{uid}\t{uid}\nVmRSS:\t  {} kB\n",
+                process.ppid,
+                process.rss_kib,
+                uid = process.uid
+            ),
+        )
+        .unwrap();
+        if let Some(app_id) = process.flatpak_app_id {
+            fs::write(
+                dir.join("root/.flatpak-info"),
+                format!(
+                    "[Application]\nname={app_id}\nruntime=runtime/org.gnome.Platform/x86_64/46\n"
+                ),
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_inherited_desktop_file() {
+        let proc_root = tempfile::tempdir().unwrap();
+        let proc_root = proc_root.path();
+        let terminal_environ = [
+            "GIO_LAUNCHED_DESKTOP_FILE=/usr/share/applications/org.gnome.Terminal.desktop",
+            "GIO_LAUNCHED_DESKTOP_FILE_PID=500",
+        ];
+        let install = |pid: u32, ppid: u32, exe: &str, environ: &[&str]| {
+            install_fixture_process(
+                proc_root,
+                pid,
+                FakeProcess {
+                    exe: Some(exe),
+                    cmdline: &[exe],
+                    environ,
+                    ppid,
+                    ..Default::default()
+                },
+            )
+        };
+        // the terminal, the shell it runs and an app started from the shell inherit its environment
+        install(500, 1, "/usr/bin/gnome-terminal", &terminal_environ);
+        install(550, 500, "/usr/bin/bash", &terminal_environ);
+        install(600, 550, "/usr/lib/firefox/firefox", &terminal_environ);
+        // reparented after the terminal exited
+        install(700, 1, "/usr/lib/firefox/firefox", &terminal_environ);
+        // no PID to check against
+        install(
+            800,
+            1,
+            "/usr/bin/gedit",
+            &["GIO_LAUNCHED_DESKTOP_FILE=/usr/share/applications/org.gnome.gedit.desktop"],
+        );
+        // PID 900 is not an ancestor of 950, which still names it
+        install(
+            900,
+            1,
+            "/usr/bin/nautilus",
+            &[
+                "GIO_LAUNCHED_DESKTOP_FILE=/usr/share/applications/org.gnome.Nautilus.desktop",
+                "GIO_LAUNCHED_DESKTOP_FILE_PID=900",
+            ],
+        );
+        install(
+            950,
+            1,
+            "/usr/bin/eog",
+            &[
+                "GIO_LAUNCHED_DESKTOP_FILE=/usr/share/applications/org.gnome.Nautilus.desktop",
+                "GIO_LAUNCHED_DESKTOP_FILE_PID=900",
+            ],
+        );
+        // started by a snap wrapper, `env BAMF_DESKTOP_FILE_HINT=... firefox`
+        let snap_desktop_file = "/var/lib/snapd/desktop/applications/firefox_firefox.desktop";
+        install(
+            1000,
+            1,
+            "/snap/firefox/current/usr/lib/firefox/firefox",
+            &[&format!("BAMF_DESKTOP_FILE_HINT={snap_desktop_file}")],
+        );
+
+        let launched = |pid: u32| {
+            let process = Process::read(proc_root, pid).unwrap();
+            process.desktop_file.zip(process.desktop_file_pid)
+        };
+        let terminal = Some((
+            PathBuf::from("/usr/share/applications/org.gnome.Terminal.desktop"),
+            500,
+        ));
+        assert_eq!(launched(500), terminal);
+        assert_eq!(launched(550), terminal);
+        assert_eq!(launched(600), terminal);
+        assert_eq!(launched(700), None);
+        assert_eq!(launched(800), None);
+        assert!(launched(900).is_some());
+        assert_eq!(launched(950), None);
+
+        let hints = |pid: u32| Process::read(proc_root, pid).unwrap().hints();
+        assert_eq!(launched(1000), None);
+        assert_eq!(hints(1000).desktop_file, None);
+        assert_eq!(
+            hints(1000).inherited_desktop_file,
+            Some(PathBuf::from(snap_desktop_file))
+        );
+        assert_eq!(
+            hints(600).inherited_desktop_file,
+            terminal.map(|(path, _)| path)
+        );
+    }
+
+    #[test]
+    fn test_app_ids_from_unit() {
+        assert_eq!(
+            app_ids_from_unit("app-gnome-org.gnome.Terminal-8829.scope"),
+            vec!["gnome-org.gnome.Terminal", "org.gnome.Terminal"]
+        );
+        assert_eq!(
+            app_ids_from_unit("app-org.kde.dolphin@a5c3e0b4.service"),
+            vec!["org.kde.dolphin"]
+        );
+        assert_eq!(
+            app_ids_from_unit(r"app-gnome-google\x2dchrome-4410.scope"),
+            vec!["gnome-google-chrome", "google-chrome"]
+        );
+        assert!(app_ids_from_unit("session-2.scope").is_empty());
+    }
+
+    #[test]
+    fn test_snap_app_from_unit() {
+        let snap_app =
+            |instance_name: &str, app: &str| Some((instance_name.to_string(), app.to_string()));
+        assert_eq!(
+            snap_app_from_unit("snap.firefox.firefox-8a5b0b4e-1e5b-4c41-a1e7-37d5a2e6c9d1.scope"),
+            snap_app("firefox", "firefox")
+        );
+        assert_eq!(
+            snap_app_from_unit(
+                "snap.code_insiders.code-insiders.0b8e5f0c-3b3a-4d5e-9f6a-1c2d3e4f5a6b.scope"
+            ),
+            snap_app("code_insiders", "code-insiders")
+        );
+        assert_eq!(
+            snap_app_from_unit("snap.lxd.daemon.service"),
+            snap_app("lxd", "daemon")
+        );
+        assert_eq!(snap_app_from_unit("app-gnome-firefox-4213.scope"), None);
+    }
+
+    #[test]
+    fn test_parse_stat() {
+        assert_eq!(
+            parse_stat("4213 (Web Content (x) ) S 1650 4213 4213 0 -1 4194560 91 0 0 0 12 3 0 0 20 0 31 0 52000 2650587136 75264"),
+            Some((1650, 52_000))
+        );
+        assert_eq!(parse_stat("4213 (firefox) S"), None);
+    }
+
+    #[test]
+    fn test_proc_layout() {
+        let root = tempfile::tempdir().unwrap();
+        let layout = ProcLayout {
+            proc_root: root.path().join("proc"),
+            passwd: root.path().join("passwd"),
+            ticks_per_second: 100,
+        };
+        fs::create_dir_all(&layout.proc_root).unwrap();
+        fs::write(
+            layout.proc_root.join("stat"),
+            "cpu  2255 34 2290 22625563 6290 127 456\nbtime 1760000000\nprocesses 26442\n",
+        )
+        .unwrap();
+        fs::write(
+            &layout.passwd,
+            "root:x:0:0:root:/root:/bin/bash\nme:x:1000:1000:Me,,,:/home/me:/bin/zsh\n",
+        )
+        .unwrap();
+        assert_eq!(layout.boot_time(), Some(1_760_000_000));
+        assert_eq!(
+            layout.user_names(),
+            HashMap::from([(0, "root".to_string()), (1000, "me".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_processes() {
+        let proc_root = tempfile::tempdir().unwrap();
+        let proc_root = proc_root.path();
+        install_fixture_process(
+            proc_root,
+            4213,
+            FakeProcess {
+                exe: Some("/usr/lib/firefox/firefox (deleted)"),
+                cmdline: &["/usr/lib/firefox/firefox", "--new-window"],
+                environ: &[
+                    "HOME=/home/me",
+                    "GIO_LAUNCHED_DESKTOP_FILE=/usr/share/applications/firefox.desktop",
+                    "GIO_LAUNCHED_DESKTOP_FILE_PID=4213",
+                    "BAMF_DESKTOP_FILE_HINTS=/usr/share/applications/firefox.desktop",
+                ],
+                cgroup: "1:name=systemd:/user.slice\n0::/user.slice/user-1000.slice/user@1000.service/app.slice/app-gnome-firefox-4213.scope\n",
+                ppid: 1650,
+                start_ticks: 52_000,
+                uid: 1000,
+                rss_kib: 301_056,
+                ..Default::default()
+            },
+        );
+        install_fixture_process(
+            proc_root,
+            77,
+            FakeProcess {
+                exe: Some("/app/bin/gnome-calculator"),
+                cmdline: &["gnome-calculator"],
+                environ: &[
+                    "GIO_LAUNCHED_DESKTOP_FILE=/var/lib/flatpak/exports/share/applications/org.gnome.Calculator.desktop",
+                    "GIO_LAUNCHED_DESKTOP_FILE_PID=77",
+                ],
+                cgroup: "0::/user.slice/user-1000.slice/user@1000.service/app.slice/app-flatpak-org.gnome.Calculator-77.scope\n",
+                flatpak_app_id: Some("org.gnome.Calculator"),
+                ..Default::default()
+            },
+        );
+        // a kernel thread
+        install_fixture_process(proc_root, 2, FakeProcess::default());
+        fs::create_dir_all(proc_root.join("self")).unwrap();
+
+        let processes = processes(proc_root);
+        assert_eq!(
+            processes
+                .iter()
+                .map(|process| process.pid)
+                .collect::<Vec<_>>(),
+            vec![77, 4213]
+        );
+        assert_eq!(
+            processes[0].desktop_file,
+            Some(PathBuf::from(
+                "/var/lib/flatpak/exports/share/applications/org.gnome.Calculator.desktop"
+            ))
+        );
+        assert_eq!(
+            processes[0].flatpak_app_id.as_deref(),
+            Some("org.gnome.Calculator")
+        );
+        assert_eq!(
+            processes[1],
+            Process {
+                pid: 4213,
+                exe: Some(PathBuf::from("/usr/lib/firefox/firefox")),
+                cmdline: vec![
+                    "/usr/lib/firefox/firefox".to_string(),
+                    "--new-window".to_string()
+                ],
+                desktop_file: Some(PathBuf::from("/usr/share/applications/firefox.desktop")),
+                desktop_file_pid: Some(4213),
+                hinted_desktop_file: Some(PathBuf::from("/usr/share/applications/firefox.desktop")),
+                systemd_unit: Some("app-gnome-firefox-4213.scope".to_string()),
+                flatpak_app_id: None,
+                ppid: Some(1650),
+                start_ticks: Some(52_000),
+                uid: Some(1000),
+                rss_bytes: Some(301_056 * 1024),
+            }
+        );
+        assert_eq!(
+            processes[1].unit_app_ids(),
+            vec!["gnome-firefox", "firefox"]
+        );
+        assert!(Process::read(proc_root, 5).is_none());
+    }
+}
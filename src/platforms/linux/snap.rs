@@ -0,0 +1,270 @@
+//! Snap app discovery
+//!
+//! snapd writes a .desktop file for each app of an installed snap to `/var/lib/snapd/desktop/applications`,
+//! named `<snap>_<app>.desktop`, e.g. `firefox_firefox.desktop`. The snap itself is mounted at
+//! `/snap/<snap>/<revision>` (`/var/lib/snapd/snap` on some distros), with `current` pointing to the active revision.
+use crate::common::{App, AppSource, SnapInfo};
+use std::path::{Path, PathBuf};
+
+/// Snap exports its .desktop files here, and adds it to `$XDG_DATA_DIRS` only in login shells
+pub const SNAPD_DESKTOP_APPLICATIONS: &str = "/var/lib/snapd/desktop/applications";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapLayout {
+    /// Where snapd writes the .desktop files of installed snaps
+    pub desktop_dir: PathBuf,
+    /// Where snaps are mounted, the first existing one is used
+    pub mount_dirs: Vec<PathBuf>,
+}
+
+impl Default for SnapLayout {
+    fn default() -> Self {
+        Self {
+            desktop_dir: PathBuf::from(SNAPD_DESKTOP_APPLICATIONS),
+            mount_dirs: vec![PathBuf::from("/snap"), PathBuf::from("/var/lib/snapd/snap")],
+        }
+    }
+}
+
+/// The fields we use from `meta/snap.yaml`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapYaml {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub confinement: Option<String>,
+    pub summary: Option<String>,
+}
+
+/// Read the top level scalar fields of a snap.yaml, that's all we need and saves us from pulling a YAML parser
+pub fn parse_snap_yaml(content: &str) -> SnapYaml {
+    let mut snap_yaml = SnapYaml::default();
+    for line in content.lines() {
+        // nested keys are indented, and the values of block scalars too
+        if line.starts_with(char::is_whitespace) || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+            .unwrap_or(value);
+        if value.is_empty() {
+            continue;
+        }
+        let value = Some(value.to_string());
+        match key.trim() {
+            "name" => snap_yaml.name = value,
+            "version" => snap_yaml.version = value,
+            "confinement" => snap_yaml.confinement = value,
+            "summary" => snap_yaml.summary = value,
+            _ => {}
+        }
+    }
+    snap_yaml
+}
+
+/// Split a snapd .desktop file name into the snap instance name and app name.
+/// Parallel installs of a snap are named `<snap>_<key>`, snapd writes them as `<snap>+<key>` in file names.
+pub fn parse_desktop_file_name(file_name: &str) -> Option<(String, String)> {
+    let stem = file_name.strip_suffix(".desktop")?;
+    let (instance, app) = stem.split_once('_')?;
+    if instance.is_empty() || app.is_empty() {
+        return None;
+    }
+    Some((instance.replace('+', "_"), app.to_string()))
+}
+
+impl SnapLayout {
+    /// Read the metadata of an installed snap from its current revision
+    pub fn snap_info(&self, instance_name: &str, app_name: &str) -> Option<SnapInfo> {
+        let (mount_dir, current) = self.mount_dirs.iter().find_map(|dir| {
+            let current = dir.join(instance_name).join("current");
+            current.exists().then_some((dir, current))
+        })?;
+        let revision = std::fs::read_link(&current)
+            .ok()?
+            .file_name()?
+            .to_str()?
+            .to_string();
+        let snap_yaml = std::fs::read_to_string(current.join("meta/snap.yaml"))
+            .map(|content| parse_snap_yaml(&content))
+            .unwrap_or_default();
+        // `snap run` commands are named after the snap, or `<snap>.<app>` for the other apps of a snap
+        let command = if instance_name == app_name {
+            instance_name.to_string()
+        } else {
+            format!("{}.{}", instance_name, app_name)
+        };
+        Some(SnapInfo {
+            name: snap_yaml.name.unwrap_or_else(|| instance_name.to_string()),
+            instance_name: instance_name.to_string(),
+            app: app_name.to_string(),
+            revision,
+            version: snap_yaml.version,
+            confinement: snap_yaml.confinement,
+            command_path: mount_dir.join("bin").join(command),
+        })
+    }
+
+    /// Find the snap a .desktop file belongs to, if snapd wrote it
+    pub fn snap_info_for_desktop_file(&self, desktop_file: &Path) -> Option<SnapInfo> {
+        if desktop_file.parent()? != self.desktop_dir {
+            return None;
+        }
+        // recent snapd versions write the names in the .desktop file, older ones only in the file name
+        let instance = super::brute_force_find_entry(desktop_file, vec!["X-SnapInstanceName"])
+            .ok()
+            .flatten();
+        let app = super::brute_force_find_entry(desktop_file, vec!["X-SnapAppName"])
+            .ok()
+            .flatten();
+        let (instance, app) = match (instance, app) {
+            (Some(instance), Some(app)) => (instance, app),
+            _ => parse_desktop_file_name(desktop_file.file_name()?.to_str()?)?,
+        };
+        self.snap_info(&instance, &app)
+    }
+}
+
+/// Tag an app as a Snap app if its .desktop file was written by snapd.
+/// Its executable is replaced with the snap's command in `/snap/bin`, instead of the `env BAMF_DESKTOP_FILE_HINT=...` wrapper.
+pub fn attribute_app(app: &mut App, layout: &SnapLayout) {
+    if let Some(info) = layout.snap_info_for_desktop_file(&app.app_desktop_path) {
+        app.app_path_exe = Some(info.command_path.clone());
+        app.source = AppSource::Snap(info);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Create a fake snap installation like snapd would, with a single app named after the snap
+    pub(crate) fn install_fixture_snap(
+        layout: &SnapLayout,
+        name: &str,
+        revision: &str,
+        desktop_name: &str,
+    ) {
+        let mount_dir = &layout.mount_dirs[0];
+        let meta = mount_dir.join(name).join(revision).join("meta");
+        fs::create_dir_all(&meta).unwrap();
+        fs::write(
+            meta.join("snap.yaml"),
+            format!("name: {name}\nversion: '128.0.3-1'\nsummary: Mozilla Firefox web browser\ndescription: |\n  name: not-the-name\nconfinement: strict\ngrade: stable\nbase: core22\napps:\n  {name}:\n    command: {name}.launcher\n    plugs: [desktop, network]\n"),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(revision, mount_dir.join(name).join("current")).unwrap();
+        fs::create_dir_all(&layout.desktop_dir).unwrap();
+        fs::write(
+            layout.desktop_dir.join(format!("{name}_{name}.desktop")),
+            format!("[Desktop Entry]\nType=Application\nName={desktop_name}\nExec=env BAMF_DESKTOP_FILE_HINT=/var/lib/snapd/desktop/applications/{name}_{name}.desktop /snap/bin/{name} %u\n"),
+        )
+        .unwrap();
+    }
+
+    pub(crate) fn fixture_layout(root: &Path) -> SnapLayout {
+        SnapLayout {
+            desktop_dir: root.join("var/lib/snapd/desktop/applications"),
+            mount_dirs: vec![root.join("snap")],
+        }
+    }
+
+    #[test]
+    fn test_parse_snap_yaml() {
+        let snap_yaml = parse_snap_yaml(
+            "name: firefox\nversion: \"128.0\"\ndescription: |\n  version: 1\nconfinement: classic\napps:\n  firefox:\n    command: firefox\n",
+        );
+        assert_eq!(
+            snap_yaml,
+            SnapYaml {
+                name: Some("firefox".to_string()),
+                version: Some("128.0".to_string()),
+                confinement: Some("classic".to_string()),
+                summary: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_desktop_file_name() {
+        assert_eq!(
+            parse_desktop_file_name("firefox_firefox.desktop"),
+            Some(("firefox".to_string(), "firefox".to_string()))
+        );
+        assert_eq!(
+            parse_desktop_file_name("gnome-system-monitor_gnome-system-monitor.desktop"),
+            Some((
+                "gnome-system-monitor".to_string(),
+                "gnome-system-monitor".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_desktop_file_name("firefox+work_firefox.desktop"),
+            Some(("firefox_work".to_string(), "firefox".to_string()))
+        );
+        assert_eq!(parse_desktop_file_name("firefox.desktop"), None);
+    }
+
+    #[test]
+    fn test_snap_info_for_desktop_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let layout = fixture_layout(tmp.path());
+        install_fixture_snap(&layout, "firefox", "4173", "Firefox");
+
+        let desktop_file = layout.desktop_dir.join("firefox_firefox.desktop");
+        let info = layout.snap_info_for_desktop_file(&desktop_file).unwrap();
+        assert_eq!(
+            info,
+            SnapInfo {
+                name: "firefox".to_string(),
+                instance_name: "firefox".to_string(),
+                app: "firefox".to_string(),
+                revision: "4173".to_string(),
+                version: Some("128.0.3-1".to_string()),
+                confinement: Some("strict".to_string()),
+                command_path: tmp.path().join("snap/bin/firefox"),
+            }
+        );
+
+        // not in the snapd desktop dir
+        assert!(layout
+            .snap_info_for_desktop_file(Path::new(
+                "/usr/share/applications/firefox_firefox.desktop"
+            ))
+            .is_none());
+        // the snap isn't mounted
+        fs::write(
+            layout.desktop_dir.join("gone_gone.desktop"),
+            "[Desktop Entry]\nName=Gone\n",
+        )
+        .unwrap();
+        assert!(layout
+            .snap_info_for_desktop_file(&layout.desktop_dir.join("gone_gone.desktop"))
+            .is_none());
+    }
+
+    #[test]
+    fn test_snap_names_from_desktop_entry() {
+        let tmp = tempfile::tempdir().unwrap();
+        let layout = fixture_layout(tmp.path());
+        install_fixture_snap(&layout, "code", "170", "Visual Studio Code");
+        let desktop_file = layout.desktop_dir.join("code_code-url-handler.desktop");
+        fs::write(
+            &desktop_file,
+            "[Desktop Entry]\nName=Code URL Handler\nX-SnapInstanceName=code\nX-SnapAppName=url-handler\n",
+        )
+        .unwrap();
+        let info = layout.snap_info_for_desktop_file(&desktop_file).unwrap();
+        assert_eq!(info.app, "url-handler");
+        assert_eq!(
+            info.command_path,
+            tmp.path().join("snap/bin/code.url-handler")
+        );
+    }
+}
@@ -0,0 +1,371 @@
+//! Terminating the processes of a running app
+//!
+//! Graceful termination sends `SIGTERM` to the main process first, so it can shut its children down itself,
+//! then to the others. Processes still alive after the timeout get `SIGKILL`.
+//!
+//! The PIDs come from an earlier scan of `/proc` and may have been reused since, so a process is only signaled while
+//! its start time is still the one of the scan. Children the app started after the scan are found in the systemd
+//! `app-*` or `snap.*` unit of its main process. Apps that don't run in one don't get their new children signaled.
+use super::procfs::{is_app_unit, read_stat, unified_cgroup, MAX_ANCESTORS, PROC};
+use crate::common::{ProcessTermination, TerminationOutcome};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Where the unified cgroup v2 hierarchy is mounted
+pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Where processes and their cgroups are read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerminateLayout {
+    pub proc_root: PathBuf,
+    pub cgroup_root: PathBuf,
+}
+
+impl TerminateLayout {
+    /// `/proc` and `/sys/fs/cgroup` of the running system
+    pub fn system() -> Self {
+        TerminateLayout {
+            proc_root: PathBuf::from(PROC),
+            cgroup_root: PathBuf::from(CGROUP_ROOT),
+        }
+    }
+}
+
+/// Send a signal, `Err` with the `errno` if it couldn't be sent
+fn send_signal(pid: u32, signal: libc::c_int) -> Result<(), std::io::Error> {
+    let pid =
+        libc::pid_t::try_from(pid).map_err(|_| std::io::Error::from_raw_os_error(libc::ESRCH))?;
+    // SAFETY: kill has no memory safety preconditions
+    match unsafe { libc::kill(pid, signal) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+/// Whether `pid` still is the process that started at `start_ticks`, if that's known
+fn is_same_process(proc_root: &Path, pid: u32, start_ticks: Option<u64>) -> bool {
+    start_ticks.is_none_or(|expected| {
+        read_stat(proc_root, pid).is_some_and(|(_, start_ticks)| start_ticks == expected)
+    })
+}
+
+/// Send a signal to a process, `ESRCH` if its PID now belongs to another one
+fn signal_process(
+    proc_root: &Path,
+    pid: u32,
+    start_ticks: Option<u64>,
+    signal: libc::c_int,
+) -> Result<(), std::io::Error> {
+    if !is_same_process(proc_root, pid, start_ticks) {
+        return Err(std::io::Error::from_raw_os_error(libc::ESRCH));
+    }
+    send_signal(pid, signal)
+}
+
+/// Zombies have exited already, they're only waiting for their parent to reap them
+fn is_alive(proc_root: &Path, pid: u32, start_ticks: Option<u64>) -> bool {
+    if send_signal(pid, 0).is_err_and(|e| e.raw_os_error() == Some(libc::ESRCH)) {
+        return false;
+    }
+    let stat = std::fs::read_to_string(proc_root.join(pid.to_string()).join("stat"));
+    let running = match stat {
+        Ok(stat) => stat
+            .rfind(')')
+            .and_then(|end| stat[end + 1..].split_whitespace().next())
+            .is_none_or(|state| state != "Z"),
+        Err(_) => false,
+    };
+    running && is_same_process(proc_root, pid, start_ticks)
+}
+
+/// What happened after a signal couldn't be sent
+fn failed_signal(pid: u32, e: std::io::Error) -> TerminationOutcome {
+    if e.raw_os_error() == Some(libc::ESRCH) {
+        TerminationOutcome::NotRunning
+    } else {
+        log::debug!("Failed to signal process {}: {}", pid, e);
+        TerminationOutcome::Failed(e.to_string())
+    }
+}
+
+/// Whether a process descends from one of `ancestors`
+fn descends_from(proc_root: &Path, pid: u32, ancestors: &HashSet<u32>) -> bool {
+    let mut current = pid;
+    for _ in 0..MAX_ANCESTORS {
+        let Some((ppid, _)) = read_stat(proc_root, current) else {
+            return false;
+        };
+        if ancestors.contains(&ppid) {
+            return true;
+        }
+        if ppid <= 1 {
+            return false;
+        }
+        current = ppid;
+    }
+    false
+}
+
+/// The processes of the systemd app scope of `main_pid` that descend from `pids` without being among them,
+/// i.e. started after `pids` were listed, with their start times. The scope's other processes are left alone.
+fn new_scope_processes(layout: &TerminateLayout, pids: &[u32], main_pid: u32) -> Vec<(u32, u64)> {
+    let Ok(cgroup) =
+        std::fs::read_to_string(layout.proc_root.join(main_pid.to_string()).join("cgroup"))
+    else {
+        return vec![];
+    };
+    let Some(path) =
+        unified_cgroup(&cgroup).filter(|path| path.rsplit('/').next().is_some_and(is_app_unit))
+    else {
+        return vec![];
+    };
+    let procs = std::fs::read_to_string(
+        layout
+            .cgroup_root
+            .join(path.trim_start_matches('/'))
+            .join("cgroup.procs"),
+    )
+    .unwrap_or_default();
+    let known: HashSet<u32> = pids.iter().copied().collect();
+    procs
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .filter(|pid| !known.contains(pid))
+        .filter(|pid| descends_from(&layout.proc_root, *pid, &known))
+        .filter_map(|pid| Some((pid, read_stat(&layout.proc_root, pid)?.1)))
+        .collect()
+}
+
+/// Terminate `pids`, `main_pid` first. Unless `graceful`, they're killed right away.
+/// `start_ticks` are the start times of `pids` when they were listed.
+pub fn terminate(
+    layout: &TerminateLayout,
+    pids: &[u32],
+    start_ticks: &HashMap<u32, u64>,
+    main_pid: Option<u32>,
+    graceful: bool,
+    timeout: Duration,
+) -> Vec<ProcessTermination> {
+    let proc_root = layout.proc_root.as_path();
+    let main_pid = main_pid.filter(|pid| pids.contains(pid));
+    let mut order: Vec<(u32, Option<u64>)> = main_pid
+        .into_iter()
+        .chain(pids.iter().copied().filter(|pid| Some(*pid) != main_pid))
+        .map(|pid| (pid, start_ticks.get(&pid).copied()))
+        .collect();
+    // the scope of a main process whose PID was reused belongs to another app
+    if let Some(main_pid) = main_pid.filter(|main_pid| {
+        is_same_process(proc_root, *main_pid, start_ticks.get(main_pid).copied())
+    }) {
+        order.extend(
+            new_scope_processes(layout, pids, main_pid)
+                .into_iter()
+                .map(|(pid, start_ticks)| (pid, Some(start_ticks))),
+        );
+    }
+
+    let mut outcomes: Vec<(u32, Option<u64>, Option<TerminationOutcome>)> = vec![];
+    for (pid, start_ticks) in order {
+        let signal = if graceful {
+            libc::SIGTERM
+        } else {
+            libc::SIGKILL
+        };
+        let outcome = signal_process(proc_root, pid, start_ticks, signal)
+            .err()
+            .map(|e| failed_signal(pid, e));
+        outcomes.push((pid, start_ticks, outcome));
+    }
+
+    let deadline = Instant::now() + if graceful { timeout } else { Duration::ZERO };
+    loop {
+        for (pid, start_ticks, outcome) in outcomes
+            .iter_mut()
+            .filter(|(_, _, outcome)| outcome.is_none())
+        {
+            if !is_alive(proc_root, *pid, *start_ticks) {
+                *outcome = Some(if graceful {
+                    TerminationOutcome::Exited
+                } else {
+                    TerminationOutcome::Killed
+                });
+            }
+        }
+        if outcomes.iter().all(|(_, _, outcome)| outcome.is_some()) || Instant::now() >= deadline {
+            break;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    // escalate to SIGKILL, it can't be caught, so the process is gone once the kernel delivers it
+    for (pid, start_ticks, outcome) in outcomes
+        .iter_mut()
+        .filter(|(_, _, outcome)| outcome.is_none())
+    {
+        *outcome = Some(
+            match signal_process(proc_root, *pid, *start_ticks, libc::SIGKILL) {
+                Ok(()) => TerminationOutcome::Killed,
+                Err(e) => failed_signal(*pid, e),
+            },
+        );
+    }
+    outcomes
+        .into_iter()
+        .map(|(pid, _, outcome)| ProcessTermination {
+            pid,
+            outcome: outcome.unwrap_or(TerminationOutcome::Killed),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platforms::linux::procfs::tests::{install_fixture_process, FakeProcess};
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+
+    /// Start a shell script and wait until it prints that it's ready, e.g. once its traps are installed
+    fn spawn(script: &str) -> Child {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(script)
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stdout.as_mut().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        assert_eq!(line, "ready\n");
+        child
+    }
+
+    fn start_ticks(child: &Child) -> u64 {
+        read_stat(Path::new(PROC), child.id()).unwrap().1
+    }
+
+    #[test]
+    fn test_terminate() {
+        // no scope to look into for new children, the tests may run in the scope of a terminal
+        let cgroup_root = tempfile::tempdir().unwrap();
+        let layout = TerminateLayout {
+            proc_root: PathBuf::from(PROC),
+            cgroup_root: cgroup_root.path().to_path_buf(),
+        };
+        let mut polite = spawn("echo ready; exec sleep 30");
+        let mut stubborn = spawn("trap '' TERM; echo ready; while true; do sleep 0.05; done");
+        let mut reused = spawn("echo ready; exec sleep 30");
+        let gone = u32::MAX;
+        let pids = [polite.id(), stubborn.id(), reused.id(), gone];
+        let listed = HashMap::from([
+            (polite.id(), start_ticks(&polite)),
+            (stubborn.id(), start_ticks(&stubborn)),
+            // as if the PID belonged to another process when the app was listed
+            (reused.id(), start_ticks(&reused) + 1),
+        ]);
+
+        let outcomes = terminate(
+            &layout,
+            &pids,
+            &listed,
+            Some(stubborn.id()),
+            true,
+            Duration::from_millis(300),
+        );
+        assert_eq!(
+            outcomes,
+            vec![
+                ProcessTermination {
+                    pid: stubborn.id(),
+                    outcome: TerminationOutcome::Killed
+                },
+                ProcessTermination {
+                    pid: polite.id(),
+                    outcome: TerminationOutcome::Exited
+                },
+                ProcessTermination {
+                    pid: reused.id(),
+                    outcome: TerminationOutcome::NotRunning
+                },
+                ProcessTermination {
+                    pid: gone,
+                    outcome: TerminationOutcome::NotRunning
+                },
+            ]
+        );
+        assert!(polite.wait().is_ok());
+        assert!(stubborn.wait().is_ok());
+        assert!(reused.try_wait().unwrap().is_none());
+        reused.kill().unwrap();
+        assert!(reused.wait().is_ok());
+
+        let mut child = spawn("echo ready; exec sleep 30");
+        let outcomes = terminate(
+            &layout,
+            &[child.id()],
+            &HashMap::new(),
+            None,
+            false,
+            Duration::ZERO,
+        );
+        assert_eq!(outcomes[0].outcome, TerminationOutcome::Killed);
+        assert!(child.wait().is_ok());
+    }
+
+    #[test]
+    fn test_new_scope_processes() {
+        let root = tempfile::tempdir().unwrap();
+        let layout = TerminateLayout {
+            proc_root: root.path().join("proc"),
+            cgroup_root: root.path().join("cgroup"),
+        };
+        let scope =
+            "user.slice/user-1000.slice/user@1000.service/app.slice/app-gnome-firefox-100.scope";
+        let cgroup = format!("0::/{scope}\n");
+        for (pid, ppid, start_ticks) in [
+            (100, 1, 5_000),
+            (101, 100, 5_100),
+            (102, 101, 9_000),
+            (103, 1, 9_100),
+        ] {
+            install_fixture_process(
+                &layout.proc_root,
+                pid,
+                FakeProcess {
+                    exe: Some("/usr/lib/firefox/firefox"),
+                    cgroup: &cgroup,
+                    ppid,
+                    start_ticks,
+                    ..Default::default()
+                },
+            );
+        }
+        install_fixture_process(
+            &layout.proc_root,
+            200,
+            FakeProcess {
+                exe: Some("/usr/bin/xterm"),
+                cgroup: "0::/user.slice/user-1000.slice/session-2.scope\n",
+                ppid: 1,
+                ..Default::default()
+            },
+        );
+        std::fs::create_dir_all(layout.cgroup_root.join(scope)).unwrap();
+        std::fs::write(
+            layout.cgroup_root.join(scope).join("cgroup.procs"),
+            "100\n101\n102\n103\n",
+        )
+        .unwrap();
+
+        // 102 was started by the app after it was listed, 103 only shares its scope
+        assert_eq!(
+            new_scope_processes(&layout, &[100, 101], 100),
+            vec![(102, 9_000)]
+        );
+        assert!(new_scope_processes(&layout, &[200], 200).is_empty());
+    }
+}
@@ -0,0 +1,728 @@
+//! Toplevel windows of a Wayland session, listed by the compositor
+//!
+//! - `zwlr_foreign_toplevel_manager_v1` (Sway, Hyprland, labwc, Wayfire, ...) lists toplevels with their app ID,
+//!   title and states, `activated` for the focused one
+//! - `ext_foreign_toplevel_list_v1` lists toplevels with their app ID and title only, it doesn't tell which is focused
+//!
+//! Only the wlr protocol can activate a toplevel, for a `wl_seat`.
+//!
+//! By the xdg-shell spec, the app ID is the app's desktop file ID, without `.desktop`.
+use crate::common::NotSupported;
+use crate::utils::matcher::ProcessHints;
+use anyhow::{anyhow, Result};
+use std::sync::Mutex;
+use wayland_client::backend::ObjectId;
+use wayland_client::backend::WaylandError;
+use wayland_client::globals::{registry_queue_init, GlobalError, GlobalListContents};
+use wayland_client::protocol::wl_registry::WlRegistry;
+use wayland_client::protocol::wl_seat::{self, WlSeat};
+use wayland_client::{
+    event_created_child, Connection, Dispatch, DispatchError, EventQueue, Proxy, QueueHandle,
+};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::{
+    ext_foreign_toplevel_handle_v1::{self, ExtForeignToplevelHandleV1},
+    ext_foreign_toplevel_list_v1::{self, ExtForeignToplevelListV1},
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+
+/// A toplevel window as the compositor lists it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Toplevel {
+    /// Number of the toplevel, in the order the compositor announced it
+    pub id: u64,
+    pub app_id: Option<String>,
+    pub title: Option<String>,
+    /// Whether it has the focus, `None` if the protocol doesn't tell
+    pub activated: Option<bool>,
+}
+
+impl Toplevel {
+    /// The app ID names the app's desktop file, and is its window class under Xwayland
+    pub fn hints(&self) -> ProcessHints {
+        let Some(app_id) = self.app_id.as_deref().filter(|app_id| !app_id.is_empty()) else {
+            return ProcessHints::default();
+        };
+        let mut desktop_file_ids = vec![];
+        for id in [
+            app_id,
+            app_id.strip_suffix(".desktop").unwrap_or(app_id),
+            &app_id.to_lowercase(),
+        ] {
+            if !desktop_file_ids.iter().any(|known| known == id) {
+                desktop_file_ids.push(id.to_string());
+            }
+        }
+        ProcessHints {
+            desktop_file_ids,
+            window_classes: vec![app_id.to_string()],
+            ..Default::default()
+        }
+    }
+}
+
+enum Handle {
+    Wlr(ZwlrForeignToplevelHandleV1),
+    Ext(ExtForeignToplevelHandleV1),
+}
+
+impl Handle {
+    fn id(&self) -> ObjectId {
+        match self {
+            Handle::Wlr(handle) => handle.id(),
+            Handle::Ext(handle) => handle.id(),
+        }
+    }
+
+    fn destroy(&self) {
+        match self {
+            Handle::Wlr(handle) => handle.destroy(),
+            Handle::Ext(handle) => handle.destroy(),
+        }
+    }
+}
+
+/// Toplevels announced so far, in the order the compositor announced them
+#[derive(Default)]
+struct State {
+    toplevels: Vec<(Handle, Toplevel)>,
+    announced: u64,
+}
+
+impl State {
+    fn announced(&mut self, handle: Handle, toplevel: Toplevel) {
+        self.announced += 1;
+        let toplevel = Toplevel {
+            id: self.announced,
+            ..toplevel
+        };
+        self.toplevels.push((handle, toplevel));
+    }
+
+    fn toplevel(&mut self, id: ObjectId) -> Option<&mut Toplevel> {
+        self.toplevels
+            .iter_mut()
+            .find(|(handle, _)| handle.id() == id)
+            .map(|(_, toplevel)| toplevel)
+    }
+
+    fn closed(&mut self, id: ObjectId) {
+        self.toplevels.retain(|(handle, _)| {
+            let closed = handle.id() == id;
+            if closed {
+                handle.destroy();
+            }
+            !closed
+        });
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _state: &mut Self,
+        _registry: &WlRegistry,
+        _event: <WlRegistry as Proxy>::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlSeat, ()> for State {
+    fn event(
+        _state: &mut Self,
+        _seat: &WlSeat,
+        _event: wl_seat::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _manager: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+            let announced = Toplevel {
+                activated: Some(false),
+                ..Default::default()
+            };
+            state.announced(Handle::Wlr(toplevel), announced);
+        }
+    }
+
+    event_created_child!(State, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_handle_v1::Event::Closed = event {
+            return state.closed(handle.id());
+        }
+        let Some(toplevel) = state.toplevel(handle.id()) else {
+            return;
+        };
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => {
+                toplevel.app_id = Some(app_id)
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Title { title } => toplevel.title = Some(title),
+            zwlr_foreign_toplevel_handle_v1::Event::State { state } => {
+                // an array of native endian u32 states
+                let activated = zwlr_foreign_toplevel_handle_v1::State::Activated as u32;
+                toplevel.activated = Some(
+                    state
+                        .chunks_exact(4)
+                        .any(|value| value == activated.to_ne_bytes()),
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ExtForeignToplevelListV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _list: &ExtForeignToplevelListV1,
+        event: ext_foreign_toplevel_list_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let ext_foreign_toplevel_list_v1::Event::Toplevel { toplevel } = event {
+            state.announced(Handle::Ext(toplevel), Toplevel::default());
+        }
+    }
+
+    event_created_child!(State, ExtForeignToplevelListV1, [
+        ext_foreign_toplevel_list_v1::EVT_TOPLEVEL_OPCODE => (ExtForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ExtForeignToplevelHandleV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        handle: &ExtForeignToplevelHandleV1,
+        event: ext_foreign_toplevel_handle_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let ext_foreign_toplevel_handle_v1::Event::Closed = event {
+            return state.closed(handle.id());
+        }
+        let Some(toplevel) = state.toplevel(handle.id()) else {
+            return;
+        };
+        match event {
+            ext_foreign_toplevel_handle_v1::Event::AppId { app_id } => {
+                toplevel.app_id = Some(app_id)
+            }
+            ext_foreign_toplevel_handle_v1::Event::Title { title } => toplevel.title = Some(title),
+            _ => {}
+        }
+    }
+}
+
+/// Whether an error comes from the connection to the compositor, rather than from a request
+pub fn is_connection_error(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<WaylandError>().is_some()
+        || e.downcast_ref::<DispatchError>().is_some()
+        || e.downcast_ref::<GlobalError>().is_some()
+}
+
+/// The protocol the toplevels are listed with
+enum Protocol {
+    Wlr(ZwlrForeignToplevelManagerV1),
+    Ext(ExtForeignToplevelListV1),
+}
+
+/// A toplevel listing kept up to date by the compositor's events, so toplevels keep their number and handle
+/// from one call to the next
+struct Listing {
+    queue: EventQueue<State>,
+    state: State,
+    protocol: Protocol,
+    seat: Option<WlSeat>,
+}
+
+impl Listing {
+    /// Bind wlr-foreign-toplevel-management if the compositor supports it, else ext-foreign-toplevel-list
+    fn bind(conn: &Connection) -> Result<Self> {
+        let (globals, mut queue) = registry_queue_init::<State>(conn)?;
+        let qh = queue.handle();
+        let mut state = State::default();
+
+        let protocol = match globals.bind::<ZwlrForeignToplevelManagerV1, _, _>(&qh, 1..=3, ()) {
+            Ok(manager) => Protocol::Wlr(manager),
+            Err(_) => Protocol::Ext(
+                globals
+                    .bind::<ExtForeignToplevelListV1, _, _>(&qh, 1..=1, ())
+                    .map_err(|_| {
+                        NotSupported(
+                            "The Wayland compositor supports neither wlr-foreign-toplevel-management nor ext-foreign-toplevel-list"
+                                .to_string(),
+                        )
+                    })?,
+            ),
+        };
+        let seat = globals.bind::<WlSeat, _, _>(&qh, 1..=1, ()).ok();
+        // the compositor announces all its toplevels right after the bind
+        queue.roundtrip(&mut state)?;
+        Ok(Listing {
+            queue,
+            state,
+            protocol,
+            seat,
+        })
+    }
+
+    /// Handle the events the compositor sent since the last call
+    fn update(&mut self) -> Result<()> {
+        self.queue.roundtrip(&mut self.state)?;
+        Ok(())
+    }
+}
+
+impl Drop for Listing {
+    fn drop(&mut self) {
+        match &self.protocol {
+            Protocol::Wlr(manager) => manager.stop(),
+            Protocol::Ext(list) => {
+                list.stop();
+                list.destroy();
+            }
+        }
+        for (handle, _) in &self.state.toplevels {
+            handle.destroy();
+        }
+        let _ = self.queue.flush();
+    }
+}
+
+/// A connection to the compositor of `$WAYLAND_DISPLAY`
+pub struct Wayland {
+    conn: Connection,
+    listing: Mutex<Option<Listing>>,
+}
+
+impl Wayland {
+    /// Connect to `$WAYLAND_DISPLAY`, [`NotSupported`] if the session has no Wayland compositor
+    pub fn connect() -> Result<Self> {
+        let conn = Connection::connect_to_env().map_err(|e| {
+            NotSupported(format!(
+                "Failed to connect to the Wayland compositor: {}",
+                e
+            ))
+        })?;
+        Ok(Wayland::new(conn))
+    }
+
+    fn new(conn: Connection) -> Self {
+        Wayland {
+            conn,
+            listing: Mutex::new(None),
+        }
+    }
+
+    /// Run `f` on the up to date listing, bound on first use
+    fn with_listing<R>(&self, f: impl FnOnce(&mut Listing) -> Result<R>) -> Result<R> {
+        let mut listing = self.listing.lock().unwrap();
+        match listing.as_mut() {
+            Some(listing) => listing.update()?,
+            None => *listing = Some(Listing::bind(&self.conn)?),
+        }
+        f(listing.as_mut().unwrap())
+    }
+
+    /// The toplevels of the compositor, over wlr-foreign-toplevel-management if it supports it,
+    /// else ext-foreign-toplevel-list. A toplevel keeps its number for as long as the connection.
+    pub fn toplevels(&self) -> Result<Vec<Toplevel>> {
+        self.with_listing(|listing| {
+            Ok(listing
+                .state
+                .toplevels
+                .iter()
+                .map(|(_, toplevel)| toplevel.clone())
+                .collect())
+        })
+    }
+
+    /// Focus the toplevel numbered `id` by [`Wayland::toplevels`], [`NotSupported`] without
+    /// wlr-foreign-toplevel-management or a seat
+    pub fn activate(&self, id: u64) -> Result<()> {
+        self.with_listing(|listing| {
+            let seat = match (&listing.protocol, &listing.seat) {
+                (Protocol::Wlr(_), Some(seat)) => seat.clone(),
+                (Protocol::Wlr(_), None) => {
+                    return Err(NotSupported("The Wayland compositor has no seat".to_string()).into())
+                }
+                (Protocol::Ext(_), _) => {
+                    return Err(NotSupported(
+                        "The Wayland compositor can't activate windows, it doesn't support wlr-foreign-toplevel-management"
+                            .to_string(),
+                    )
+                    .into())
+                }
+            };
+            let handle = listing
+                .state
+                .toplevels
+                .iter()
+                .find(|(_, toplevel)| toplevel.id == id)
+                .map(|(handle, _)| handle)
+                .ok_or_else(|| anyhow!("The Wayland toplevel {} is closed", id))?;
+            if let Handle::Wlr(handle) = handle {
+                handle.activate(&seat);
+            }
+            // wait for the compositor to handle the request
+            listing.update()
+        })
+    }
+
+    /// The focused toplevel, [`NotSupported`] if the compositor doesn't tell
+    pub fn active_toplevel(&self) -> Result<Option<Toplevel>> {
+        let toplevels = self.toplevels()?;
+        if toplevels
+            .iter()
+            .any(|toplevel| toplevel.activated.is_none())
+        {
+            return Err(NotSupported(
+                "The Wayland compositor doesn't tell which window is focused, it only supports ext-foreign-toplevel-list"
+                    .to_string(),
+            )
+            .into());
+        }
+        Ok(toplevels
+            .into_iter()
+            .find(|toplevel| toplevel.activated == Some(true)))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use wayland_protocols::ext::foreign_toplevel_list::v1::server::{
+        ext_foreign_toplevel_handle_v1 as ext_handle, ext_foreign_toplevel_list_v1 as ext_list,
+    };
+    use wayland_protocols_wlr::foreign_toplevel::v1::server::{
+        zwlr_foreign_toplevel_handle_v1 as wlr_handle,
+        zwlr_foreign_toplevel_manager_v1 as wlr_manager,
+    };
+    use wayland_server::backend::{ClientData, ClientId, DisconnectReason};
+    use wayland_server::protocol::wl_seat;
+    use wayland_server::{
+        Client, DataInit, Dispatch as ServerDispatch, Display, DisplayHandle, GlobalDispatch, New,
+        Resource,
+    };
+
+    /// A headless compositor announcing its toplevels, app ID, title and whether it's focused,
+    /// to each client binding the toplevel protocols, and recording the indices of those activated
+    pub(crate) struct Compositor {
+        toplevels: Vec<(&'static str, &'static str, bool)>,
+        pub(crate) activations: Arc<Mutex<Vec<usize>>>,
+    }
+
+    /// Tells the compositor to stop once the client hangs up
+    struct Session(AtomicBool);
+
+    impl ClientData for Session {
+        fn disconnected(&self, _client_id: ClientId, _reason: DisconnectReason) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    impl GlobalDispatch<wlr_manager::ZwlrForeignToplevelManagerV1, ()> for Compositor {
+        fn bind(
+            state: &mut Self,
+            handle: &DisplayHandle,
+            client: &Client,
+            resource: New<wlr_manager::ZwlrForeignToplevelManagerV1>,
+            _global_data: &(),
+            data_init: &mut DataInit<'_, Self>,
+        ) {
+            let manager = data_init.init(resource, ());
+            for (index, (app_id, title, activated)) in state.toplevels.iter().enumerate() {
+                let toplevel = client
+                    .create_resource::<wlr_handle::ZwlrForeignToplevelHandleV1, usize, Self>(
+                        handle,
+                        manager.version(),
+                        index,
+                    )
+                    .unwrap();
+                manager.toplevel(&toplevel);
+                toplevel.app_id(app_id.to_string());
+                toplevel.title(title.to_string());
+                let mut states = (wlr_handle::State::Maximized as u32).to_ne_bytes().to_vec();
+                if *activated {
+                    states.extend((wlr_handle::State::Activated as u32).to_ne_bytes());
+                }
+                toplevel.state(states);
+                toplevel.done();
+            }
+        }
+    }
+
+    impl ServerDispatch<wlr_manager::ZwlrForeignToplevelManagerV1, ()> for Compositor {
+        fn request(
+            _state: &mut Self,
+            _client: &Client,
+            resource: &wlr_manager::ZwlrForeignToplevelManagerV1,
+            request: wlr_manager::Request,
+            _data: &(),
+            _dhandle: &DisplayHandle,
+            _data_init: &mut DataInit<'_, Self>,
+        ) {
+            if let wlr_manager::Request::Stop = request {
+                resource.finished();
+            }
+        }
+    }
+
+    impl ServerDispatch<wlr_handle::ZwlrForeignToplevelHandleV1, usize> for Compositor {
+        fn request(
+            state: &mut Self,
+            _client: &Client,
+            _resource: &wlr_handle::ZwlrForeignToplevelHandleV1,
+            request: wlr_handle::Request,
+            index: &usize,
+            _dhandle: &DisplayHandle,
+            _data_init: &mut DataInit<'_, Self>,
+        ) {
+            if let wlr_handle::Request::Activate { .. } = request {
+                state.activations.lock().unwrap().push(*index);
+            }
+        }
+    }
+
+    impl GlobalDispatch<wl_seat::WlSeat, ()> for Compositor {
+        fn bind(
+            _state: &mut Self,
+            _handle: &DisplayHandle,
+            _client: &Client,
+            resource: New<wl_seat::WlSeat>,
+            _global_data: &(),
+            data_init: &mut DataInit<'_, Self>,
+        ) {
+            data_init.init(resource, ());
+        }
+    }
+
+    impl ServerDispatch<wl_seat::WlSeat, ()> for Compositor {
+        fn request(
+            _state: &mut Self,
+            _client: &Client,
+            _resource: &wl_seat::WlSeat,
+            _request: wl_seat::Request,
+            _data: &(),
+            _dhandle: &DisplayHandle,
+            _data_init: &mut DataInit<'_, Self>,
+        ) {
+        }
+    }
+
+    impl GlobalDispatch<ext_list::ExtForeignToplevelListV1, ()> for Compositor {
+        fn bind(
+            state: &mut Self,
+            handle: &DisplayHandle,
+            client: &Client,
+            resource: New<ext_list::ExtForeignToplevelListV1>,
+            _global_data: &(),
+            data_init: &mut DataInit<'_, Self>,
+        ) {
+            let list = data_init.init(resource, ());
+            for (app_id, title, _) in &state.toplevels {
+                let toplevel = client
+                    .create_resource::<ext_handle::ExtForeignToplevelHandleV1, (), Self>(
+                        handle,
+                        list.version(),
+                        (),
+                    )
+                    .unwrap();
+                list.toplevel(&toplevel);
+                toplevel.identifier(format!("{}-{}", app_id, title));
+                toplevel.app_id(app_id.to_string());
+                toplevel.title(title.to_string());
+                toplevel.done();
+            }
+        }
+    }
+
+    impl ServerDispatch<ext_list::ExtForeignToplevelListV1, ()> for Compositor {
+        fn request(
+            _state: &mut Self,
+            _client: &Client,
+            resource: &ext_list::ExtForeignToplevelListV1,
+            request: ext_list::Request,
+            _data: &(),
+            _dhandle: &DisplayHandle,
+            _data_init: &mut DataInit<'_, Self>,
+        ) {
+            if let ext_list::Request::Stop = request {
+                resource.finished();
+            }
+        }
+    }
+
+    impl ServerDispatch<ext_handle::ExtForeignToplevelHandleV1, ()> for Compositor {
+        fn request(
+            _state: &mut Self,
+            _client: &Client,
+            _resource: &ext_handle::ExtForeignToplevelHandleV1,
+            _request: ext_handle::Request,
+            _data: &(),
+            _dhandle: &DisplayHandle,
+            _data_init: &mut DataInit<'_, Self>,
+        ) {
+        }
+    }
+
+    /// Serve `compositor` with the given protocols on a thread, and connect to it
+    pub(crate) fn serve(mut compositor: Compositor, wlr: bool, ext: bool) -> Wayland {
+        let (server, client) = UnixStream::pair().unwrap();
+        std::thread::spawn(move || {
+            let mut display = Display::<Compositor>::new().unwrap();
+            let mut handle = display.handle();
+            if wlr {
+                handle.create_global::<Compositor, wlr_manager::ZwlrForeignToplevelManagerV1, ()>(
+                    3,
+                    (),
+                );
+            }
+            handle.create_global::<Compositor, wl_seat::WlSeat, ()>(1, ());
+            if ext {
+                handle.create_global::<Compositor, ext_list::ExtForeignToplevelListV1, ()>(1, ());
+            }
+            let session = Arc::new(Session(AtomicBool::new(false)));
+            handle.insert_client(server, session.clone()).unwrap();
+            while !session.0.load(Ordering::SeqCst) {
+                display.dispatch_clients(&mut compositor).unwrap();
+                display.flush_clients().unwrap();
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        });
+        Wayland::new(Connection::from_socket(client).unwrap())
+    }
+
+    pub(crate) fn compositor() -> Compositor {
+        Compositor {
+            toplevels: vec![
+                ("firefox", "Mozilla Firefox", false),
+                ("org.gnome.Nautilus", "Home", true),
+            ],
+            activations: Arc::default(),
+        }
+    }
+
+    fn toplevel(id: u64, app_id: &str, title: &str, activated: Option<bool>) -> Toplevel {
+        Toplevel {
+            id,
+            app_id: Some(app_id.to_string()),
+            title: Some(title.to_string()),
+            activated,
+        }
+    }
+
+    #[test]
+    fn test_wlr_toplevels() {
+        let wayland = serve(compositor(), true, true);
+        assert_eq!(
+            wayland.toplevels().unwrap(),
+            vec![
+                toplevel(1, "firefox", "Mozilla Firefox", Some(false)),
+                toplevel(2, "org.gnome.Nautilus", "Home", Some(true)),
+            ]
+        );
+        // a second listing on the same connection
+        assert_eq!(
+            wayland.active_toplevel().unwrap(),
+            Some(toplevel(2, "org.gnome.Nautilus", "Home", Some(true)))
+        );
+    }
+
+    #[test]
+    fn test_ext_toplevels() {
+        let wayland = serve(compositor(), false, true);
+        assert_eq!(
+            wayland.toplevels().unwrap(),
+            vec![
+                toplevel(1, "firefox", "Mozilla Firefox", None),
+                toplevel(2, "org.gnome.Nautilus", "Home", None),
+            ]
+        );
+        let err = wayland.active_toplevel().unwrap_err();
+        assert!(err.downcast_ref::<NotSupported>().is_some());
+
+        let wayland = serve(compositor(), false, false);
+        let err = wayland.toplevels().unwrap_err();
+        assert!(err.downcast_ref::<NotSupported>().is_some());
+    }
+
+    #[test]
+    fn test_activate() {
+        // two terminals at the same directory
+        let terminals = Compositor {
+            toplevels: vec![
+                ("org.gnome.Console", "~", true),
+                ("org.gnome.Console", "~", false),
+            ],
+            activations: Arc::default(),
+        };
+        let activations = terminals.activations.clone();
+        let wayland = serve(terminals, true, true);
+        let toplevels = wayland.toplevels().unwrap();
+        wayland.activate(toplevels[1].id).unwrap();
+        assert_eq!(*activations.lock().unwrap(), vec![1]);
+        // the numbers hold across listings
+        assert_eq!(wayland.toplevels().unwrap(), toplevels);
+        wayland.activate(toplevels[0].id).unwrap();
+        assert_eq!(*activations.lock().unwrap(), vec![1, 0]);
+        assert!(wayland.activate(3).is_err());
+        assert_eq!(activations.lock().unwrap().len(), 2);
+
+        let wayland = serve(compositor(), false, true);
+        let err = wayland.activate(1).unwrap_err();
+        assert!(err.downcast_ref::<NotSupported>().is_some());
+    }
+
+    #[test]
+    fn test_hints() {
+        let hints = toplevel(1, "Org.Gnome.Nautilus", "Home", None).hints();
+        assert_eq!(
+            hints.desktop_file_ids,
+            vec!["Org.Gnome.Nautilus", "org.gnome.nautilus"]
+        );
+        assert_eq!(hints.window_classes, vec!["Org.Gnome.Nautilus"]);
+        assert_eq!(
+            toplevel(1, "gimp.desktop", "GIMP", None)
+                .hints()
+                .desktop_file_ids,
+            vec!["gimp.desktop", "gimp"]
+        );
+        assert_eq!(Toplevel::default().hints(), ProcessHints::default());
+    }
+}
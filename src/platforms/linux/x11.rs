@@ -0,0 +1,347 @@
+//! Windows of an X11 session, read from the properties window managers set following
+//! the [EWMH](https://specifications.freedesktop.org/wm-spec/latest/) spec
+//!
+//! - `_NET_ACTIVE_WINDOW` on the root window, the focused window
+//! - `_NET_WM_PID` on each window, the process that owns it
+//! - `WM_CLASS` on each window, its instance and class names, which usually match the app's `StartupWMClass`
+//! - `_NET_CLIENT_LIST` on the root window, the windows the window manager manages
+//! - `_NET_WM_NAME` and `_NET_WM_DESKTOP` on each window, its title and workspace
+//! - `_NET_CLIENT_LIST_STACKING` on the root window, the same windows from the bottom to the top of the stack
+//!
+//! Windows are activated by sending the window manager a `_NET_ACTIVE_WINDOW` client message.
+use crate::common::{NotSupported, WindowGeometry};
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ClientMessageEvent, ConnectionExt, EventMask, Window,
+};
+use x11rb::rust_connection::RustConnection;
+
+x11rb::atom_manager! {
+    pub Atoms: AtomsCookie {
+        _NET_ACTIVE_WINDOW,
+        _NET_CLIENT_LIST,
+        _NET_CLIENT_LIST_STACKING,
+        _NET_WM_DESKTOP,
+        _NET_WM_NAME,
+        _NET_WM_PID,
+        UTF8_STRING,
+    }
+}
+
+/// `_NET_WM_DESKTOP` of windows shown on all workspaces
+const ALL_DESKTOPS: u32 = 0xFFFFFFFF;
+
+/// The source indication of `_NET_ACTIVE_WINDOW` requests from pagers and taskbars, which window managers
+/// obey, unlike requests from applications that may be refused to prevent focus stealing
+const SOURCE_PAGER: u32 = 2;
+
+/// A connection to the X server of `$DISPLAY`
+pub struct X11 {
+    conn: RustConnection,
+    root: Window,
+    atoms: Atoms,
+}
+
+/// `WM_CLASS` holds two NUL terminated strings, the instance and the class name
+pub fn parse_wm_class(value: &[u8]) -> Vec<String> {
+    value
+        .split(|byte| *byte == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect()
+}
+
+impl X11 {
+    /// Connect to `$DISPLAY`, [`NotSupported`] if the session has no X server
+    pub fn connect() -> Result<Self> {
+        if std::env::var_os("DISPLAY").is_none_or(|display| display.is_empty()) {
+            let session = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+                "a Wayland session without Xwayland"
+            } else {
+                "a session without a display server"
+            };
+            return Err(NotSupported(format!(
+                "No X11 display in {}, $DISPLAY is not set",
+                session
+            ))
+            .into());
+        }
+        let (conn, screen) = x11rb::connect(None)
+            .map_err(|e| NotSupported(format!("Failed to connect to the X server: {}", e)))?;
+        let root = conn.setup().roots[screen].root;
+        let atoms = Atoms::new(&conn)?.reply()?;
+        Ok(X11 { conn, root, atoms })
+    }
+
+    /// The 32-bit values of a window property, empty if it isn't set
+    fn property32(&self, window: Window, property: Atom, kind: AtomEnum) -> Result<Vec<u32>> {
+        let reply = self
+            .conn
+            .get_property(false, window, property, kind, 0, u32::MAX / 4)?
+            .reply()?;
+        Ok(reply
+            .value32()
+            .map(|values| values.collect())
+            .unwrap_or_default())
+    }
+
+    /// The focused window, `None` if there's none or the window manager doesn't tell
+    pub fn active_window(&self) -> Result<Option<Window>> {
+        let windows =
+            self.property32(self.root, self.atoms._NET_ACTIVE_WINDOW, AtomEnum::WINDOW)?;
+        Ok(windows
+            .first()
+            .copied()
+            .filter(|window| *window != x11rb::NONE))
+    }
+
+    /// The raw value of a window property, empty if it isn't set
+    fn property8(&self, window: Window, property: Atom, kind: impl Into<Atom>) -> Result<Vec<u8>> {
+        let reply = self
+            .conn
+            .get_property(false, window, property, kind, 0, u32::MAX / 4)?
+            .reply()?;
+        Ok(reply.value)
+    }
+
+    /// The windows managed by the window manager, in the order they were mapped
+    pub fn client_list(&self) -> Result<Vec<Window>> {
+        self.property32(self.root, self.atoms._NET_CLIENT_LIST, AtomEnum::WINDOW)
+    }
+
+    /// The managed windows from the bottom to the top of the stack, so the most recently raised one last
+    pub fn client_list_stacking(&self) -> Result<Vec<Window>> {
+        self.property32(
+            self.root,
+            self.atoms._NET_CLIENT_LIST_STACKING,
+            AtomEnum::WINDOW,
+        )
+    }
+
+    /// Ask the window manager to switch to the workspace of a window, raise it and focus it
+    pub fn activate_window(&self, window: Window) -> Result<()> {
+        let active = self.active_window()?.unwrap_or(x11rb::NONE);
+        let event = ClientMessageEvent::new(
+            32,
+            window,
+            self.atoms._NET_ACTIVE_WINDOW,
+            [SOURCE_PAGER, x11rb::CURRENT_TIME, active, 0, 0],
+        );
+        self.conn.send_event(
+            false,
+            self.root,
+            EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+            event,
+        )?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// The process that owns a window, if the client tells
+    pub fn window_pid(&self, window: Window) -> Result<Option<u32>> {
+        let pids = self.property32(window, self.atoms._NET_WM_PID, AtomEnum::CARDINAL)?;
+        Ok(pids.first().copied())
+    }
+
+    /// The instance and class names of a window
+    pub fn window_class(&self, window: Window) -> Result<Vec<String>> {
+        let value = self.property8(window, AtomEnum::WM_CLASS.into(), AtomEnum::STRING)?;
+        Ok(parse_wm_class(&value))
+    }
+
+    /// The title of a window, from `_NET_WM_NAME` or else the Latin-1 `WM_NAME` of older clients
+    pub fn window_title(&self, window: Window) -> Result<Option<String>> {
+        let name = self.property8(window, self.atoms._NET_WM_NAME, self.atoms.UTF8_STRING)?;
+        if !name.is_empty() {
+            return Ok(Some(String::from_utf8_lossy(&name).into_owned()));
+        }
+        let name = self.property8(window, AtomEnum::WM_NAME.into(), AtomEnum::STRING)?;
+        Ok(Some(name.iter().map(|byte| char::from(*byte)).collect())
+            .filter(|name: &String| !name.is_empty()))
+    }
+
+    /// The index of the workspace a window is on, `None` if it's on all of them
+    pub fn window_workspace(&self, window: Window) -> Result<Option<u32>> {
+        let desktops = self.property32(window, self.atoms._NET_WM_DESKTOP, AtomEnum::CARDINAL)?;
+        Ok(desktops
+            .first()
+            .copied()
+            .filter(|desktop| *desktop != ALL_DESKTOPS))
+    }
+
+    /// The position of a window on the screen and its size, without the frame the window manager adds
+    pub fn window_geometry(&self, window: Window) -> Result<WindowGeometry> {
+        let geometry = self.conn.get_geometry(window)?.reply()?;
+        let position = self
+            .conn
+            .translate_coordinates(window, self.root, 0, 0)?
+            .reply()?;
+        Ok(WindowGeometry {
+            x: position.dst_x.into(),
+            y: position.dst_y.into(),
+            width: geometry.width.into(),
+            height: geometry.height.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x11rb::protocol::xproto::{
+        ChangeWindowAttributesAux, CreateWindowAux, PropMode, WindowClass,
+    };
+    use x11rb::protocol::Event;
+    use x11rb::wrapper::ConnectionExt as _;
+
+    #[test]
+    fn test_parse_wm_class() {
+        assert_eq!(
+            parse_wm_class(b"Navigator\0firefox\0"),
+            vec!["Navigator", "firefox"]
+        );
+        assert_eq!(parse_wm_class(b"xterm\0XTerm"), vec!["xterm", "XTerm"]);
+        assert!(parse_wm_class(b"").is_empty());
+    }
+
+    #[test]
+    #[ignore = "needs an X server, run with `xvfb-run cargo test -- --ignored`"]
+    fn test_active_window() {
+        let x11 = X11::connect().unwrap();
+        let window = x11.conn.generate_id().unwrap();
+        x11.conn
+            .create_window(
+                x11rb::COPY_DEPTH_FROM_PARENT,
+                window,
+                x11.root,
+                10,
+                20,
+                100,
+                50,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                x11rb::COPY_FROM_PARENT,
+                &CreateWindowAux::new(),
+            )
+            .unwrap();
+        x11.conn
+            .change_property8(
+                PropMode::REPLACE,
+                window,
+                AtomEnum::WM_CLASS,
+                AtomEnum::STRING,
+                b"gedit\0Gedit\0",
+            )
+            .unwrap();
+        x11.conn
+            .change_property32(
+                PropMode::REPLACE,
+                window,
+                x11.atoms._NET_WM_PID,
+                AtomEnum::CARDINAL,
+                &[std::process::id()],
+            )
+            .unwrap();
+        x11.conn
+            .change_property8(
+                PropMode::REPLACE,
+                window,
+                x11.atoms._NET_WM_NAME,
+                x11.atoms.UTF8_STRING,
+                "notes.txt – gedit".as_bytes(),
+            )
+            .unwrap();
+        x11.conn
+            .change_property32(
+                PropMode::REPLACE,
+                window,
+                x11.atoms._NET_WM_DESKTOP,
+                AtomEnum::CARDINAL,
+                &[1],
+            )
+            .unwrap();
+        // there's no window manager to do it
+        x11.conn
+            .change_property32(
+                PropMode::REPLACE,
+                x11.root,
+                x11.atoms._NET_CLIENT_LIST,
+                AtomEnum::WINDOW,
+                &[window],
+            )
+            .unwrap();
+        x11.conn
+            .change_property32(
+                PropMode::REPLACE,
+                x11.root,
+                x11.atoms._NET_ACTIVE_WINDOW,
+                AtomEnum::WINDOW,
+                &[window],
+            )
+            .unwrap();
+        x11.conn.flush().unwrap();
+
+        assert_eq!(x11.active_window().unwrap(), Some(window));
+        assert_eq!(x11.window_pid(window).unwrap(), Some(std::process::id()));
+        assert_eq!(x11.window_class(window).unwrap(), vec!["gedit", "Gedit"]);
+        assert_eq!(x11.client_list().unwrap(), vec![window]);
+        assert_eq!(
+            x11.window_title(window).unwrap().as_deref(),
+            Some("notes.txt – gedit")
+        );
+        assert_eq!(x11.window_workspace(window).unwrap(), Some(1));
+        assert_eq!(
+            x11.window_geometry(window).unwrap(),
+            WindowGeometry {
+                x: 10,
+                y: 20,
+                width: 100,
+                height: 50
+            }
+        );
+    }
+
+    #[test]
+    #[ignore = "needs an X server, run with `xvfb-run cargo test -- --ignored`"]
+    fn test_activate_window() {
+        // play the window manager, which receives the requests of clients
+        let wm = X11::connect().unwrap();
+        wm.conn
+            .change_window_attributes(
+                wm.root,
+                &ChangeWindowAttributesAux::new().event_mask(EventMask::SUBSTRUCTURE_REDIRECT),
+            )
+            .unwrap()
+            .check()
+            .unwrap();
+
+        let x11 = X11::connect().unwrap();
+        let window = x11.conn.generate_id().unwrap();
+        x11.conn
+            .create_window(
+                x11rb::COPY_DEPTH_FROM_PARENT,
+                window,
+                x11.root,
+                0,
+                0,
+                100,
+                100,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                x11rb::COPY_FROM_PARENT,
+                &CreateWindowAux::new(),
+            )
+            .unwrap();
+        x11.activate_window(window).unwrap();
+
+        loop {
+            if let Event::ClientMessage(event) = wm.conn.wait_for_event().unwrap() {
+                assert_eq!(event.window, window);
+                assert_eq!(event.type_, wm.atoms._NET_ACTIVE_WINDOW);
+                assert_eq!(event.data.as_data32()[0], SOURCE_PAGER);
+                break;
+            }
+        }
+    }
+}
@@ -1,9 +1,13 @@
-use crate::common::{App, AppTrait, SearchPath};
+use crate::common::{
+    App, AppTrait, NotSupported, ProcessTermination, RunningApp, SearchPath, SearchPathKind, Window,
+};
+use crate::utils::executable::{app_from_executable, is_executable};
 use crate::utils::image::{RustImage, RustImageData};
 use crate::utils::mac::{
     run_mdfind_to_get_app_list, run_system_profiler_to_get_app_list, MacAppPath,
     MacSystemProfilerAppList, MacSystemProfilterAppInfo,
 };
+use crate::utils::parallel;
 use anyhow::Result;
 use cocoa::base::id;
 use objc;
@@ -131,6 +135,32 @@ pub fn open_file_with(file_path: PathBuf, app: App) {
     println!("output: {:?}", output);
 }
 
+/// MIME type associations aren't implemented on Mac yet
+pub fn default_app_for_mime_type(_mime_type: &str) -> Result<Option<PathBuf>> {
+    Err(NotSupported("MIME type associations are not supported on Mac yet".to_string()).into())
+}
+
+pub fn apps_for_mime_type(_mime_type: &str) -> Result<Vec<PathBuf>> {
+    Err(NotSupported("MIME type associations are not supported on Mac yet".to_string()).into())
+}
+
+pub fn apps_for_file(_path: &Path) -> Result<Vec<PathBuf>> {
+    Err(NotSupported("MIME type associations are not supported on Mac yet".to_string()).into())
+}
+
+/// Nothing to add to the apps found in the cache: their [`App::url_schemes`] are read from each app's Info.plist
+pub fn apps_for_scheme(_scheme: &str) -> Result<Vec<PathBuf>> {
+    Ok(vec![])
+}
+
+pub fn set_default_app_for_mime_type(_mime_type: &str, _app_path: &Path) -> Result<()> {
+    Err(NotSupported("MIME type associations are not supported on Mac yet".to_string()).into())
+}
+
+pub fn open(_target: &str, _dry_run: bool) -> Result<(PathBuf, Vec<String>)> {
+    Err(NotSupported("Opening with the default app is not supported on Mac yet".to_string()).into())
+}
+
 pub fn nsstring_to_string(nsstring: *mut Object) -> Result<String> {
     unsafe {
         let cstr: *const i8 = msg_send![nsstring, UTF8String];
@@ -182,6 +212,11 @@ pub fn nsstring_to_string(nsstring: *mut Object) -> Result<String> {
 //     Ok(app)
 // }
 
+/// The frontmost app, the platform finds its bundle or executable itself
+pub fn frontmost_application(_apps: &[App]) -> Result<App> {
+    get_frontmost_application()
+}
+
 pub fn get_frontmost_application() -> Result<App> {
     unsafe {
         let shared_workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
@@ -245,63 +280,87 @@ pub fn get_all_apps_sys_profiler() -> Result<Vec<App>> {
 }
 
 pub fn get_all_apps_mdfind() -> Result<Vec<App>> {
-    let apps_list = run_mdfind_to_get_app_list()?;
-    Ok(apps_list
-        .iter()
-        .map(|app_path| MacAppPath::new(PathBuf::from(app_path)).to_app())
-        .filter_map(|x| x)
-        .collect())
+    let mut apps_list = run_mdfind_to_get_app_list()?;
+    // mdfind output order isn't stable, sort so the result is deterministic
+    apps_list.sort();
+    Ok(parallel::filter_map_ordered(apps_list, |app_path| {
+        MacAppPath::new(PathBuf::from(app_path)).to_app()
+    }))
 }
 
 /// Search apps in the given path iteratively by walking down the path, depth is the depth of the path
 pub fn search_apps(path: PathBuf, depth: u8) -> Result<Vec<App>> {
-    if depth == 0 {
+    search_path_apps(&SearchPath::new(path, depth))
+}
+
+/// Find the apps in a search path, honoring its kind and include/exclude patterns
+pub fn search_path_apps(search_path: &SearchPath) -> Result<Vec<App>> {
+    if search_path.depth == 0 {
         return Ok(vec![]);
     }
 
-    let mut apps = Vec::new();
-    // Set max_depth on WalkDir to limit traversal depth
-    let walker = WalkDir::new(path).max_depth(depth.into());
-
-    for entry in walker {
-        let entry = match entry {
-            Ok(e) => e,
-            Err(_) => continue,
-        };
-
-        let path = entry.path();
-        if path.is_dir() {
+    let entries = search_path
+        .walk()
+        .map(|entry| entry.into_path())
+        .filter(|path| search_path.is_match(path));
+    match search_path.kind {
+        SearchPathKind::Auto | SearchPathKind::AppBundles => {
             // Check if the path has an extension and if it's an .app
-            if let Some(ext) = path.extension() {
-                if ext == "app" {
-                    if let Ok(app) = App::from_path(&path) {
-                        apps.push(app);
-                    }
-                }
-            }
+            let app_paths: Vec<PathBuf> = entries
+                .filter(|path| path.is_dir() && path.extension().is_some_and(|ext| ext == "app"))
+                .collect();
+            Ok(parallel::filter_map_ordered(app_paths, |path| {
+                App::from_path(&path).ok()
+            }))
+        }
+        SearchPathKind::Executables => Ok(entries
+            .filter(|path| is_executable(path))
+            .filter_map(|path| app_from_executable(&path))
+            .collect()),
+        kind => {
+            log::warn!(
+                "Search path kind {:?} is not supported on Mac: {:?}",
+                kind,
+                search_path.path
+            );
+            Ok(vec![])
         }
     }
-
-    Ok(apps)
 }
 
 pub fn get_default_search_paths() -> Vec<SearchPath> {
     vec![]
 }
 
-pub fn get_all_apps(extra_search_paths: &Vec<SearchPath>) -> Result<Vec<App>> {
-    let mut all_apps = get_all_apps_mdfind()?;
+/// Scan the given search paths only. Apps found in earlier search paths come first,
+/// and an .app bundle reachable from several search paths is only reported once.
+pub fn get_apps_in_search_paths(search_paths: &[SearchPath]) -> Result<Vec<App>> {
+    let results = parallel::map_ordered(search_paths.to_vec(), |path| search_path_apps(&path));
+    let mut seen_paths = std::collections::HashSet::new();
+    let mut all_apps = vec![];
+    for apps in results {
+        for app in apps? {
+            if seen_paths.insert(app.app_desktop_path.clone()) {
+                all_apps.push(app);
+            }
+        }
+    }
+    Ok(all_apps)
+}
+
+pub fn get_all_apps(extra_search_paths: &[SearchPath]) -> Result<Vec<App>> {
+    let (all_apps, extra_apps) = parallel::join(get_all_apps_mdfind, || {
+        get_apps_in_search_paths(extra_search_paths)
+    });
+    let mut all_apps = all_apps?;
     let mut seen_paths = all_apps
         .iter()
         .map(|app| app.app_desktop_path.clone())
         .collect::<std::collections::HashSet<_>>();
 
-    for path in extra_search_paths {
-        let apps = search_apps(path.path.clone(), path.depth)?;
-        for app in apps {
-            if seen_paths.insert(app.app_desktop_path.clone()) {
-                all_apps.push(app);
-            }
+    for app in extra_apps? {
+        if seen_paths.insert(app.app_desktop_path.clone()) {
+            all_apps.push(app);
         }
     }
     Ok(all_apps)
@@ -338,6 +397,54 @@ pub fn get_running_apps() -> Vec<App> {
     }
 }
 
+pub fn terminate(
+    _pids: &[u32],
+    _start_ticks: &std::collections::HashMap<u32, u64>,
+    _main_pid: Option<u32>,
+    _graceful: bool,
+    _timeout: std::time::Duration,
+) -> Result<Vec<ProcessTermination>> {
+    Err(NotSupported("Terminating apps is not supported on Mac yet".to_string()).into())
+}
+
+pub fn windows(_apps: &[App]) -> Result<Vec<Window>> {
+    Err(NotSupported("Listing windows is not supported on Mac yet".to_string()).into())
+}
+
+pub fn activate_window(_windows: &[Window]) -> Result<Window> {
+    Err(NotSupported("Activating windows is not supported on Mac yet".to_string()).into())
+}
+
+/// `open -a` launches the app, or brings it to the front if it's running already
+pub fn launch_app(app: &App) -> Result<Vec<String>> {
+    let argv = vec![
+        "open".to_string(),
+        "-a".to_string(),
+        app.app_desktop_path.to_string_lossy().into_owned(),
+    ];
+    std::process::Command::new(&argv[0])
+        .args(&argv[1..])
+        .spawn()?;
+    Ok(argv)
+}
+
+/// The running apps, without their processes yet
+pub fn running_app_processes(_apps: &[App]) -> Result<Vec<RunningApp>> {
+    Ok(get_running_apps()
+        .into_iter()
+        .map(|app| RunningApp {
+            app,
+            pids: vec![],
+            main_pid: None,
+            start_ticks: Default::default(),
+            started_at: None,
+            user: None,
+            memory_bytes: None,
+            is_frontmost: false,
+        })
+        .collect())
+}
+
 /// path can be the path to .app folder or .icns file
 pub fn load_icon(path: &Path) -> Result<RustImageData> {
     // check file type and file extension
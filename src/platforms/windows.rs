@@ -1,5 +1,10 @@
-use crate::common::{App, SearchPath};
+use crate::common::{
+    App, AppSource, NotSupported, ProcessTermination, RunningApp, SearchPath, SearchPathKind,
+    Window,
+};
+use crate::utils::executable::{app_from_executable, is_executable};
 use crate::utils::image::{RustImage, RustImageData};
+use crate::utils::parallel;
 use crate::AppTrait;
 use anyhow::Ok;
 use parselnk::string_data;
@@ -14,10 +19,11 @@ use serde_derive::Serialize;
 // use std::ffi::OsString;
 // use std::os::windows::ffi::OsStringExt;
 use std::process::Command;
-use walkdir::WalkDir;
 // use winapi::um::winuser::{GetForegroundWindow, GetWindowTextLengthW, GetWindowTextW};
 use image;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use winreg::enums::HKEY_CLASSES_ROOT;
+use winreg::RegKey;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -126,6 +132,7 @@ pub fn parse_lnk_with_powershell_2(lnk_path: PathBuf) -> anyhow::Result<App> {
         icon_path: icon_path,
         app_path_exe: Some(target_path),
         app_desktop_path: desktop_path,
+        ..Default::default()
     };
     Ok(app)
 }
@@ -156,6 +163,7 @@ fn parse_lnk(path: PathBuf) -> Option<App> {
         icon_path,
         app_path_exe: exe,
         app_desktop_path: work_dir,
+        ..Default::default()
     })
 }
 
@@ -283,6 +291,7 @@ fn parse_lnk2(path: PathBuf) -> Option<App> {
         icon_path: icon,
         app_path_exe: Some(exe_path),
         app_desktop_path: work_dir,
+        ..Default::default()
     })
 }
 
@@ -294,8 +303,45 @@ pub fn open_file_with(file_path: PathBuf, app: App) {
         .expect("Failed to open file with the specified application.");
 }
 
+/// MIME type associations aren't implemented on Windows yet
+pub fn default_app_for_mime_type(_mime_type: &str) -> Result<Option<PathBuf>> {
+    Err(NotSupported("MIME type associations are not supported on Windows yet".to_string()).into())
+}
+
+pub fn apps_for_mime_type(_mime_type: &str) -> Result<Vec<PathBuf>> {
+    Err(NotSupported("MIME type associations are not supported on Windows yet".to_string()).into())
+}
+
+pub fn apps_for_file(_path: &Path) -> Result<Vec<PathBuf>> {
+    Err(NotSupported("MIME type associations are not supported on Windows yet".to_string()).into())
+}
+
+/// Nothing to add to the apps found in the cache: their [`App::url_schemes`] are read from the registry when apps are scanned
+pub fn apps_for_scheme(_scheme: &str) -> Result<Vec<PathBuf>> {
+    Ok(vec![])
+}
+
+pub fn set_default_app_for_mime_type(_mime_type: &str, _app_path: &Path) -> Result<()> {
+    Err(NotSupported("MIME type associations are not supported on Windows yet".to_string()).into())
+}
+
+pub fn open(_target: &str, _dry_run: bool) -> Result<(PathBuf, Vec<String>)> {
+    Err(
+        NotSupported("Opening with the default app is not supported on Windows yet".to_string())
+            .into(),
+    )
+}
+
+/// The frontmost app, the platform finds its bundle or executable itself
+pub fn frontmost_application(_apps: &[App]) -> Result<App> {
+    get_frontmost_application()
+}
+
 pub fn get_frontmost_application() -> Result<App> {
-    todo!();
+    Err(
+        NotSupported("Finding the frontmost app is not supported on Windows yet".to_string())
+            .into(),
+    )
     // unsafe {
     //     let hwnd = GetForegroundWindow();
     //     let mut buffer = vec![0u16; GetWindowTextLengthW(hwnd) as usize + 1];
@@ -331,56 +377,201 @@ pub fn get_default_search_paths() -> Vec<SearchPath> {
     search_paths
 }
 
-pub fn get_all_apps(extra_search_paths: &Vec<SearchPath>) -> Result<Vec<App>> {
-    // Create a HashSet of search paths starting with the default Windows paths
-    let mut search_paths: HashSet<SearchPath> = HashSet::new();
+/// Check if a shortcut is in a `Start Menu` folder, of the current user or of all users
+fn is_start_menu_shortcut(path: &Path) -> bool {
+    path.components()
+        .any(|component| component.as_os_str().eq_ignore_ascii_case("Start Menu"))
+}
 
-    // Add default Windows paths with unlimited depth
-    let default_paths = get_default_search_paths();
-    for path in default_paths {
-        search_paths.insert(path);
-    }
+/// A file found while walking a search path
+enum ScanEntry {
+    Shortcut(PathBuf),
+    Executable(PathBuf),
+}
 
-    // Add extra search paths
-    for path in extra_search_paths {
-        search_paths.insert(path.clone());
+fn find_entries(search_path: &SearchPath) -> Vec<ScanEntry> {
+    if !search_path.path.exists() {
+        return vec![];
     }
+    let entries = search_path
+        .walk()
+        .map(|entry| entry.into_path())
+        .filter(|path| path.is_file() && search_path.is_match(path));
+    match search_path.kind {
+        SearchPathKind::Auto | SearchPathKind::Shortcuts => entries
+            .filter(|path| path.extension().is_some_and(|ext| ext == "lnk"))
+            .map(ScanEntry::Shortcut)
+            .collect(),
+        SearchPathKind::Executables => entries
+            .filter(|path| is_executable(path))
+            .map(ScanEntry::Executable)
+            .collect(),
+        kind => {
+            log::warn!(
+                "Search path kind {:?} is not supported on Windows: {:?}",
+                kind,
+                search_path.path
+            );
+            vec![]
+        }
+    }
+}
 
-    let mut apps = vec![];
-    for search_path in search_paths {
-        if !search_path.path.exists() {
+/// The program of a `shell\open\command` value, quoted or up to the first space
+fn command_program(command: &str) -> Option<&str> {
+    let command = command.trim();
+    let program = match command.strip_prefix('"') {
+        Some(rest) => rest.split('"').next(),
+        None => command.split_whitespace().next(),
+    };
+    program.filter(|program| !program.is_empty())
+}
+
+/// The URL schemes registered under `HKEY_CLASSES_ROOT` with a `URL Protocol` value,
+/// keyed by the lowercased program of their open command
+fn url_protocol_handlers() -> HashMap<String, Vec<String>> {
+    let classes = RegKey::predef(HKEY_CLASSES_ROOT);
+    let mut handlers: HashMap<String, Vec<String>> = HashMap::new();
+    for scheme in classes.enum_keys().filter_map(|key| key.ok()) {
+        if scheme.starts_with('.') {
             continue;
         }
-
-        for entry in WalkDir::new(search_path.path)
-            .max_depth(search_path.depth as usize)
-            .into_iter()
-            .filter_map(|e| e.ok())
+        let Some(key) = classes.open_subkey(&scheme).ok() else {
+            continue;
+        };
+        if key.get_raw_value("URL Protocol").is_err() {
+            continue;
+        }
+        let command: String = match key
+            .open_subkey("shell\\open\\command")
+            .and_then(|command| command.get_value(""))
         {
-            let path = entry.path();
-            if path.is_file() {
-                if let Some(extension) = path.extension() {
-                    if extension == "lnk" {
-                        log::debug!("Found lnk: {:?}", path);
-                        let result = App::from_path(&path);
-                        if let Some(app) = result.ok() {
-                            log::debug!("Added app: {:?}", app);
-                            apps.push(app);
-                        } else {
-                            log::debug!("Failed to create App from path: {:?}", path);
-                        }
+            std::result::Result::Ok(command) => command,
+            Err(_) => continue,
+        };
+        if let Some(program) = command_program(&command) {
+            let program = translate_path_alias(PathBuf::from(program));
+            let schemes = handlers
+                .entry(program.to_string_lossy().to_lowercase())
+                .or_default();
+            let scheme = scheme.to_lowercase();
+            if !schemes.contains(&scheme) {
+                schemes.push(scheme);
+            }
+        }
+    }
+    handlers
+}
+
+/// Scan the given search paths only, without adding the default Start Menu folders.
+/// Shortcuts are returned in search path order, then by file name.
+pub fn get_apps_in_search_paths(search_paths: &[SearchPath]) -> Result<Vec<App>> {
+    // Skip search paths that were already added
+    let mut seen_paths: HashSet<&SearchPath> = HashSet::new();
+    let search_paths: Vec<&SearchPath> = search_paths
+        .iter()
+        .filter(|search_path| seen_paths.insert(*search_path))
+        .collect();
+
+    let entries: Vec<ScanEntry> = parallel::map_ordered(search_paths, find_entries)
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let apps = parallel::filter_map_ordered(entries, |entry| match entry {
+        ScanEntry::Shortcut(path) => {
+            log::debug!("Found lnk: {:?}", path);
+            match App::from_path(&path) {
+                std::result::Result::Ok(mut app) => {
+                    if is_start_menu_shortcut(&path) {
+                        app.source = AppSource::StartMenu;
                     }
+                    log::debug!("Added app: {:?}", app);
+                    Some(app)
+                }
+                Err(_) => {
+                    log::debug!("Failed to create App from path: {:?}", path);
+                    None
                 }
             }
         }
-    }
+        ScanEntry::Executable(path) => app_from_executable(&path),
+    });
+    let handlers = url_protocol_handlers();
+    let apps = apps
+        .into_iter()
+        .map(|mut app| {
+            if let Some(schemes) = app
+                .app_path_exe
+                .as_ref()
+                .and_then(|exe| handlers.get(&exe.to_string_lossy().to_lowercase()))
+            {
+                app.url_schemes = schemes.clone();
+            }
+            app
+        })
+        .collect();
     Ok(apps)
 }
 
+pub fn get_all_apps(extra_search_paths: &[SearchPath]) -> Result<Vec<App>> {
+    // Start with the default Windows paths with unlimited depth
+    let mut search_paths = get_default_search_paths();
+
+    // Add extra search paths
+    search_paths.extend(extra_search_paths.iter().cloned());
+
+    get_apps_in_search_paths(&search_paths)
+}
+
 pub fn get_running_apps() -> Vec<App> {
     vec![]
 }
 
+pub fn terminate(
+    _pids: &[u32],
+    _start_ticks: &std::collections::HashMap<u32, u64>,
+    _main_pid: Option<u32>,
+    _graceful: bool,
+    _timeout: std::time::Duration,
+) -> Result<Vec<ProcessTermination>> {
+    Err(NotSupported("Terminating apps is not supported on Windows yet".to_string()).into())
+}
+
+pub fn windows(_apps: &[App]) -> Result<Vec<Window>> {
+    Err(NotSupported("Listing windows is not supported on Windows yet".to_string()).into())
+}
+
+pub fn activate_window(_windows: &[Window]) -> Result<Window> {
+    Err(NotSupported("Activating windows is not supported on Windows yet".to_string()).into())
+}
+
+pub fn launch_app(app: &App) -> Result<Vec<String>> {
+    let exe = app
+        .app_path_exe
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("{} has no executable", app.name))?;
+    Command::new(exe).spawn()?;
+    Ok(vec![exe.to_string_lossy().into_owned()])
+}
+
+/// The running apps, without their processes yet
+pub fn running_app_processes(_apps: &[App]) -> Result<Vec<RunningApp>> {
+    Ok(get_running_apps()
+        .into_iter()
+        .map(|app| RunningApp {
+            app,
+            pids: vec![],
+            main_pid: None,
+            start_ticks: Default::default(),
+            started_at: None,
+            user: None,
+            memory_bytes: None,
+            is_frontmost: false,
+        })
+        .collect())
+}
+
 impl AppTrait for App {
     fn load_icon(&self) -> Result<RustImageData> {
         let icon_path = match &self.icon_path {
@@ -439,6 +630,19 @@ mod tests {
         assert!(!apps.is_empty());
     }
 
+    #[test]
+    fn test_is_start_menu_shortcut() {
+        assert!(is_start_menu_shortcut(Path::new(
+            "C:\\ProgramData\\Microsoft\\Windows\\Start Menu\\Programs\\Firefox.lnk"
+        )));
+        assert!(is_start_menu_shortcut(Path::new(
+            "C:\\Users\\me\\AppData\\Roaming\\Microsoft\\Windows\\start menu\\Programs\\Code.lnk"
+        )));
+        assert!(!is_start_menu_shortcut(Path::new(
+            "C:\\Users\\me\\Desktop\\Firefox.lnk"
+        )));
+    }
+
     #[test]
     fn test_path_alias() {
         let path = PathBuf::from("%windir%\\system32\\mstsc.exe");
@@ -0,0 +1,163 @@
+//! Discovery providers
+//!
+//! Apps are discovered by providers. The built-in [`SearchPathProvider`] scans the platform's search paths,
+//! more providers can be registered with [`AppInfoContext::with_provider`], e.g. for an internal tool registry.
+//!
+//! ```ignore
+//! use applications::{App, AppInfoContext, AppProvider};
+//!
+//! struct ToolboxProvider;
+//!
+//! impl AppProvider for ToolboxProvider {
+//!     fn id(&self) -> &str {
+//!         "jetbrains-toolbox"
+//!     }
+//!
+//!     fn scan(&self, _ctx: &AppInfoContext) -> anyhow::Result<Vec<App>> {
+//!         Ok(vec![])
+//!     }
+//! }
+//!
+//! let mut ctx = AppInfoContext::new(vec![]).with_provider(ToolboxProvider, 10);
+//! ```
+use crate::common::{App, AppInfoContext};
+use crate::platforms::{get_all_apps, get_apps_in_search_paths};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Priority of the built-in [`SearchPathProvider`], register providers above it to take precedence over it
+pub const BUILTIN_PRIORITY: i32 = 0;
+
+/// A source of apps
+pub trait AppProvider: Send + Sync {
+    /// Unique name of the provider, used in logs and errors
+    fn id(&self) -> &str;
+
+    /// Find the apps this provider knows about. The context holds the search paths and options set by the user.
+    fn scan(&self, ctx: &AppInfoContext) -> Result<Vec<App>>;
+
+    /// Directories whose changes could change the result of [`AppProvider::scan`], for callers that watch the file system
+    fn watch_paths(&self, _ctx: &AppInfoContext) -> Vec<PathBuf> {
+        vec![]
+    }
+}
+
+/// A provider registered in an [`AppInfoContext`]
+#[derive(Clone)]
+pub struct ProviderEntry {
+    pub provider: Arc<dyn AppProvider>,
+    /// Providers with a higher priority are merged first, so their apps win conflicts
+    pub priority: i32,
+}
+
+impl std::fmt::Debug for ProviderEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProviderEntry")
+            .field("id", &self.provider.id())
+            .field("priority", &self.priority)
+            .finish()
+    }
+}
+
+/// How apps from different providers are merged
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MergeRule {
+    /// Apps with the same id (`app_desktop_path`) as an app from a higher priority provider are dropped
+    #[default]
+    ById,
+    /// Apps with the same name as an app from a higher priority provider are dropped
+    ByName,
+    /// Keep every app
+    KeepAll,
+}
+
+impl MergeRule {
+    /// Merge the results of providers, ordered from the highest priority to the lowest
+    pub fn merge(&self, results: Vec<Vec<App>>) -> Vec<App> {
+        let apps = results.into_iter().flatten();
+        match self {
+            MergeRule::ById => {
+                let mut seen = HashSet::new();
+                apps.filter(|app| seen.insert(app.app_desktop_path.clone()))
+                    .collect()
+            }
+            MergeRule::ByName => {
+                let mut seen = HashSet::new();
+                apps.filter(|app| seen.insert(app.name.clone())).collect()
+            }
+            MergeRule::KeepAll => apps.collect(),
+        }
+    }
+}
+
+/// The built-in provider: scans the platform's default search paths and the context's extra search paths
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchPathProvider;
+
+impl AppProvider for SearchPathProvider {
+    fn id(&self) -> &str {
+        "search-paths"
+    }
+
+    fn scan(&self, ctx: &AppInfoContext) -> Result<Vec<App>> {
+        // platforms scan their defaults their own way, e.g. with Spotlight on macOS
+        if ctx.replace_default_search_paths {
+            get_apps_in_search_paths(&ctx.search_paths())
+        } else {
+            get_all_apps(&ctx.extra_search_paths)
+        }
+    }
+
+    fn watch_paths(&self, ctx: &AppInfoContext) -> Vec<PathBuf> {
+        ctx.search_paths()
+            .into_iter()
+            .map(|search_path| search_path.path)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(name: &str, path: &str) -> App {
+        App {
+            name: name.to_string(),
+            app_desktop_path: PathBuf::from(path),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_merge_rules() {
+        let results = vec![
+            vec![app("Code", "/toolbox/code"), app("IDEA", "/toolbox/idea")],
+            vec![
+                app("Code", "/apps/code.desktop"),
+                app("IDEA", "/toolbox/idea"),
+            ],
+        ];
+        let paths = |apps: Vec<App>| -> Vec<PathBuf> {
+            apps.into_iter().map(|app| app.app_desktop_path).collect()
+        };
+        assert_eq!(
+            paths(MergeRule::ById.merge(results.clone())),
+            vec![
+                PathBuf::from("/toolbox/code"),
+                PathBuf::from("/toolbox/idea"),
+                PathBuf::from("/apps/code.desktop"),
+            ]
+        );
+        assert_eq!(
+            paths(MergeRule::ByName.merge(results.clone())),
+            vec![
+                PathBuf::from("/toolbox/code"),
+                PathBuf::from("/toolbox/idea")
+            ]
+        );
+        assert_eq!(MergeRule::KeepAll.merge(results).len(), 4);
+    }
+}
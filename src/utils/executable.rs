@@ -0,0 +1,298 @@
+//! Helpers for bare executable files, used by search paths of kind [`SearchPathKind::Executables`](crate::common::SearchPathKind::Executables)
+//! and to list the command line tools on `$PATH`
+use crate::common::{App, AppKind, AppSource};
+use crate::utils::parallel;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Check if a path is a file the current user could execute
+pub fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        match std::fs::metadata(path) {
+            Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+            Err(_) => false,
+        }
+    }
+    #[cfg(windows)]
+    {
+        let is_exe = path.extension().is_some_and(|ext| {
+            ["exe", "bat", "cmd", "com"]
+                .iter()
+                .any(|candidate| ext.eq_ignore_ascii_case(candidate))
+        });
+        is_exe && path.is_file()
+    }
+}
+
+/// Build an App for a bare executable, named after the file without its extension
+pub fn app_from_executable(path: &Path) -> Option<App> {
+    let name = path.file_stem()?.to_str()?.to_string();
+    Some(App {
+        name,
+        icon_path: None,
+        app_path_exe: Some(path.to_path_buf()),
+        app_desktop_path: path.to_path_buf(),
+        source: AppSource::Executable,
+        kind: AppKind::CommandLineTool { desktop_app: None },
+        url_schemes: vec![],
+        window_class: None,
+        bundle_id: None,
+    })
+}
+
+/// Directories listed in `$PATH`, in order, without duplicates and relative entries
+pub fn path_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = vec![];
+    if let Some(path) = std::env::var_os("PATH") {
+        for dir in std::env::split_paths(&path).filter(|dir| dir.is_absolute()) {
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+    }
+    dirs
+}
+
+/// Executables directly inside `dir`, sorted by file name
+fn executables_in_dir(dir: PathBuf) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| is_executable(path))
+            .collect(),
+        Err(_) => vec![],
+    };
+    paths.sort();
+    paths
+}
+
+/// List the executables in `dirs` as command line tools.
+/// When several directories contain an executable with the same name, the first one wins, like the shell does.
+pub fn find_executables(dirs: &[PathBuf]) -> Vec<App> {
+    let mut seen_names: HashSet<OsString> = HashSet::new();
+    parallel::map_ordered(dirs.to_vec(), executables_in_dir)
+        .into_iter()
+        .flatten()
+        .filter(|path| seen_names.insert(path.file_name().unwrap_or_default().to_os_string()))
+        .filter_map(|path| app_from_executable(&path))
+        .collect()
+}
+
+/// The program an app's executable runs. On Linux `app_path_exe` holds the `Exec` command line, which is split
+/// as the desktop entry spec says, skipping `env` and its variable assignments,
+/// e.g. `env BAMF_DESKTOP_FILE_HINT=/x.desktop /snap/bin/firefox` runs `/snap/bin/firefox`. Elsewhere it's a path.
+pub(crate) fn exec_program(exec: &Path) -> Option<PathBuf> {
+    #[cfg(target_os = "linux")]
+    {
+        let args = crate::platforms::launch::split_exec(exec.to_str()?).ok()?;
+        let mut args = args.into_iter().peekable();
+        if args.next_if(|arg| arg == "env").is_some() {
+            while args.next_if(|arg| arg.contains('=')).is_some() {}
+        }
+        args.next().map(PathBuf::from)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Some(exec.to_path_buf())
+    }
+}
+
+/// Link command line tools to the applications that run the same executable.
+/// An application's executable matches a tool if it's the tool's path, resolves to the same file, or is the tool's bare name.
+/// Flatpak apps are skipped: they run `flatpak run`, not their own executable.
+pub fn link_desktop_apps(tools: &mut [App], apps: &[App]) {
+    let mut by_program: HashMap<PathBuf, PathBuf> = HashMap::new();
+    for app in apps.iter().filter(|app| app.kind == AppKind::Application) {
+        if matches!(app.source, AppSource::Flatpak(_)) {
+            continue;
+        }
+        let Some(program) = app.app_path_exe.as_deref().and_then(exec_program) else {
+            continue;
+        };
+        let canonical = program.canonicalize().ok();
+        for key in std::iter::once(program).chain(canonical) {
+            by_program
+                .entry(key)
+                .or_insert_with(|| app.app_desktop_path.clone());
+        }
+    }
+    for tool in tools.iter_mut() {
+        let Some(path) = tool.app_path_exe.clone() else {
+            continue;
+        };
+        let candidates = [
+            Some(path.clone()),
+            path.canonicalize().ok(),
+            path.file_name().map(PathBuf::from),
+        ];
+        let desktop_app = candidates
+            .into_iter()
+            .flatten()
+            .find_map(|key| by_program.get(&key).cloned());
+        if let Some(app) =
+            desktop_app.and_then(|id| apps.iter().find(|app| app.app_desktop_path == id))
+        {
+            if tool.icon_path.is_none() {
+                tool.icon_path = app.icon_path.clone();
+            }
+            tool.kind = AppKind::CommandLineTool {
+                desktop_app: Some(app.app_desktop_path.clone()),
+            };
+        }
+    }
+}
+
+/// The executables on `$PATH` that aren't in `apps` already, linked to the app in `apps` that runs them, if any
+pub fn path_executables(apps: &[App]) -> Vec<App> {
+    let known: HashSet<&Path> = apps
+        .iter()
+        .map(|app| app.app_desktop_path.as_path())
+        .collect();
+    let mut tools: Vec<App> = find_executables(&path_dirs())
+        .into_iter()
+        .filter(|tool| !known.contains(tool.app_desktop_path.as_path()))
+        .collect();
+    link_desktop_apps(&mut tools, apps);
+    tools
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    fn write_executable(path: &Path) {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_executable() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = tempfile::tempdir().unwrap();
+        let script = tmp.path().join("script.sh");
+        std::fs::write(&script, "#!/bin/sh\n").unwrap();
+        assert!(!is_executable(&script));
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(is_executable(&script));
+        assert!(!is_executable(tmp.path()));
+
+        let app = app_from_executable(&script).unwrap();
+        assert_eq!(app.name, "script");
+        assert_eq!(app.app_path_exe, Some(script));
+        assert_eq!(app.kind, AppKind::CommandLineTool { desktop_app: None });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_executables() {
+        let tmp = tempfile::tempdir().unwrap();
+        let local = tmp.path().join("local/bin");
+        let usr = tmp.path().join("usr/bin");
+        write_executable(&local.join("rg"));
+        write_executable(&usr.join("rg"));
+        write_executable(&usr.join("code"));
+        std::fs::write(usr.join("README"), "").unwrap();
+
+        let tools = find_executables(&[local.clone(), usr.clone(), tmp.path().join("missing")]);
+        let paths: Vec<PathBuf> = tools
+            .iter()
+            .filter_map(|tool| tool.app_path_exe.clone())
+            .collect();
+        assert_eq!(paths, vec![local.join("rg"), usr.join("code")]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_exec_program() {
+        assert_eq!(exec_program(Path::new("code")), Some(PathBuf::from("code")));
+        assert_eq!(
+            exec_program(Path::new(
+                "env BAMF_DESKTOP_FILE_HINT=/x.desktop /snap/bin/firefox"
+            )),
+            Some(PathBuf::from("/snap/bin/firefox"))
+        );
+        assert_eq!(
+            exec_program(Path::new("\"/opt/idea/bin/idea\" --wait")),
+            Some(PathBuf::from("/opt/idea/bin/idea"))
+        );
+        assert_eq!(
+            exec_program(Path::new("\"/opt/My App/bin/app\" %U")),
+            Some(PathBuf::from("/opt/My App/bin/app"))
+        );
+        assert_eq!(
+            exec_program(Path::new("env \"/opt/My App/bin/app\"")),
+            Some(PathBuf::from("/opt/My App/bin/app"))
+        );
+        assert_eq!(exec_program(Path::new("\"/opt/unterminated")), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_link_desktop_apps() {
+        let tmp = tempfile::tempdir().unwrap();
+        let bin = tmp.path().join("bin");
+        write_executable(&tmp.path().join("opt/firefox/firefox"));
+        std::fs::create_dir_all(&bin).unwrap();
+        std::os::unix::fs::symlink(tmp.path().join("opt/firefox/firefox"), bin.join("firefox"))
+            .unwrap();
+        write_executable(&bin.join("code"));
+        write_executable(&bin.join("flatpak"));
+        write_executable(&bin.join("rg"));
+
+        let desktop_app = |name: &str, exec: PathBuf, source: AppSource| App {
+            name: name.to_string(),
+            icon_path: Some(PathBuf::from(format!("/icons/{name}.png"))),
+            app_path_exe: Some(exec),
+            app_desktop_path: PathBuf::from(format!("/apps/{name}.desktop")),
+            source,
+            ..Default::default()
+        };
+        let apps = vec![
+            desktop_app(
+                "Firefox",
+                tmp.path().join("opt/firefox/firefox"),
+                AppSource::Unknown,
+            ),
+            desktop_app(
+                "Code",
+                PathBuf::from("code --new-window"),
+                AppSource::Unknown,
+            ),
+            desktop_app(
+                "Calculator",
+                PathBuf::from("/usr/bin/flatpak run org.gnome.Calculator"),
+                AppSource::Flatpak(Default::default()),
+            ),
+        ];
+        let mut tools = find_executables(&[bin]);
+        link_desktop_apps(&mut tools, &apps);
+        let links: Vec<(&str, Option<PathBuf>)> = tools
+            .iter()
+            .map(|tool| match &tool.kind {
+                AppKind::CommandLineTool { desktop_app } => {
+                    (tool.name.as_str(), desktop_app.clone())
+                }
+                AppKind::Application => panic!("expected a command line tool"),
+            })
+            .collect();
+        assert_eq!(
+            links,
+            vec![
+                ("code", Some(PathBuf::from("/apps/Code.desktop"))),
+                ("firefox", Some(PathBuf::from("/apps/Firefox.desktop"))),
+                ("flatpak", None),
+                ("rg", None),
+            ]
+        );
+        assert_eq!(tools[0].icon_path, Some(PathBuf::from("/icons/Code.png")));
+        assert_eq!(tools[3].icon_path, None);
+    }
+}
@@ -0,0 +1,169 @@
+//! The `Info.plist` of macOS app bundles
+//!
+//! Parsing doesn't depend on macOS, so it's available, and tested, on every platform.
+use anyhow::Result;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use std::path::PathBuf;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CFBundlePrimaryIcon {
+    #[serde(rename = "CFBundleIconName")]
+    pub(crate) cf_bundle_icon_name: Option<String>,
+    #[serde(rename = "CFBundleIconFiles")]
+    pub(crate) cf_bundle_icon_files: Option<Vec<String>>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CFBundleIcons {
+    #[serde(rename = "CFBundlePrimaryIcon")]
+    pub(crate) cf_bundle_primary_icon: Option<CFBundlePrimaryIcon>,
+}
+
+/// An entry of `CFBundleURLTypes`, the URL schemes an app registers
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CFBundleURLType {
+    #[serde(rename = "CFBundleURLName")]
+    pub(crate) cf_bundle_url_name: Option<String>,
+    #[serde(rename = "CFBundleURLSchemes")]
+    pub(crate) cf_bundle_url_schemes: Option<Vec<String>>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct InfoPlist {
+    #[serde(rename = "CFBundleIconFile")]
+    pub(crate) cf_bundle_icon_file: Option<String>,
+    #[serde(rename = "CFBundleIcons")]
+    pub(crate) cf_bundle_icons: Option<CFBundleIcons>,
+    #[serde(rename = "CFBundleIcons~ipad")]
+    pub(crate) cf_bundle_icons_ipad: Option<CFBundleIcons>,
+    #[serde(rename = "CFBundleExecutable")]
+    pub(crate) cf_bundle_executable: Option<String>,
+    #[serde(rename = "CFBundleIconName")]
+    pub(crate) cf_bundle_icon_name: Option<String>,
+    #[serde(rename = "CFBundleIdentifier")]
+    pub(crate) cf_bundle_identifier: Option<String>,
+    #[serde(rename = "CFBundleInfoDictionaryVersion")]
+    pub(crate) cf_bundle_info_dictionary_version: Option<String>,
+    #[serde(rename = "CFBundleName")]
+    pub(crate) cf_bundle_name: Option<String>,
+    #[serde(rename = "CFBundlePackageType")]
+    pub(crate) cf_bundle_package_type: Option<String>,
+    #[serde(rename = "CFBundleShortVersionString")]
+    pub(crate) cf_bundle_short_version_string: Option<String>,
+    #[serde(rename = "CFBundleVersion")]
+    pub(crate) cf_bundle_version: Option<String>,
+    #[serde(rename = "CFBundleDisplayName")]
+    pub(crate) cf_bundle_display_name: Option<String>,
+    #[serde(rename = "CFBundleURLTypes")]
+    pub(crate) cf_bundle_url_types: Option<Vec<CFBundleURLType>>,
+}
+
+impl InfoPlist {
+    pub fn from_value(value: &plist::Value) -> Result<InfoPlist> {
+        let info_plist = plist::from_value(value).unwrap();
+        Ok(info_plist)
+    }
+
+    pub fn from_file(path: &PathBuf) -> Result<InfoPlist> {
+        match plist::from_file(path) {
+            Ok(info_plist) => Ok(info_plist),
+            Err(_) => match plist::Value::from_file(path) {
+                // using plist::Value is a workaround for the error "duplicate key: CFBundleShortVersionString"
+                Ok(value) => Ok(InfoPlist::from_value(&value).unwrap()),
+                Err(err) => Err(anyhow::Error::msg(format!("Fail to parse plist: {}", err))),
+            },
+        }
+    }
+
+    pub fn from_string(s: &str) -> Result<InfoPlist> {
+        Ok(plist::from_bytes(s.as_bytes()).expect("failed to read info.plist"))
+    }
+
+    /// URL schemes the app handles, lowercased, e.g. `zoommtg` for `zoommtg://` links
+    pub fn url_schemes(&self) -> Vec<String> {
+        let mut schemes: Vec<String> = vec![];
+        for scheme in self
+            .cf_bundle_url_types
+            .iter()
+            .flatten()
+            .filter_map(|url_type| url_type.cf_bundle_url_schemes.as_ref())
+            .flatten()
+        {
+            let scheme = scheme.to_ascii_lowercase();
+            if !schemes.contains(&scheme) {
+                schemes.push(scheme);
+            }
+        }
+        schemes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZOOM_INFO_PLIST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>CFBundleExecutable</key>
+    <string>zoom.us</string>
+    <key>CFBundleIdentifier</key>
+    <string>us.zoom.xos</string>
+    <key>CFBundleIconFile</key>
+    <string>ZPLogo.icns</string>
+    <key>CFBundleURLTypes</key>
+    <array>
+        <dict>
+            <key>CFBundleURLName</key>
+            <string>us.zoom.xos</string>
+            <key>CFBundleURLSchemes</key>
+            <array>
+                <string>zoommtg</string>
+                <string>ZoomUs</string>
+            </array>
+        </dict>
+        <dict>
+            <key>CFBundleURLName</key>
+            <string>Zoom Phone</string>
+            <key>CFBundleURLSchemes</key>
+            <array>
+                <string>zoomphonecall</string>
+                <string>zoommtg</string>
+            </array>
+        </dict>
+        <dict>
+            <key>CFBundleURLName</key>
+            <string>No schemes</string>
+        </dict>
+    </array>
+</dict>
+</plist>"#;
+
+    #[test]
+    fn test_url_schemes() {
+        let info_plist = InfoPlist::from_string(ZOOM_INFO_PLIST).unwrap();
+        assert_eq!(info_plist.cf_bundle_executable.as_deref(), Some("zoom.us"));
+        assert_eq!(
+            info_plist.url_schemes(),
+            vec!["zoommtg", "zoomus", "zoomphonecall"]
+        );
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("Info.plist");
+        std::fs::write(&path, ZOOM_INFO_PLIST).unwrap();
+        let from_file = InfoPlist::from_file(&path).unwrap();
+        assert_eq!(from_file, info_plist);
+
+        let without_url_types = InfoPlist::from_string(
+            r#"<?xml version="1.0" encoding="UTF-8"?><plist version="1.0"><dict><key>CFBundleName</key><string>Calculator</string></dict></plist>"#,
+        )
+        .unwrap();
+        assert!(without_url_types.url_schemes().is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::common::App;
+use crate::common::{App, AppSource};
 use anyhow::Result;
 use core_foundation::{bundle::CFBundle, url::CFURL};
 use glob::glob;
@@ -6,6 +6,8 @@ use serde_derive::Deserialize;
 use serde_derive::Serialize;
 use std::path::PathBuf;
 
+pub use crate::utils::info_plist::{CFBundleIcons, CFBundlePrimaryIcon, InfoPlist};
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MacSystemProfilerAppList {
@@ -30,76 +32,6 @@ pub struct MacSystemProfilterAppInfo {
     pub info: Option<String>,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub struct CFBundlePrimaryIcon {
-    #[serde(rename = "CFBundleIconName")]
-    cf_bundle_icon_name: Option<String>,
-    #[serde(rename = "CFBundleIconFiles")]
-    cf_bundle_icon_files: Option<Vec<String>>,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub struct CFBundleIcons {
-    #[serde(rename = "CFBundlePrimaryIcon")]
-    cf_bundle_primary_icon: Option<CFBundlePrimaryIcon>,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub struct InfoPlist {
-    #[serde(rename = "CFBundleIconFile")]
-    cf_bundle_icon_file: Option<String>,
-    #[serde(rename = "CFBundleIcons")]
-    cf_bundle_icons: Option<CFBundleIcons>,
-    #[serde(rename = "CFBundleIcons~ipad")]
-    cf_bundle_icons_ipad: Option<CFBundleIcons>,
-    #[serde(rename = "CFBundleExecutable")]
-    cf_bundle_executable: Option<String>,
-    #[serde(rename = "CFBundleIconName")]
-    cf_bundle_icon_name: Option<String>,
-    #[serde(rename = "CFBundleIdentifier")]
-    cf_bundle_identifier: Option<String>,
-    #[serde(rename = "CFBundleInfoDictionaryVersion")]
-    cf_bundle_info_dictionary_version: Option<String>,
-    #[serde(rename = "CFBundleName")]
-    cf_bundle_name: Option<String>,
-    #[serde(rename = "CFBundlePackageType")]
-    cf_bundle_package_type: Option<String>,
-    #[serde(rename = "CFBundleShortVersionString")]
-    cf_bundle_short_version_string: Option<String>,
-    #[serde(rename = "CFBundleVersion")]
-    cf_bundle_version: Option<String>,
-    #[serde(rename = "CFBundleDisplayName")]
-    cf_bundle_display_name: Option<String>,
-}
-
-impl InfoPlist {
-    pub fn from_value(value: &plist::Value) -> Result<InfoPlist> {
-        let info_plist = plist::from_value(value).unwrap();
-        Ok(info_plist)
-    }
-
-    pub fn from_file(path: &PathBuf) -> Result<InfoPlist> {
-        match plist::from_file(path) {
-            Ok(info_plist) => Ok(info_plist),
-            Err(_) => match plist::Value::from_file(path) {
-                // using plist::Value is a workaround for the error "duplicate key: CFBundleShortVersionString"
-                Ok(value) => Ok(InfoPlist::from_value(&value).unwrap()),
-                Err(err) => Err(anyhow::Error::msg(format!(
-                    "Fail to parse plist: {}",
-                    err.to_string()
-                ))),
-            },
-        }
-    }
-
-    pub fn from_string(s: &str) -> Result<InfoPlist> {
-        Ok(plist::from_bytes(s.as_bytes()).expect("failed to read info.plist"))
-    }
-}
-
 /// system_profiler command on mac is the simplest way I found to get a list of apps
 /// This function runs the command and returns the stdout
 pub fn run_system_profiler_to_get_app_list() -> Result<String> {
@@ -253,6 +185,8 @@ impl MacAppPath {
         // };
         // use path filename without .app extension
         let name = self.0.file_stem()?.to_str()?.to_string();
+        let url_schemes = info_plist.url_schemes();
+        let bundle_id = info_plist.cf_bundle_identifier.clone();
         let is_ios_app = self.has_wrapper();
         let icon_file_name = if is_ios_app {
             let icons = info_plist.cf_bundle_icons;
@@ -316,6 +250,14 @@ impl MacAppPath {
             icon_path,
             app_path_exe,
             app_desktop_path: self.0.clone(),
+            source: if self.0.starts_with("/System") {
+                AppSource::MacSystem
+            } else {
+                AppSource::MacApplication
+            },
+            url_schemes,
+            bundle_id,
+            ..Default::default()
         })
     }
 }
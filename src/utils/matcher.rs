@@ -0,0 +1,341 @@
+//! Match running processes and windows to installed apps
+//!
+//! Platforms know different things about a process: its executable, a .desktop file hint, a window class,
+//! a sandbox app ID or a bundle ID. Each one that points at an app gives it a score, and the app with the
+//! highest score wins. Scores are confidences between 0 and 1.
+use crate::common::{App, AppKind, AppSource};
+use crate::utils::executable::exec_program;
+use std::path::{Path, PathBuf};
+
+/// A .desktop file the process was launched from or a bundle ID names the app exactly
+const EXACT: f32 = 1.0;
+/// Sandbox IDs name the package, which may ship several apps
+const SANDBOX_ID: f32 = 0.95;
+const DESKTOP_FILE_ID: f32 = 0.9;
+const WINDOW_CLASS: f32 = 0.85;
+const EXE_PATH: f32 = 0.8;
+const WINDOW_CLASS_IS_DESKTOP_FILE_ID: f32 = 0.7;
+const PROGRAM_NAME: f32 = 0.6;
+const WINDOW_CLASS_IS_PROGRAM_NAME: f32 = 0.5;
+const WINDOW_CLASS_IS_APP_NAME: f32 = 0.4;
+/// Below the program name, so that an app started from a terminal isn't taken for the terminal
+const INHERITED_DESKTOP_FILE: f32 = 0.55;
+
+/// Interpreters and wrappers that many apps run through, so their name says nothing about the app
+const GENERIC_PROGRAMS: [&str; 11] = [
+    "bash", "electron", "env", "flatpak", "java", "node", "python", "python3", "sh", "snap", "wine",
+];
+
+/// What is known of a running process or of one of its windows
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessHints {
+    /// Path of the running executable
+    pub exe: Option<PathBuf>,
+    /// First argument of the command line
+    pub argv0: Option<String>,
+    /// The .desktop file the process was launched from
+    pub desktop_file: Option<PathBuf>,
+    /// The .desktop file an ancestor was launched from, which the process only inherited
+    pub inherited_desktop_file: Option<PathBuf>,
+    /// Desktop file IDs, without `.desktop`, the process is known by, e.g. from its systemd unit
+    pub desktop_file_ids: Vec<String>,
+    /// X11 `WM_CLASS` instance and class names, or the Wayland app ID
+    pub window_classes: Vec<String>,
+    pub flatpak_app_id: Option<String>,
+    /// Snap instance name and app name
+    pub snap_app: Option<(String, String)>,
+    /// macOS bundle identifier, e.g. `com.apple.Safari`
+    pub bundle_id: Option<String>,
+}
+
+/// An app matched to a process
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppMatch {
+    pub app: App,
+    /// Between 0 and 1, how sure the match is
+    pub confidence: f32,
+}
+
+/// The .desktop file ID of an app, without `.desktop`
+fn desktop_file_stem(app: &App) -> Option<&str> {
+    app.app_desktop_path
+        .extension()
+        .filter(|extension| *extension == "desktop")?;
+    app.app_desktop_path.file_stem()?.to_str()
+}
+
+/// The program an app runs, see [`exec_program`]
+fn app_program(app: &App) -> Option<PathBuf> {
+    app.app_path_exe.as_deref().and_then(exec_program)
+}
+
+fn file_name(path: &Path) -> Option<&str> {
+    path.file_name()?.to_str()
+}
+
+/// The .desktop file the process was launched from, unless its systemd unit names another app
+pub fn trusted_desktop_file(hints: &ProcessHints) -> Option<&Path> {
+    let desktop_file = hints.desktop_file.as_deref()?;
+    let stem = desktop_file.file_stem().and_then(|stem| stem.to_str());
+    let contradicted = !hints.desktop_file_ids.is_empty()
+        && stem.is_none_or(|stem| !hints.desktop_file_ids.iter().any(|id| id == stem));
+    (!contradicted).then_some(desktop_file)
+}
+
+/// How well a process matches an app, 0 if nothing points at it
+pub fn score(hints: &ProcessHints, app: &App) -> f32 {
+    let mut scores: Vec<f32> = vec![];
+    let stem = desktop_file_stem(app);
+
+    if trusted_desktop_file(hints) == Some(app.app_desktop_path.as_path()) {
+        scores.push(EXACT);
+    }
+    if hints.inherited_desktop_file.as_ref() == Some(&app.app_desktop_path) {
+        scores.push(INHERITED_DESKTOP_FILE);
+    }
+    if let (Some(bundle_id), Some(app_bundle_id)) = (&hints.bundle_id, &app.bundle_id) {
+        if bundle_id.eq_ignore_ascii_case(app_bundle_id) {
+            scores.push(EXACT);
+        }
+    }
+    match (&app.source, &hints.flatpak_app_id, &hints.snap_app) {
+        (AppSource::Flatpak(info), Some(app_id), _) if &info.app_id == app_id => {
+            // the main .desktop file of a Flatpak app is named after it
+            let main = stem == Some(app_id.as_str());
+            scores.push(if main { SANDBOX_ID } else { DESKTOP_FILE_ID });
+        }
+        (AppSource::Snap(info), _, Some((instance_name, app_name)))
+            if &info.instance_name == instance_name && &info.app == app_name =>
+        {
+            scores.push(SANDBOX_ID);
+        }
+        _ => {}
+    }
+    if stem.is_some_and(|stem| hints.desktop_file_ids.iter().any(|id| id == stem)) {
+        scores.push(DESKTOP_FILE_ID);
+    }
+
+    let program = app_program(app);
+    let program_name = program
+        .as_deref()
+        .and_then(file_name)
+        .filter(|name| !GENERIC_PROGRAMS.contains(name));
+    for class in &hints.window_classes {
+        if app
+            .window_class
+            .as_ref()
+            .is_some_and(|window_class| window_class.eq_ignore_ascii_case(class))
+        {
+            scores.push(WINDOW_CLASS);
+        } else if stem.is_some_and(|stem| stem.eq_ignore_ascii_case(class)) {
+            scores.push(WINDOW_CLASS_IS_DESKTOP_FILE_ID);
+        } else if program_name.is_some_and(|name| name.eq_ignore_ascii_case(class)) {
+            scores.push(WINDOW_CLASS_IS_PROGRAM_NAME);
+        } else if app.name.eq_ignore_ascii_case(class) {
+            scores.push(WINDOW_CLASS_IS_APP_NAME);
+        }
+    }
+
+    if let (Some(program), Some(program_name)) = (&program, program_name) {
+        let running = [hints.exe.clone(), hints.argv0.as_deref().map(PathBuf::from)];
+        for path in running.iter().flatten() {
+            if program.is_absolute() && program == path {
+                scores.push(EXE_PATH);
+            } else if file_name(path) == Some(program_name) {
+                scores.push(PROGRAM_NAME);
+            }
+        }
+    }
+
+    scores.into_iter().fold(0.0, f32::max)
+}
+
+/// The app a process most likely belongs to, the first one on ties. Command line tools are skipped.
+pub fn best_match(hints: &ProcessHints, apps: &[App]) -> Option<AppMatch> {
+    let mut best: Option<AppMatch> = None;
+    for app in apps.iter().filter(|app| app.kind == AppKind::Application) {
+        let confidence = score(hints, app);
+        if confidence > best.as_ref().map_or(0.0, |best| best.confidence) {
+            best = Some(AppMatch {
+                app: app.clone(),
+                confidence,
+            });
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{FlatpakInfo, SnapInfo};
+
+    fn app(desktop_file: &str, exec: &str) -> App {
+        App {
+            name: Path::new(desktop_file)
+                .file_stem()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned(),
+            app_path_exe: Some(PathBuf::from(exec)),
+            app_desktop_path: PathBuf::from(desktop_file),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_best_match() {
+        let firefox = app("/usr/share/applications/firefox.desktop", "firefox");
+        let nautilus = App {
+            window_class: Some("org.gnome.Nautilus".to_string()),
+            ..app(
+                "/usr/share/applications/org.gnome.Nautilus.desktop",
+                "nautilus --new-window",
+            )
+        };
+        let code = app(
+            "/usr/share/applications/code.desktop",
+            "/usr/share/code/code",
+        );
+        let calculator = App {
+            source: AppSource::Flatpak(FlatpakInfo {
+                app_id: "org.gnome.Calculator".to_string(),
+                ..Default::default()
+            }),
+            ..app(
+                "/var/lib/flatpak/exports/share/applications/org.gnome.Calculator.desktop",
+                "/usr/bin/flatpak run --branch=stable org.gnome.Calculator",
+            )
+        };
+        let spotify = App {
+            source: AppSource::Snap(SnapInfo {
+                instance_name: "spotify".to_string(),
+                app: "spotify".to_string(),
+                ..Default::default()
+            }),
+            ..app(
+                "/var/lib/snapd/desktop/applications/spotify_spotify.desktop",
+                "env BAMF_DESKTOP_FILE_HINT=/var/lib/snapd/desktop/applications/spotify_spotify.desktop /snap/bin/spotify",
+            )
+        };
+        let apps = vec![
+            firefox.clone(),
+            nautilus.clone(),
+            code.clone(),
+            calculator.clone(),
+            spotify.clone(),
+        ];
+        let best = |hints: ProcessHints| {
+            best_match(&hints, &apps).map(|found| (found.app.name, found.confidence))
+        };
+
+        assert_eq!(
+            best(ProcessHints {
+                exe: Some(PathBuf::from("/usr/lib/firefox/firefox")),
+                desktop_file: Some(firefox.app_desktop_path.clone()),
+                ..Default::default()
+            }),
+            Some(("firefox".to_string(), EXACT))
+        );
+        assert_eq!(
+            best(ProcessHints {
+                exe: Some(PathBuf::from("/usr/lib/firefox/firefox")),
+                ..Default::default()
+            }),
+            Some(("firefox".to_string(), PROGRAM_NAME))
+        );
+        assert_eq!(
+            best(ProcessHints {
+                window_classes: vec!["org.gnome.Nautilus".to_string()],
+                ..Default::default()
+            }),
+            Some(("org.gnome.Nautilus".to_string(), WINDOW_CLASS))
+        );
+        assert_eq!(
+            best(ProcessHints {
+                exe: Some(PathBuf::from("/usr/share/code/code")),
+                window_classes: vec!["code".to_string(), "Code".to_string()],
+                ..Default::default()
+            }),
+            Some(("code".to_string(), EXE_PATH))
+        );
+        assert_eq!(
+            best(ProcessHints {
+                exe: Some(PathBuf::from("/app/bin/gnome-calculator")),
+                flatpak_app_id: Some("org.gnome.Calculator".to_string()),
+                ..Default::default()
+            }),
+            Some(("org.gnome.Calculator".to_string(), SANDBOX_ID))
+        );
+        assert_eq!(
+            best(ProcessHints {
+                snap_app: Some(("spotify".to_string(), "spotify".to_string())),
+                ..Default::default()
+            }),
+            Some(("spotify_spotify".to_string(), SANDBOX_ID))
+        );
+        assert_eq!(
+            best(ProcessHints {
+                desktop_file_ids: vec!["gnome-firefox".to_string(), "firefox".to_string()],
+                ..Default::default()
+            }),
+            Some(("firefox".to_string(), DESKTOP_FILE_ID))
+        );
+        // the flatpak wrapper is shared by all Flatpak apps
+        assert_eq!(
+            best(ProcessHints {
+                exe: Some(PathBuf::from("/usr/bin/flatpak")),
+                ..Default::default()
+            }),
+            None
+        );
+        // the systemd unit wins over the .desktop file the process says it was launched from
+        assert_eq!(
+            best(ProcessHints {
+                desktop_file: Some(nautilus.app_desktop_path.clone()),
+                desktop_file_ids: vec!["gnome-firefox".to_string(), "firefox".to_string()],
+                ..Default::default()
+            }),
+            Some(("firefox".to_string(), DESKTOP_FILE_ID))
+        );
+        // started from a file manager's environment, but running its own program
+        assert_eq!(
+            best(ProcessHints {
+                exe: Some(PathBuf::from("/usr/lib/firefox/firefox")),
+                inherited_desktop_file: Some(nautilus.app_desktop_path.clone()),
+                ..Default::default()
+            }),
+            Some(("firefox".to_string(), PROGRAM_NAME))
+        );
+        assert_eq!(
+            best(ProcessHints {
+                exe: Some(PathBuf::from("/usr/bin/bash")),
+                inherited_desktop_file: Some(nautilus.app_desktop_path.clone()),
+                ..Default::default()
+            }),
+            Some(("org.gnome.Nautilus".to_string(), INHERITED_DESKTOP_FILE))
+        );
+        assert_eq!(
+            best(ProcessHints {
+                exe: Some(PathBuf::from("/usr/sbin/sshd")),
+                ..Default::default()
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn test_bundle_id() {
+        let safari = App {
+            name: "Safari".to_string(),
+            bundle_id: Some("com.apple.Safari".to_string()),
+            app_desktop_path: PathBuf::from("/Applications/Safari.app"),
+            ..Default::default()
+        };
+        let hints = ProcessHints {
+            bundle_id: Some("com.apple.safari".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(score(&hints, &safari), EXACT);
+    }
+}
@@ -1,3 +1,8 @@
+pub mod executable;
 pub mod image;
+pub mod info_plist;
 #[cfg(target_os = "macos")]
 pub mod mac;
+pub mod matcher;
+pub mod parallel;
+pub mod xdg;
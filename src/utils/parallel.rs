@@ -0,0 +1,69 @@
+//! Small helpers used by the scanners to fan work out over a thread pool.
+//! With the `parallel` feature enabled the work runs on rayon's global pool,
+//! otherwise everything runs sequentially on the calling thread.
+//! Either way, results come back in the same order as the input, so callers can rely on it for precedence.
+
+/// Map `f` over `items` and collect the results in input order.
+pub fn map_ordered<T, R, F>(items: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        items.into_par_iter().map(f).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        items.into_iter().map(f).collect()
+    }
+}
+
+/// Like [`map_ordered`], but drops the items for which `f` returns `None`.
+pub fn filter_map_ordered<T, R, F>(items: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> Option<R> + Sync + Send,
+{
+    map_ordered(items, f).into_iter().flatten().collect()
+}
+
+/// Run two closures, potentially in parallel, and return both results.
+pub fn join<A, B, RA, RB>(a: A, b: B) -> (RA, RB)
+where
+    A: FnOnce() -> RA + Send,
+    B: FnOnce() -> RB + Send,
+    RA: Send,
+    RB: Send,
+{
+    #[cfg(feature = "parallel")]
+    {
+        rayon::join(a, b)
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        (a(), b())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_ordered_keeps_order() {
+        let items: Vec<u32> = (0..1000).collect();
+        let doubled = map_ordered(items, |x| x * 2);
+        assert_eq!(doubled, (0..1000).map(|x| x * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_filter_map_ordered() {
+        let items: Vec<u32> = (0..10).collect();
+        let odd = filter_map_ordered(items, |x| if x % 2 == 1 { Some(x) } else { None });
+        assert_eq!(odd, vec![1, 3, 5, 7, 9]);
+    }
+}
@@ -0,0 +1,243 @@
+//! XDG Base Directory Specification
+//!
+//! <https://specifications.freedesktop.org/basedir-spec/latest/>
+//!
+//! Each variable falls back to its default from the spec when it's unset or empty.
+//! Relative paths are invalid according to the spec and are ignored.
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BaseDirectories {
+    pub home: Option<PathBuf>,
+    /// `$XDG_DATA_HOME`, defaults to `$HOME/.local/share`
+    pub data_home: Option<PathBuf>,
+    /// `$XDG_DATA_DIRS`, defaults to `/usr/local/share:/usr/share`
+    pub data_dirs: Vec<PathBuf>,
+    /// `$XDG_CONFIG_HOME`, defaults to `$HOME/.config`
+    pub config_home: Option<PathBuf>,
+    /// `$XDG_CONFIG_DIRS`, defaults to `/etc/xdg`
+    pub config_dirs: Vec<PathBuf>,
+    /// `$XDG_CACHE_HOME`, defaults to `$HOME/.cache`
+    pub cache_home: Option<PathBuf>,
+    /// `$XDG_STATE_HOME`, defaults to `$HOME/.local/state`
+    pub state_home: Option<PathBuf>,
+    /// `$XDG_RUNTIME_DIR`, the spec doesn't define a default
+    pub runtime_dir: Option<PathBuf>,
+}
+
+fn absolute_path(value: &str) -> Option<PathBuf> {
+    let path = Path::new(value);
+    if path.is_absolute() {
+        Some(path.to_path_buf())
+    } else {
+        None
+    }
+}
+
+/// Split a colon separated list of paths, keeping only the first occurrence of each absolute path
+fn path_list(value: &str) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = vec![];
+    for path in value.split(':').filter_map(absolute_path) {
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+impl BaseDirectories {
+    /// Read the base directories from the environment of the current process
+    pub fn new() -> Self {
+        Self::from_env(|key| std::env::var(key).ok())
+    }
+
+    /// Build the base directories from an environment lookup function, e.g. for another process' environment or tests
+    pub fn from_env<F>(lookup: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let var = |key: &str| lookup(key).filter(|value| !value.is_empty());
+        let home = var("HOME").and_then(|home| absolute_path(&home));
+        let home_dir = |key: &str, default: &str| {
+            var(key)
+                .and_then(|value| absolute_path(&value))
+                .or_else(|| home.as_ref().map(|home| home.join(default)))
+        };
+        let dir_list = |key: &str, default: &str| {
+            let dirs = var(key).map(|value| path_list(&value)).unwrap_or_default();
+            if dirs.is_empty() {
+                path_list(default)
+            } else {
+                dirs
+            }
+        };
+
+        BaseDirectories {
+            data_home: home_dir("XDG_DATA_HOME", ".local/share"),
+            data_dirs: dir_list("XDG_DATA_DIRS", "/usr/local/share:/usr/share"),
+            config_home: home_dir("XDG_CONFIG_HOME", ".config"),
+            config_dirs: dir_list("XDG_CONFIG_DIRS", "/etc/xdg"),
+            cache_home: home_dir("XDG_CACHE_HOME", ".cache"),
+            state_home: home_dir("XDG_STATE_HOME", ".local/state"),
+            runtime_dir: var("XDG_RUNTIME_DIR").and_then(|value| absolute_path(&value)),
+            home,
+        }
+    }
+
+    /// `$XDG_DATA_HOME` followed by `$XDG_DATA_DIRS`, in order of preference
+    pub fn data_search_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = self.data_home.iter().cloned().collect();
+        for dir in &self.data_dirs {
+            if !dirs.contains(dir) {
+                dirs.push(dir.clone());
+            }
+        }
+        dirs
+    }
+
+    /// `$XDG_CONFIG_HOME` followed by `$XDG_CONFIG_DIRS`, in order of preference
+    pub fn config_search_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = self.config_home.iter().cloned().collect();
+        for dir in &self.config_dirs {
+            if !dirs.contains(dir) {
+                dirs.push(dir.clone());
+            }
+        }
+        dirs
+    }
+
+    /// `subdir` inside each data directory, in order of preference, e.g. `applications` or `icons`
+    pub fn data_subdirs(&self, subdir: &str) -> Vec<PathBuf> {
+        self.data_search_dirs()
+            .into_iter()
+            .map(|dir| dir.join(subdir))
+            .collect()
+    }
+
+    /// Find the first existing file at `relative_path` inside the data directories
+    pub fn find_data_file(&self, relative_path: &str) -> Option<PathBuf> {
+        self.data_subdirs(relative_path)
+            .into_iter()
+            .find(|path| path.exists())
+    }
+
+    /// Find the first existing file at `relative_path` inside the config directories
+    pub fn find_config_file(&self, relative_path: &str) -> Option<PathBuf> {
+        self.config_search_dirs()
+            .into_iter()
+            .map(|dir| dir.join(relative_path))
+            .find(|path| path.exists())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn from_vars(vars: &[(&str, &str)]) -> BaseDirectories {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        BaseDirectories::from_env(|key| vars.get(key).cloned())
+    }
+
+    #[test]
+    fn test_defaults() {
+        let dirs = from_vars(&[("HOME", "/home/me")]);
+        assert_eq!(dirs.data_home, Some(PathBuf::from("/home/me/.local/share")));
+        assert_eq!(
+            dirs.data_dirs,
+            vec![
+                PathBuf::from("/usr/local/share"),
+                PathBuf::from("/usr/share")
+            ]
+        );
+        assert_eq!(dirs.config_home, Some(PathBuf::from("/home/me/.config")));
+        assert_eq!(dirs.config_dirs, vec![PathBuf::from("/etc/xdg")]);
+        assert_eq!(dirs.cache_home, Some(PathBuf::from("/home/me/.cache")));
+        assert_eq!(
+            dirs.state_home,
+            Some(PathBuf::from("/home/me/.local/state"))
+        );
+        assert_eq!(dirs.runtime_dir, None);
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let dirs = from_vars(&[
+            ("HOME", "/home/me"),
+            ("XDG_DATA_HOME", "/data/home"),
+            (
+                "XDG_DATA_DIRS",
+                "/opt/share:relative/share::/usr/share:/opt/share",
+            ),
+            ("XDG_CONFIG_HOME", "relative/config"),
+            ("XDG_CONFIG_DIRS", ""),
+            ("XDG_RUNTIME_DIR", "/run/user/1000"),
+        ]);
+        assert_eq!(dirs.data_home, Some(PathBuf::from("/data/home")));
+        // relative and duplicated entries are dropped
+        assert_eq!(
+            dirs.data_dirs,
+            vec![PathBuf::from("/opt/share"), PathBuf::from("/usr/share")]
+        );
+        // relative paths are invalid, fall back to the default
+        assert_eq!(dirs.config_home, Some(PathBuf::from("/home/me/.config")));
+        assert_eq!(dirs.config_dirs, vec![PathBuf::from("/etc/xdg")]);
+        assert_eq!(dirs.runtime_dir, Some(PathBuf::from("/run/user/1000")));
+        assert_eq!(
+            dirs.data_subdirs("applications"),
+            vec![
+                PathBuf::from("/data/home/applications"),
+                PathBuf::from("/opt/share/applications"),
+                PathBuf::from("/usr/share/applications"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_without_home() {
+        let dirs = from_vars(&[]);
+        assert_eq!(dirs.home, None);
+        assert_eq!(dirs.data_home, None);
+        assert_eq!(
+            dirs.data_search_dirs(),
+            vec![
+                PathBuf::from("/usr/local/share"),
+                PathBuf::from("/usr/share")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let home = tmp.path().join("home");
+        let system = tmp.path().join("system");
+        std::fs::create_dir_all(home.join(".config")).unwrap();
+        std::fs::create_dir_all(system.join("etc/xdg")).unwrap();
+        std::fs::create_dir_all(system.join("share/mime")).unwrap();
+        std::fs::write(home.join(".config/mimeapps.list"), "").unwrap();
+        std::fs::write(system.join("etc/xdg/mimeapps.list"), "").unwrap();
+        std::fs::write(system.join("share/mime/globs2"), "").unwrap();
+
+        let config_dirs = system.join("etc/xdg");
+        let data_dirs = system.join("share");
+        let dirs = from_vars(&[
+            ("HOME", home.to_str().unwrap()),
+            ("XDG_CONFIG_DIRS", config_dirs.to_str().unwrap()),
+            ("XDG_DATA_DIRS", data_dirs.to_str().unwrap()),
+        ]);
+        assert_eq!(
+            dirs.find_config_file("mimeapps.list"),
+            Some(home.join(".config/mimeapps.list"))
+        );
+        assert_eq!(
+            dirs.find_data_file("mime/globs2"),
+            Some(system.join("share/mime/globs2"))
+        );
+        assert_eq!(dirs.find_data_file("mime/magic"), None);
+    }
+}